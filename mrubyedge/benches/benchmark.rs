@@ -1,154 +1,88 @@
-// use criterion::{criterion_group, criterion_main, Criterion};
-// use mrubyedge::*;
-
-// use std::{any::Any, cell::RefCell, rc::Rc};
-
-// fn bm0_load(c: &mut Criterion) {
-//     let bin = include_bytes!("./fib.mrb");
-//     c.bench_function("Load time", |b| {
-//         b.iter(|| {
-//             let _ = mrubyedge::rite::load(bin).unwrap();
-//         })
-//     });
-// }
-
-// fn bm0_prelude(c: &mut Criterion) {
-//     let bin = include_bytes!("./fib.mrb");
-//     let rite = mrubyedge::rite::load(bin).unwrap();
-//     let mut vm = mrubyedge::vm::VM::open(rite);
-//     c.bench_function("Prelude time", |b| {
-//         b.iter(|| {
-//             vm.prelude().unwrap();
-//         })
-//     });
-// }
-
-// fn bm0_eval(c: &mut Criterion) {
-//     let bin = include_bytes!("./fib.mrb");
-//     let rite = mrubyedge::rite::load(bin).unwrap();
-//     let mut vm = mrubyedge::vm::VM::open(rite);
-//     vm.prelude().unwrap();
-//     c.bench_function("Eval time", |b| {
-//         b.iter(|| {
-//             vm.eval_insn().unwrap();
-//         })
-//     });
-// }
-
-// fn bm2(c: &mut Criterion) {
-//     let bin = include_bytes!("./fib.mrb");
-//     let rite = mrubyedge::rite::load(bin).unwrap();
-//     let mut vm = mrubyedge::vm::VM::open(rite);
-//     vm.prelude().unwrap();
-//     vm.eval_insn().unwrap();
-
-//     let objclass_sym = vm.target_class.unwrap() as usize;
-//     let top_self = vm::RObject::RInstance {
-//         class_index: objclass_sym,
-//         data: Rc::new(RefCell::new(Box::new(()) as Box<dyn Any>)),
-//     };
-//     let args1 = vec![Rc::new(vm::RObject::RInteger(1))];
-//     let args5 = vec![Rc::new(vm::RObject::RInteger(5))];
-//     // let args10 = vec![Rc::new(vm::RObject::RInteger(10))];
-//     // let args15 = vec![Rc::new(vm::RObject::RInteger(15))];
-//     // let args20 = vec![Rc::new(vm::RObject::RInteger(20))];
-
-//     c.bench_function("Fib 1", |b| {
-//         b.iter(|| {
-//             match mrb_helper::mrb_funcall(&mut vm, &top_self, "fib".to_string(), &args1) {
-//                 Ok(_) => {
-//                     // OK
-//                 }
-//                 Err(ex) => {
-//                     dbg!(ex);
-//                 }
-//             };
-//         })
-//     });
-
-//     c.bench_function("Fib 5", |b| {
-//         b.iter(|| {
-//             match mrb_helper::mrb_funcall(&mut vm, &top_self, "fib".to_string(), &args5) {
-//                 Ok(_) => {
-//                     // OK
-//                 }
-//                 Err(ex) => {
-//                     dbg!(ex);
-//                 }
-//             };
-//         })
-//     });
-
-//     // c.bench_function("Fib 10", |b| {
-//     //     b.iter(|| {
-//     //         match mrb_helper::mrb_funcall(&mut vm, &top_self, "fib".to_string(), &args10) {
-//     //             Ok(_) => {
-//     //                 // OK
-//     //             }
-//     //             Err(ex) => {
-//     //                 dbg!(ex);
-//     //             }
-//     //         };
-//     //     })
-//     // });
-
-//     // c.bench_function("Fib 15", |b| {
-//     //     b.iter(|| {
-//     //         match mrb_helper::mrb_funcall(&mut vm, &top_self, "fib".to_string(), &args15) {
-//     //             Ok(_) => {
-//     //                 // OK
-//     //             }
-//     //             Err(ex) => {
-//     //                 dbg!(ex);
-//     //             }
-//     //         };
-//     //     })
-//     // });
-
-//     // c.bench_function("Fib 20", |b| {
-//     //     b.iter(|| {
-//     //         match mrb_helper::mrb_funcall(&mut vm, &top_self, "fib".to_string(), &args20) {
-//     //             Ok(_) => {
-//     //                 // OK
-//     //             }
-//     //             Err(ex) => {
-//     //                 dbg!(ex);
-//     //             }
-//     //         };
-//     //     })
-//     // });
-// }
-
-// // fn bm3(c: &mut Criterion) {
-// //     let bin = include_bytes!("./long.mrb");
-// //     let rite = mrubyedge::rite::load(bin).unwrap();
-// //     let mut vm = mrubyedge::vm::VM::open(rite);
-// //     vm.prelude().unwrap();
-// //     vm.eval_insn().unwrap();
-
-// //     let objclass_sym = vm.target_class.unwrap() as usize;
-// //     let top_self = vm::RObject::RInstance {
-// //         class_index: objclass_sym,
-// //         data: Rc::new(RefCell::new(Box::new(()) as Box<dyn Any>)),
-// //     };
-// //     let args = vec![];
-
-// //     c.bench_function("Long inst", |b| {
-// //         b.iter(|| {
-// //             match mrb_helper::mrb_funcall(&mut vm, &top_self, "long".to_string(), &args) {
-// //                 Ok(_) => {
-// //                     // OK
-// //                 }
-// //                 Err(ex) => {
-// //                     dbg!(ex);
-// //                 }
-// //             };
-// //         })
-// //     });
-// // }
-
-// // criterion_group!(benches, bm1, bm2, bm3);
-// criterion_group!(benches, bm0_load, bm0_prelude, bm0_eval, bm2);
-// criterion_main!(benches);
-
-fn main () {}
\ No newline at end of file
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use mrubyedge::yamrb::helpers::mrb_funcall;
+use mrubyedge::yamrb::vm::VM;
+
+#[path = "../tests/helpers/mod.rs"]
+mod helpers;
+use helpers::*;
+
+fn vm_for(name: &'static str, code: &'static str) -> VM {
+    let binary = mrbc_compile(name, code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+    vm.run().unwrap();
+    vm
+}
+
+fn bm_fib(c: &mut Criterion) {
+    let mut vm = vm_for("bench_fib", include_str!("./fib.rb"));
+    let args = vec![int(15)];
+    c.bench_function("fib(15)", |b| {
+        b.iter(|| mrb_funcall(&mut vm, None, "fib", &args).unwrap())
+    });
+}
+
+// Methods found deep in the ancestors are the slowest to look up
+fn bm_deep_ancestors(c: &mut Criterion) {
+    let code = "
+    module M1; end
+    module M2; end
+    module M3; end
+    class A
+      def value
+        1
+      end
+    end
+    class B < A
+      include M1
+    end
+    class C < B
+      include M2
+      include M3
+    end
+
+    def calls(obj)
+      i = 0
+      sum = 0
+      while i < 1000
+        sum += obj.value
+        i += 1
+      end
+      sum
+    end
+
+    def bench
+      calls(C.new)
+    end
+    ";
+    let mut vm = vm_for("bench_deep_ancestors", code);
+    let args = vec![];
+    c.bench_function("1000 calls through ancestors", |b| {
+        b.iter(|| mrb_funcall(&mut vm, None, "bench", &args).unwrap())
+    });
+}
+
+fn bm_loop(c: &mut Criterion) {
+    let code = "
+    def bench
+      i = 0
+      sum = 0
+      while i < 10000
+        if i % 2 == 0
+          sum += i
+        end
+        i += 1
+      end
+      sum
+    end
+    ";
+    let mut vm = vm_for("bench_loop", code);
+    let args = vec![];
+    c.bench_function("while loop of 10000", |b| {
+        b.iter(|| mrb_funcall(&mut vm, None, "bench", &args).unwrap())
+    });
+}
+
+criterion_group!(benches, bm_fib, bm_deep_ancestors, bm_loop);
+criterion_main!(benches);
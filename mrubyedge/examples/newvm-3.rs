@@ -73,7 +73,10 @@ fn main() {
         syms: vec![value::RSym::new("fib".to_string())],
         pool: Vec::new(),
         reps: Vec::new(),
-        catch_target_pos: Vec::new(),
+        catch_handlers: Vec::new(),
+        debug_info: None,
+        lv: Vec::new(),
+        method_cache: method_cache::MethodCache::default(),
     };
 
     // irep0:
@@ -104,7 +107,10 @@ fn main() {
         syms: vec![value::RSym::new("fib".to_string())],
         pool: Vec::new(),
         reps: vec![Rc::new(irep1)],
-        catch_target_pos: Vec::new(),
+        catch_handlers: Vec::new(),
+        debug_info: None,
+        lv: Vec::new(),
+        method_cache: method_cache::MethodCache::default(),
     };
     let mut vm = vm::VM::new_by_raw_irep(irep0);
     let ret = vm.run().unwrap();
@@ -38,7 +38,10 @@ fn main() {
         ],
         pool: Vec::new(),
         reps: Vec::new(),
-        catch_target_pos: Vec::new(),
+        catch_handlers: Vec::new(),
+        debug_info: None,
+        lv: Vec::new(),
+        method_cache: method_cache::MethodCache::default(),
     };
     let mut vm = vm::VM::new_by_raw_irep(irep);
     let ret = vm.run().unwrap();
@@ -2,7 +2,7 @@ use std::error;
 use std::fmt;
 use std::rc::Rc;
 
-use crate::yamrb::value::RClass;
+use crate::yamrb::value::{RClass, RException};
 use crate::yamrb::vm::VM;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -12,8 +12,24 @@ pub enum Error {
     InvalidOpCode,
     RuntimeError(String),
     TypeMismatch,
+    TypeError(String),
     NoMethodError(String),
     NameError(String),
+    ArgumentError(String),
+    RangeError(String),
+    NoMemoryError(String),
+    SystemStackError(String),
+    ZeroDivisionError(String),
+    LocalJumpError(String),
+    FiberError(String),
+    // non-local exit from a block (break, return); see VM::break_object
+    Break,
+    // an exception object raised from Ruby
+    Exception(Rc<RException>),
+    // the instruction budget ran out; see VM::set_fuel and VM::step
+    OutOfFuel,
+    // a snapshot not restorable into the VM; see VM::restore
+    InvalidSnapshot(String),
 }
 
 impl fmt::Display for Error {
@@ -36,8 +52,39 @@ impl Error {
             Error::InvalidOpCode => "Invalid opcode".to_string(),
             Error::RuntimeError(msg) => msg.clone(),
             Error::TypeMismatch => "Type mismatch".to_string(),
+            Error::TypeError(msg) => msg.clone(),
             Error::NoMethodError(msg) => format!("Method not found: {}", msg),
             Error::NameError(msg) => format!("Cannot found name: {}", msg),
+            Error::ArgumentError(msg) => msg.clone(),
+            Error::RangeError(msg) => msg.clone(),
+            Error::NoMemoryError(msg) => msg.clone(),
+            Error::SystemStackError(msg) => msg.clone(),
+            Error::ZeroDivisionError(msg) => msg.clone(),
+            Error::LocalJumpError(msg) => msg.clone(),
+            Error::FiberError(msg) => msg.clone(),
+            Error::Break => "break from proc-closure".to_string(),
+            Error::Exception(e) => e.message.borrow().clone(),
+            Error::OutOfFuel => "instruction budget exhausted".to_string(),
+            Error::InvalidSnapshot(msg) => format!("invalid snapshot: {}", msg),
+        }
+    }
+
+    // Ruby backtrace of the raised exception, if recorded
+    pub fn backtrace(&self) -> Vec<String> {
+        match self {
+            Error::Exception(e) => e.backtrace.borrow().clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    // The variant the VM raised this error as, e.g. Error::NoMethodError
+    // for an Error::Exception carrying its backtrace. Exceptions raised
+    // from Ruby stay Error::Exception.
+    pub fn kind(&self) -> Error {
+        match self {
+            Error::Exception(e) => e.error_type.borrow().clone()
+                .unwrap_or_else(|| self.clone()),
+            e => e.clone(),
         }
     }
 
@@ -48,8 +95,19 @@ impl Error {
             (Error::InvalidOpCode, "StandardError") => true,
             (Error::RuntimeError(_), "RuntimeError") => true,
             (Error::TypeMismatch, "StandardError") => true,
+            (Error::TypeError(_), "TypeError") => true,
             (Error::NoMethodError(_), "NoMethodError") => true,
             (Error::NameError(_), "NameError") => true,
+            (Error::ArgumentError(_), "ArgumentError") => true,
+            (Error::RangeError(_), "RangeError") => true,
+            (Error::NoMemoryError(_), "NoMemoryError") => true,
+            (Error::SystemStackError(_), "SystemStackError") => true,
+            (Error::ZeroDivisionError(_), "ZeroDivisionError") => true,
+            (Error::LocalJumpError(_), "LocalJumpError") => true,
+            (Error::FiberError(_), "FiberError") => true,
+            (Error::Break, "LocalJumpError") => true,
+            (Error::InvalidSnapshot(_), "LoadError") => true,
+            (Error::Exception(e), name) => e.class.sym_id.name == name,
             _ => false,
         }
     }
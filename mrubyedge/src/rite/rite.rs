@@ -24,12 +24,34 @@ pub struct Irep<'a> {
     pub header: IrepRecord,
     pub insn: &'a [u8],
     pub plen: usize,
-    pub strvals: Vec<CString>,
+    pub pool: Vec<PoolValue>,
     pub slen: usize,
     pub syms: Vec<CString>,
     pub catch_handlers: Vec<CatchHandler>,
+    pub debug_info: Option<DebugInfo>,
+    // local variable names of R1, R2, ...; None for unnamed ones
+    pub lv: Vec<Option<String>>,
 }
 
+// A literal in the irep pool; see IREP_TT_* in mruby/irep.h
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoolValue {
+    Str(CString),
+    StaticStr(CString),
+    Int32(i32),
+    Int64(i64),
+    Float(f64),
+    // digits are ASCII in the given base; a negative base means a negative number
+    BigInt { base: i8, digits: Vec<u8> },
+}
+
+const IREP_TT_STR: u8 = 0;
+const IREP_TT_INT32: u8 = 1;
+const IREP_TT_SSTR: u8 = 2;
+const IREP_TT_INT64: u8 = 3;
+const IREP_TT_FLOAT: u8 = 5;
+const IREP_TT_BIGINT: u8 = 7;
+
 impl Irep<'_> {
     pub fn nlocals(&self) -> usize {
         be16_to_u16(self.header.nlocals) as usize
@@ -56,9 +78,33 @@ pub struct CatchHandler {
     pub target: usize,
 }
 
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    pub files: Vec<DebugInfoFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugInfoFile {
+    pub start_pos: usize,
+    pub filename: String,
+    // (pos, line) sorted by pos: ops from pos on are in the line
+    pub lines: Vec<(usize, usize)>,
+}
+
+impl DebugInfo {
+    // file name and line of the op at the byte position
+    pub fn lookup(&self, pos: usize) -> Option<(&str, usize)> {
+        let file = self.files.iter().rev().find(|f| f.start_pos <= pos)?;
+        let (_, line) = file.lines.iter().rev().find(|(p, _)| *p <= pos)?;
+        Some((file.filename.as_str(), *line))
+    }
+}
+
 #[derive(Debug)]
 pub struct LVar {
     pub header: SectionMiscHeader,
+    // the names referred from the records of each irep
+    pub syms: Vec<String>,
 }
 
 pub fn load<'a>(src: &'a [u8]) -> Result<Rite<'a>, Error> {
@@ -94,12 +140,12 @@ pub fn load<'a>(src: &'a [u8]) -> Result<Rite<'a>, Error> {
                     head = &head[cur..];
                 }
                 LVAR => {
-                    let (cur, lvar) = section_lvar(head)?;
+                    let (cur, lvar) = section_lvar(head, &mut rite.irep)?;
                     rite.lvar = Some(lvar);
                     head = &head[cur..];
                 }
                 DBG => {
-                    let cur = section_skip(head)?;
+                    let cur = section_debug(head, &mut rite.irep)?;
                     head = &head[cur..];
                 }
                 END => {
@@ -136,7 +182,7 @@ let mut cur = 0;
     let mut ireps: Vec<Irep> = Vec::new();
 
     while cur < irep_size {
-        let mut strvals = Vec::<CString>::new();
+        let mut pool = Vec::<PoolValue>::new();
         let mut syms = Vec::<CString>::new();
 
         let start_cur = cur;
@@ -176,21 +222,51 @@ let mut cur = 0;
 
         for _ in 0..plen {
             let typ = head[cur];
-            match typ {
-                0 => {
-                    cur += 1;
+            cur += 1;
+            let value = match typ {
+                IREP_TT_STR | IREP_TT_SSTR => {
                     let data = &head[cur..cur + 2];
                     let strlen = be16_to_u16([data[0], data[1]]) as usize + 1;
                     cur += 2;
                     let strval = CStr::from_bytes_with_nul(&head[cur..cur + strlen])
                         .or(Err(Error::InvalidFormat))?;
-                    strvals.push(strval.to_owned());
                     cur += strlen;
+                    if typ == IREP_TT_STR {
+                        PoolValue::Str(strval.to_owned())
+                    } else {
+                        PoolValue::StaticStr(strval.to_owned())
+                    }
                 }
-                v => {
-                    unimplemented!("require more support pool type {}", v);
+                IREP_TT_INT32 => {
+                    let v = be32_to_u32([head[cur], head[cur + 1], head[cur + 2], head[cur + 3]]);
+                    cur += 4;
+                    PoolValue::Int32(v as i32)
                 }
-            }
+                IREP_TT_INT64 => {
+                    let hi = be32_to_u32([head[cur], head[cur + 1], head[cur + 2], head[cur + 3]]);
+                    let lo = be32_to_u32([head[cur + 4], head[cur + 5], head[cur + 6], head[cur + 7]]);
+                    cur += 8;
+                    PoolValue::Int64(((hi as u64) << 32 | lo as u64) as i64)
+                }
+                IREP_TT_FLOAT => {
+                    // dumped in little endian, unlike the other numbers
+                    let mut bytes = [0u8; 8];
+                    bytes.copy_from_slice(&head[cur..cur + 8]);
+                    cur += 8;
+                    PoolValue::Float(f64::from_le_bytes(bytes))
+                }
+                IREP_TT_BIGINT => {
+                    let len = head[cur] as usize;
+                    let base = head[cur + 1] as i8;
+                    let digits = head[cur + 2..cur + 2 + len].to_vec();
+                    cur += len + 2;
+                    PoolValue::BigInt { base, digits }
+                }
+                _ => {
+                    return Err(Error::InvalidFormat);
+                }
+            };
+            pool.push(value);
         }
 
         // syms
@@ -213,10 +289,12 @@ let mut cur = 0;
             header: irep_record,
             insn: insns,
             plen,
-            strvals,
+            pool,
             slen,
             syms,
             catch_handlers,
+            debug_info: None,
+            lv: Vec::new(),
         };
         ireps.push(irep);
     }
@@ -230,10 +308,150 @@ pub fn section_end(head: &[u8]) -> Result<usize, Error> {
     Ok(be32_to_u32(header.size) as usize)
 }
 
-pub fn section_lvar(head: &[u8]) -> Result<(usize, LVar), Error> {
+// Reads the local variable names of each irep, stored in the same order as the IREP section
+pub fn section_lvar(head: &[u8], ireps: &mut [Irep]) -> Result<(usize, LVar), Error> {
+    let header = SectionMiscHeader::from_bytes(head)?;
+    let size = be32_to_u32(header.size) as usize;
+    if head.len() < size {
+        return Err(Error::TooShort);
+    }
+    let mut cur = mem::size_of::<SectionMiscHeader>();
+
+    let syms_len = be32_to_u32([head[cur], head[cur + 1], head[cur + 2], head[cur + 3]]) as usize;
+    cur += 4;
+    let mut syms = Vec::new();
+    for _ in 0..syms_len {
+        let len = be16_to_u16([head[cur], head[cur + 1]]) as usize;
+        cur += 2;
+        syms.push(String::from_utf8_lossy(&head[cur..cur + len]).to_string());
+        cur += len;
+    }
+
+    for irep in ireps.iter_mut() {
+        // R0 is self, which has no name
+        for _ in 1..irep.nlocals() {
+            let idx = be16_to_u16([head[cur], head[cur + 1]]);
+            cur += 2;
+            if idx == LV_NULL_MARK {
+                irep.lv.push(None);
+            } else {
+                let name = syms.get(idx as usize).cloned().ok_or(Error::InvalidFormat)?;
+                irep.lv.push(Some(name));
+            }
+        }
+    }
+    if cur != size {
+        return Err(Error::InvalidFormat);
+    }
+
+    Ok((size, LVar { header, syms }))
+}
+
+const LV_NULL_MARK: u16 = u16::MAX;
+
+// Reads the debug info of each irep, stored in the same order as the IREP section
+pub fn section_debug(head: &[u8], ireps: &mut [Irep]) -> Result<usize, Error> {
     let header = SectionMiscHeader::from_bytes(head)?;
-    let lvar = LVar { header };
-    Ok((be32_to_u32(lvar.header.size) as usize, lvar))
+    let size = be32_to_u32(header.size) as usize;
+    if head.len() < size {
+        return Err(Error::TooShort);
+    }
+    let mut cur = mem::size_of::<SectionMiscHeader>();
+
+    let filenames_len = be16_to_u16([head[cur], head[cur + 1]]) as usize;
+    cur += 2;
+    let mut filenames = Vec::new();
+    for _ in 0..filenames_len {
+        let len = be16_to_u16([head[cur], head[cur + 1]]) as usize;
+        cur += 2;
+        filenames.push(String::from_utf8_lossy(&head[cur..cur + len]).to_string());
+        cur += len;
+    }
+
+    for irep in ireps.iter_mut() {
+        if cur >= size {
+            break;
+        }
+        let start_cur = cur;
+        let record_size = be32_to_u32([head[cur], head[cur + 1], head[cur + 2], head[cur + 3]]) as usize;
+        cur += 4;
+        let flen = be16_to_u16([head[cur], head[cur + 1]]) as usize;
+        cur += 2;
+
+        let mut files = Vec::new();
+        for _ in 0..flen {
+            let start_pos = be32_to_u32([head[cur], head[cur + 1], head[cur + 2], head[cur + 3]]) as usize;
+            cur += 4;
+            let filename_idx = be16_to_u16([head[cur], head[cur + 1]]) as usize;
+            cur += 2;
+            let count = be32_to_u32([head[cur], head[cur + 1], head[cur + 2], head[cur + 3]]) as usize;
+            cur += 4;
+            let line_type = head[cur];
+            cur += 1;
+
+            let mut lines = Vec::new();
+            match line_type {
+                // ary: the line of each op position
+                0 => {
+                    for i in 0..count {
+                        let line = be16_to_u16([head[cur], head[cur + 1]]) as usize;
+                        cur += 2;
+                        lines.push((start_pos + i, line));
+                    }
+                }
+                // flat map: (start position, line) pairs
+                1 => {
+                    for _ in 0..count {
+                        let pos = be32_to_u32([head[cur], head[cur + 1], head[cur + 2], head[cur + 3]]) as usize;
+                        cur += 4;
+                        let line = be16_to_u16([head[cur], head[cur + 1]]) as usize;
+                        cur += 2;
+                        lines.push((pos, line));
+                    }
+                }
+                // packed map: varint deltas of position and line, `count` bytes
+                2 => {
+                    let packed = &head[cur..cur + count];
+                    let mut i = 0;
+                    let (mut pos, mut line) = (0, 0);
+                    while i < packed.len() {
+                        pos += read_packed_int(packed, &mut i);
+                        line += read_packed_int(packed, &mut i);
+                        lines.push((pos, line));
+                    }
+                    cur += count;
+                }
+                _ => {
+                    return Err(Error::InvalidFormat);
+                }
+            }
+
+            let filename = filenames.get(filename_idx).cloned().ok_or(Error::InvalidFormat)?;
+            files.push(DebugInfoFile { start_pos, filename, lines });
+        }
+        if cur - start_cur != record_size {
+            return Err(Error::InvalidFormat);
+        }
+        irep.debug_info = Some(DebugInfo { files });
+    }
+
+    Ok(size)
+}
+
+// mruby's packed int: 7 bits per byte, little endian, MSB is the continuation flag
+fn read_packed_int(buf: &[u8], i: &mut usize) -> usize {
+    let mut n = 0;
+    let mut shift = 0;
+    while *i < buf.len() {
+        let b = buf[*i];
+        *i += 1;
+        n |= ((b & 0x7f) as usize) << shift;
+        shift += 7;
+        if b & 0x80 == 0 || shift >= 32 {
+            break;
+        }
+    }
+    n
 }
 
 pub fn section_skip(head: &[u8]) -> Result<usize, Error> {
@@ -0,0 +1,260 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// Arbitrary precision integer that Integer values are promoted to
+// when they overflow i64. The magnitude is kept in little endian
+// 32bit limbs without leading zeros, so zero is an empty vec.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RBigint {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+impl RBigint {
+    pub fn zero() -> Self {
+        RBigint { negative: false, mag: Vec::new() }
+    }
+
+    pub fn from_i64(n: i64) -> Self {
+        Self::from_mag(n < 0, Self::mag_from_u64(n.unsigned_abs()))
+    }
+
+    pub fn from_str_radix(digits: &str, base: u32) -> Option<Self> {
+        let (negative, digits) = match digits.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, digits),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        let mut mag = Vec::new();
+        for c in digits.chars() {
+            if c == '_' {
+                continue;
+            }
+            let d = c.to_digit(base)?;
+            Self::mag_mul_add_small(&mut mag, base, d);
+        }
+        Some(Self::from_mag(negative, mag))
+    }
+
+    fn from_mag(negative: bool, mut mag: Vec<u32>) -> Self {
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        let negative = negative && !mag.is_empty();
+        RBigint { negative, mag }
+    }
+
+    fn mag_from_u64(n: u64) -> Vec<u32> {
+        vec![n as u32, (n >> 32) as u32]
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    // Some(n) when the value fits in i64
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let mut n: u64 = 0;
+        for (i, limb) in self.mag.iter().enumerate() {
+            n |= (*limb as u64) << (32 * i);
+        }
+        if self.negative {
+            if n <= i64::MAX as u64 + 1 {
+                Some((n as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else {
+            i64::try_from(n).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mut f = 0.0;
+        for limb in self.mag.iter().rev() {
+            f = f * 4294967296.0 + *limb as f64;
+        }
+        if self.negative { -f } else { f }
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::from_mag(!self.negative, self.mag.clone())
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::from_mag(self.negative, Self::mag_add(&self.mag, &other.mag));
+        }
+        match Self::mag_cmp(&self.mag, &other.mag) {
+            Ordering::Equal => Self::zero(),
+            Ordering::Greater => Self::from_mag(self.negative, Self::mag_sub(&self.mag, &other.mag)),
+            Ordering::Less => Self::from_mag(other.negative, Self::mag_sub(&other.mag, &self.mag)),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut mag = vec![0u32; self.mag.len() + other.mag.len()];
+        for (i, a) in self.mag.iter().enumerate() {
+            let mut carry: u64 = 0;
+            for (j, b) in other.mag.iter().enumerate() {
+                let t = (*a as u64) * (*b as u64) + mag[i + j] as u64 + carry;
+                mag[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            mag[i + other.mag.len()] = carry as u32;
+        }
+        Self::from_mag(self.negative != other.negative, mag)
+    }
+
+    // Floored division as Integer#divmod; None when dividing by zero
+    pub fn divmod(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = Self::mag_divmod(&self.mag, &other.mag);
+        let mut q = Self::from_mag(self.negative != other.negative, q);
+        let mut r = Self::from_mag(self.negative, r);
+        if !r.is_zero() && r.negative != other.negative {
+            q = q.sub(&Self::from_i64(1));
+            r = r.add(other);
+        }
+        Some((q, r))
+    }
+
+    pub fn to_string_radix(&self, base: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        let mut mag = self.mag.clone();
+        let mut digits = Vec::new();
+        while !mag.is_empty() {
+            let r = Self::mag_divmod_small(&mut mag, base);
+            digits.push(std::char::from_digit(r, base).unwrap());
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+
+    fn mag_cmp(a: &[u32], b: &[u32]) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+    }
+
+    fn mag_add(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+        let mut mag = Vec::with_capacity(a.len() + 1);
+        let mut carry: u64 = 0;
+        for (i, x) in a.iter().enumerate() {
+            let t = *x as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+            mag.push(t as u32);
+            carry = t >> 32;
+        }
+        mag.push(carry as u32);
+        mag
+    }
+
+    // a - b where a >= b
+    fn mag_sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+        let mut mag = Vec::with_capacity(a.len());
+        let mut borrow: i64 = 0;
+        for (i, x) in a.iter().enumerate() {
+            let mut t = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+            if t < 0 {
+                t += 1 << 32;
+                borrow = 1;
+            }
+            mag.push(t as u32);
+        }
+        mag
+    }
+
+    fn mag_mul_add_small(mag: &mut Vec<u32>, m: u32, a: u32) {
+        let mut carry = a as u64;
+        for limb in mag.iter_mut() {
+            let t = (*limb as u64) * (m as u64) + carry;
+            *limb = t as u32;
+            carry = t >> 32;
+        }
+        if carry > 0 {
+            mag.push(carry as u32);
+        }
+    }
+
+    // divides mag by d in place and returns the remainder
+    fn mag_divmod_small(mag: &mut Vec<u32>, d: u32) -> u32 {
+        let mut r: u64 = 0;
+        for limb in mag.iter_mut().rev() {
+            let t = (r << 32) | *limb as u64;
+            *limb = (t / d as u64) as u32;
+            r = t % d as u64;
+        }
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        r as u32
+    }
+
+    // truncated division of magnitudes by shift and subtract
+    fn mag_divmod(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+        if Self::mag_cmp(a, b) == Ordering::Less {
+            return (Vec::new(), a.to_vec());
+        }
+        if b.len() == 1 {
+            let mut q = a.to_vec();
+            let r = Self::mag_divmod_small(&mut q, b[0]);
+            return (q, vec![r]);
+        }
+        let mut q = vec![0u32; a.len()];
+        let mut r: Vec<u32> = Vec::new();
+        for i in (0..a.len() * 32).rev() {
+            // r = r << 1 | bit i of a
+            Self::mag_mul_add_small(&mut r, 2, (a[i / 32] >> (i % 32)) & 1);
+            if Self::mag_cmp(&r, b) != Ordering::Less {
+                r = Self::mag_sub(&r, b);
+                while r.last() == Some(&0) {
+                    r.pop();
+                }
+                q[i / 32] |= 1 << (i % 32);
+            }
+        }
+        (q, r)
+    }
+}
+
+impl PartialOrd for RBigint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RBigint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => Self::mag_cmp(&self.mag, &other.mag),
+            (true, true) => Self::mag_cmp(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl fmt::Display for RBigint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_radix(10))
+    }
+}
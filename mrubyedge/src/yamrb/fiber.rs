@@ -0,0 +1,255 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::mem;
+use std::rc::{Rc, Weak};
+
+use crate::Error;
+
+use super::optable::new_callinfo;
+use super::value::{RClass, RObject, RProc, RSym};
+use super::vm::{CALLINFO, ENV, IREP, VM};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FiberState {
+    Created,
+    Resumed,
+    Suspended,
+    Terminated,
+}
+
+// The execution state swapped into the VM while a fiber runs: each
+// fiber has its own register stack and chain of frames
+pub(crate) struct ExecContext {
+    regs: Vec<Option<Rc<RObject>>>,
+    current_regs_offset: usize,
+    current_callinfo: Option<Rc<CALLINFO>>,
+    current_irep: Rc<IREP>,
+    pc: usize,
+    upper: Option<Rc<ENV>>,
+    target_class: Rc<RClass>,
+    cur_env: HashMap<usize, Rc<ENV>>,
+    has_env_ref: HashMap<usize, bool>,
+}
+
+impl ExecContext {
+    // Exchanges the state of the VM with this one
+    fn swap(&mut self, vm: &mut VM) {
+        mem::swap(&mut self.regs, &mut vm.regs);
+        mem::swap(&mut self.current_regs_offset, &mut vm.current_regs_offset);
+        mem::swap(&mut self.current_callinfo, &mut vm.current_callinfo);
+        mem::swap(&mut self.current_irep, &mut vm.current_irep);
+        self.pc = vm.pc.replace(self.pc);
+        mem::swap(&mut self.upper, &mut vm.upper);
+        mem::swap(&mut self.target_class, &mut vm.target_class);
+        mem::swap(&mut self.cur_env, &mut vm.cur_env);
+        mem::swap(&mut self.has_env_ref, &mut vm.has_env_ref);
+    }
+}
+
+// While the fiber runs, its context holds the one of the resumer
+pub struct RFiber {
+    // Fiber or a subclass of it
+    pub(crate) class: Rc<RClass>,
+    pub(crate) state: Cell<FiberState>,
+    pub(crate) block: RProc,
+    pub(crate) context: RefCell<ExecContext>,
+    // VM::run_depth of the run executing the fiber
+    pub(crate) run_depth: Cell<usize>,
+    // register of the Fiber.yield call, which receives the resumed value
+    pub(crate) yield_reg: Cell<usize>,
+}
+
+// The registers may hold the fiber itself
+impl Debug for RFiber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RFiber")
+            .field("state", &self.state.get())
+            .finish()
+    }
+}
+
+impl RFiber {
+    pub fn new(vm: &VM, class: Rc<RClass>, block: RProc) -> Result<Self, Error> {
+        let irep = match &block.irep {
+            Some(irep) if block.is_rb_func => irep.clone(),
+            _ => return Err(Error::ArgumentError("tried to create a fiber from a Rust proc".to_string())),
+        };
+        let context = ExecContext {
+            regs: Vec::new(),
+            current_regs_offset: 0,
+            current_callinfo: None,
+            current_irep: irep,
+            pc: 0,
+            upper: block.environ.clone(),
+            target_class: vm.target_class.clone(),
+            cur_env: HashMap::new(),
+            has_env_ref: HashMap::new(),
+        };
+        Ok(RFiber {
+            class,
+            state: Cell::new(FiberState::Created),
+            block,
+            context: RefCell::new(context),
+            run_depth: Cell::new(0),
+            yield_reg: Cell::new(0),
+        })
+    }
+
+    pub fn state(&self) -> FiberState {
+        self.state.get()
+    }
+
+    // Objects held by the suspended fiber
+    pub(crate) fn held_objects(&self) -> (Vec<Rc<RObject>>, Option<Rc<ENV>>) {
+        match self.context.try_borrow() {
+            Ok(context) if self.state.get() != FiberState::Resumed => {
+                let regs = context.regs.iter().flatten().cloned().collect();
+                (regs, context.upper.clone())
+            }
+            _ => (Vec::new(), None),
+        }
+    }
+
+    // Drops the registers of a suspended fiber, which cannot be resumed anymore
+    pub(crate) fn terminate(&self) -> Vec<Option<Rc<RObject>>> {
+        match self.context.try_borrow_mut() {
+            Ok(mut context) if self.state.get() != FiberState::Resumed => {
+                self.state.set(FiberState::Terminated);
+                mem::take(&mut context.regs)
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Runs the fiber until it yields or finishes. The arguments are the
+// block parameters on the first resume, and the value of Fiber.yield after.
+pub(crate) fn resume(vm: &mut VM, fiber: &Rc<RFiber>, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    match fiber.state.get() {
+        FiberState::Terminated => {
+            return Err(Error::FiberError("attempt to resume a terminated fiber".to_string()));
+        }
+        FiberState::Resumed => {
+            return Err(Error::FiberError("attempt to resume a resumed fiber (double resume)".to_string()));
+        }
+        _ => {}
+    }
+    vm.check_native_depth()?;
+
+    fiber.context.borrow_mut().swap(vm);
+    if fiber.state.get() == FiberState::Created {
+        if let Err(e) = enter_block(vm, &fiber.block, args) {
+            fiber.context.borrow_mut().swap(vm);
+            fiber.state.set(FiberState::Terminated);
+            return Err(e);
+        }
+    } else {
+        vm.regs[fiber.yield_reg.get()].replace(transfer_value(args));
+    }
+    fiber.state.set(FiberState::Resumed);
+    vm.fibers.push(fiber.clone());
+
+    vm.run_depth += 1;
+    fiber.run_depth.set(vm.run_depth);
+    let res = vm.run_loop();
+    vm.run_depth -= 1;
+
+    vm.fibers.pop();
+    let yielded = vm.fiber_yield.take();
+    fiber.context.borrow_mut().swap(vm);
+
+    match res {
+        Ok(None) => {
+            fiber.state.set(FiberState::Suspended);
+            Ok(yielded.unwrap_or_else(|| RObject::nil().into_rc()))
+        }
+        Ok(Some(ret)) => {
+            fiber.state.set(FiberState::Terminated);
+            Ok(ret)
+        }
+        Err(e) => {
+            fiber.state.set(FiberState::Terminated);
+            match e.downcast::<Error>() {
+                Ok(e) => Err(*e),
+                Err(e) => Err(Error::RuntimeError(e.to_string())),
+            }
+        }
+    }
+}
+
+// Suspends the running fiber, which returns the value from Fiber#resume.
+// The fiber leaves its run at the next instruction, so it cannot yield
+// from a block called by a Rust method such as Hash#each: the Rust
+// method would have to be suspended as well, and FiberError is raised
+// instead. Blocks called by `yield`, Proc#call and the iterators of the
+// mrblib run in the run of the fiber and can yield.
+pub(crate) fn fiber_yield(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let fiber = vm.fibers.last().cloned()
+        .ok_or_else(|| Error::FiberError("can't yield from root fiber".to_string()))?;
+    // a nested run cannot be suspended
+    if fiber.run_depth.get() != vm.run_depth {
+        return Err(Error::FiberError("can't yield from a block called by a Rust method".to_string()));
+    }
+    // the registers of a Rust method start at the receiver of the call,
+    // where the value of the call is stored
+    fiber.yield_reg.set(vm.current_regs_offset);
+    vm.fiber_yield = Some(transfer_value(args));
+    Ok(RObject::nil().into_rc())
+}
+
+// Runs f on the register stack of the fiber, or the root one for None.
+// Blocks may be called while the stack holding their environment is
+// swapped out: then it is in the context of the fiber it resumed, or
+// in its own context for a suspended fiber.
+pub(crate) fn with_stack<R>(vm: &mut VM, stack: &Option<Weak<RFiber>>, f: impl FnOnce(&mut Vec<Option<Rc<RObject>>>) -> R) -> Result<R, Error> {
+    let owner = match stack {
+        Some(fiber) => Some(fiber.upgrade().ok_or_else(|| Error::internal("fiber of the environment is freed"))?),
+        None => None,
+    };
+    let is_owner = |fiber: &Rc<RFiber>| matches!(&owner, Some(o) if Rc::ptr_eq(o, fiber));
+    let running = match vm.fibers.last() {
+        Some(fiber) => is_owner(fiber),
+        None => owner.is_none(),
+    };
+    if running {
+        return Ok(f(&mut vm.regs));
+    }
+    let resumed = match &owner {
+        None => vm.fibers.first().cloned(),
+        Some(_) => vm.fibers.iter().position(is_owner)
+            .and_then(|i| vm.fibers.get(i + 1).cloned()),
+    };
+    let holder = resumed.or(owner).ok_or_else(|| Error::internal("register stack not found"))?;
+    let mut context = holder.context.try_borrow_mut()
+        .map_err(|_| Error::internal("register stack is in use"))?;
+    Ok(f(&mut context.regs))
+}
+
+// Pushes the frame of the fiber block, returning from which ends the run
+fn enter_block(vm: &mut VM, block: &RProc, args: &[Rc<RObject>]) -> Result<(), Error> {
+    let block_self = block.block_self.clone()
+        .ok_or_else(|| Error::RuntimeError("No block self assigned".to_string()))?;
+    let method_id = block.sym_id.clone().unwrap_or_else(|| RSym::new("<block>".to_string()));
+    let mut callinfo = new_callinfo(vm, method_id, args.len())?;
+    callinfo.called_from_rust = true;
+    callinfo.is_lambda = block.is_lambda;
+    vm.current_callinfo = Some(Rc::new(callinfo));
+
+    vm.ensure_regs(args.len() + 2);
+    vm.current_regs()[0].replace(block_self);
+    for (i, arg) in args.iter().enumerate() {
+        vm.current_regs()[i + 1].replace(arg.clone());
+    }
+    vm.current_regs()[args.len() + 1].replace(RObject::nil().into_rc());
+    Ok(())
+}
+
+// nil for no values, and an array for more than one
+fn transfer_value(args: &[Rc<RObject>]) -> Rc<RObject> {
+    match args {
+        [] => RObject::nil().into_rc(),
+        [value] => value.clone(),
+        values => RObject::array(values.to_vec()).into_rc(),
+    }
+}
@@ -0,0 +1,287 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::rc::{Rc, Weak};
+
+use super::value::{RObject, RType, RValue};
+use super::vm::ENV;
+
+// Objects are reference counted, so anything but a reference cycle is
+// freed as soon as it is unreachable. The collector keeps weak handles
+// to the objects able to hold others (arrays, hashes, instances,
+// exceptions, procs and fibers) and frees the cycles among them on demand.
+// Objects are tracked by the collector of the VM entered on the thread
+// when they are allocated (see VM::enter).
+#[derive(Default)]
+pub struct CycleCollector {
+    tracked: RefCell<Vec<Weak<RObject>>>,
+    // tracked handles still alive when the list was last pruned
+    live_at_prune: Cell<usize>,
+    count: Cell<usize>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<CycleCollector>>> = const { RefCell::new(None) };
+}
+
+// Tracked objects hold the whole object graph
+impl Debug for CycleCollector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CycleCollector")
+            .field("tracked", &self.tracked.borrow().len())
+            .field("count", &self.count.get())
+            .finish()
+    }
+}
+
+impl CycleCollector {
+    // Number of collections run so far
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+
+    // Live tracked objects by their type
+    pub fn count_objects(&self) -> HashMap<RType, usize> {
+        let mut counts = HashMap::new();
+        for obj in self.tracked.borrow().iter().filter_map(Weak::upgrade) {
+            *counts.entry(obj.tt).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    // Frees the objects only reachable from reference cycles and
+    // returns how many of the tracked ones were freed
+    pub fn collect(&self) -> usize {
+        self.count.set(self.count.get() + 1);
+        self.prune();
+        let objects: Vec<Rc<RObject>> = self.tracked.borrow().iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+
+        let mut graph = Graph::default();
+        for obj in objects.iter() {
+            graph.add(Node::Object(obj.clone()));
+        }
+        drop(objects);
+        let garbage = graph.garbage();
+
+        let mut freed = 0;
+        for node in garbage.iter() {
+            if let Node::Object(_) = node {
+                freed += 1;
+            }
+            node.break_refs();
+        }
+        drop(garbage);
+        drop(graph);
+        self.prune();
+        freed
+    }
+
+    fn track(&self, obj: &Rc<RObject>) {
+        let mut tracked = self.tracked.borrow_mut();
+        tracked.push(Rc::downgrade(obj));
+        // drop the handles of freed objects once the list has doubled
+        if tracked.len() > 2 * self.live_at_prune.get().max(256) {
+            tracked.retain(|obj| obj.strong_count() > 0);
+            self.live_at_prune.set(tracked.len());
+        }
+    }
+
+    fn prune(&self) {
+        let mut tracked = self.tracked.borrow_mut();
+        tracked.retain(|obj| obj.strong_count() > 0);
+        self.live_at_prune.set(tracked.len());
+    }
+}
+
+// Makes the collector current, returning the previous one
+pub(crate) fn set_current(collector: Option<Rc<CycleCollector>>) -> Option<Rc<CycleCollector>> {
+    CURRENT.with(|current| current.replace(collector))
+}
+
+// Tracks the object if it may hold other objects
+pub(crate) fn track(obj: &Rc<RObject>) {
+    match obj.tt {
+        RType::Array | RType::Hash | RType::Instance | RType::Exception | RType::Proc | RType::Fiber => {}
+        _ => return,
+    }
+    let _ = CURRENT.try_with(|current| {
+        if let Some(collector) = current.borrow().as_ref() {
+            collector.track(obj);
+        }
+    });
+}
+
+// Block environments hold the captured registers, so they are
+// nodes of the object graph along with the objects
+#[derive(Clone)]
+enum Node {
+    Object(Rc<RObject>),
+    Env(Rc<ENV>),
+}
+
+impl Node {
+    fn addr(&self) -> usize {
+        match self {
+            Node::Object(obj) => Rc::as_ptr(obj) as *const () as usize,
+            Node::Env(env) => Rc::as_ptr(env) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Object(obj) => Rc::strong_count(obj),
+            Node::Env(env) => Rc::strong_count(env),
+        }
+    }
+
+    // Contents borrowed mutably by a running method are skipped, which
+    // only makes their children look held from outside
+    fn children(&self) -> Vec<Node> {
+        let mut children = Vec::new();
+        match self {
+            Node::Object(obj) => match &obj.value {
+                RValue::Array(a) => if let Ok(a) = a.try_borrow() {
+                    children.extend(a.iter().cloned().map(Node::Object));
+                }
+                RValue::Hash(h) => if let Ok(h) = h.try_borrow() {
+                    for (k, v) in h.values() {
+                        children.push(Node::Object(k.clone()));
+                        children.push(Node::Object(v.clone()));
+                    }
+                }
+                RValue::Range(start, end, _) => {
+                    children.push(Node::Object(start.clone()));
+                    children.push(Node::Object(end.clone()));
+                }
+                RValue::Instance(ins) => if let Ok(ivar) = ins.ivar.try_borrow() {
+                    children.extend(ivar.values().cloned().map(Node::Object));
+                }
+                RValue::Exception(e) => if let Ok(ivar) = e.ivar.try_borrow() {
+                    children.extend(ivar.values().cloned().map(Node::Object));
+                }
+                RValue::Proc(p) => {
+                    if let Some(env) = &p.environ {
+                        children.push(Node::Env(env.clone()));
+                    }
+                    if let Some(block_self) = &p.block_self {
+                        children.push(Node::Object(block_self.clone()));
+                    }
+                }
+                RValue::Fiber(fiber) => {
+                    let (regs, upper) = fiber.held_objects();
+                    children.extend(regs.into_iter().map(Node::Object));
+                    if let Some(env) = upper {
+                        children.push(Node::Env(env));
+                    }
+                    if let Some(env) = &fiber.block.environ {
+                        children.push(Node::Env(env.clone()));
+                    }
+                    if let Some(block_self) = &fiber.block.block_self {
+                        children.push(Node::Object(block_self.clone()));
+                    }
+                }
+                _ => {}
+            },
+            Node::Env(env) => {
+                if let Some(upper) = &env.upper {
+                    children.push(Node::Env(upper.clone()));
+                }
+                if let Ok(captured) = env.captured.try_borrow()
+                    && let Some(captured) = captured.as_ref()
+                {
+                    children.extend(captured.iter().flatten().cloned().map(Node::Object));
+                }
+            }
+        }
+        children
+    }
+
+    // Drops the references held by a garbage node, which frees the cycle
+    fn break_refs(&self) {
+        match self {
+            Node::Object(obj) => match &obj.value {
+                RValue::Array(a) => {
+                    let items = obj.with_resize(|| a.take());
+                    drop(items);
+                }
+                RValue::Hash(h) => {
+                    let items = obj.with_resize(|| h.take());
+                    drop(items);
+                }
+                RValue::Instance(ins) => {
+                    let ivar = ins.ivar.take();
+                    drop(ivar);
+                }
+                RValue::Exception(e) => {
+                    let ivar = e.ivar.take();
+                    drop(ivar);
+                }
+                RValue::Fiber(fiber) => {
+                    let regs = fiber.terminate();
+                    drop(regs);
+                }
+                _ => {}
+            },
+            Node::Env(env) => {
+                let captured = env.captured.take();
+                drop(captured);
+            }
+        }
+    }
+}
+
+// The objects reachable from the tracked ones, found by trial deletion:
+// a node referenced more times than from inside the graph is held from
+// outside (registers, globals, classes or the host), and everything it
+// reaches is alive. The rest is only held by cycles.
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    index: HashMap<usize, usize>,
+}
+
+impl Graph {
+    fn add(&mut self, root: Node) {
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let addr = node.addr();
+            if self.index.contains_key(&addr) {
+                continue;
+            }
+            stack.extend(node.children());
+            self.index.insert(addr, self.nodes.len());
+            self.nodes.push(node);
+        }
+    }
+
+    fn garbage(&self) -> Vec<Node> {
+        let mut internal = vec![0usize; self.nodes.len()];
+        for node in self.nodes.iter() {
+            for child in node.children() {
+                internal[self.index[&child.addr()]] += 1;
+            }
+        }
+
+        let mut alive = HashSet::new();
+        let mut stack: Vec<usize> = self.nodes.iter().enumerate()
+            // one reference is the graph's own
+            .filter(|(i, node)| node.strong_count() - 1 > internal[*i])
+            .map(|(i, _)| i)
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !alive.insert(i) {
+                continue;
+            }
+            for child in self.nodes[i].children() {
+                stack.push(self.index[&child.addr()]);
+            }
+        }
+
+        self.nodes.iter().enumerate()
+            .filter(|(i, _)| !alive.contains(i))
+            .map(|(_, node)| node.clone())
+            .collect()
+    }
+}
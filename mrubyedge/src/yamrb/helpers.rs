@@ -1,57 +1,55 @@
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::Error;
 
-use super::{optable::push_callinfo, value::{RClass, RFn, RObject, RProc, RSym, RValue}, vm::VM};
-
-fn call_block(vm: &mut VM, block: RProc, recv: Rc<RObject>, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
-    push_callinfo(vm, RSym::new("<block>".to_string()), args.len());
-
-    let old_callinfo = vm.current_callinfo.take();
+use super::{optable::new_callinfo, value::{RClass, RFn, RObject, RProc, RSym, RValue}, vm::VM};
+
+fn call_block(vm: &mut VM, block: RProc, recv: Rc<RObject>, args: &[Rc<RObject>], kargs: Option<Rc<RObject>>, blk: Option<Rc<RObject>>, owner: Option<Rc<RClass>>) -> Result<Rc<RObject>, Error> {
+    vm.check_native_depth()?;
+    let method_id = block.sym_id.clone().unwrap_or_else(|| RSym::new("<block>".to_string()));
+    let mut callinfo = new_callinfo(vm, method_id, args.len())?;
+    callinfo.called_from_rust = true;
+    callinfo.host_entry = vm.run_depth == 0;
+    callinfo.is_lambda = block.is_lambda;
+
+    // self, args, the keyword hash if any, and the block
+    let mut regs = vec![recv];
+    regs.extend(args.iter().cloned());
+    if let Some(kargs) = kargs {
+        callinfo.kdict_index.set(Some(regs.len()));
+        regs.push(kargs);
+    }
+    regs.push(blk.unwrap_or_else(|| RObject::nil().into_rc()));
+    vm.current_callinfo = Some(Rc::new(callinfo));
+    if let Some(owner) = owner {
+        vm.target_class = owner;
+    }
 
     // Since call_block does not move the registers offset,
     // keep the state before the call.
-    let prev_self = vm.current_regs()[0].replace(recv);
-
-    let mut prev_args = vec![];
-    for (i, arg) in args.iter().enumerate() {
-        let old = vm.current_regs()[i + 1].replace(arg.clone());
-        prev_args.push(old);
-    }
+    vm.ensure_regs(regs.len());
+    let prev_regs: Vec<_> = regs.into_iter().enumerate()
+        .map(|(i, v)| vm.current_regs()[i].replace(v))
+        .collect();
+    let prev_upper = vm.upper.take();
 
     vm.pc.set(0);
     vm.current_irep = block.irep.as_ref().ok_or_else(|| Error::RuntimeError("No IREP".to_string()))?.clone();
     vm.upper = block.environ;
 
+    // The callinfo pushed above is popped by RETURN (or by unwinding),
+    // which also restores pc, irep and registers offset of the caller.
     let res = vm.run();
 
-    if let Some(prev) = prev_self {
-        vm.current_regs()[0].replace(prev);
-    } else {
-        vm.current_regs()[0].take();
-    }
-    for (i, prev_arg) in prev_args.into_iter().enumerate() {
-        if let Some(prev) = prev_arg {
-            vm.current_regs()[i + 1].replace(prev);
+    for (i, prev) in prev_regs.into_iter().enumerate() {
+        if let Some(prev) = prev {
+            vm.current_regs()[i].replace(prev);
         } else {
-            vm.current_regs()[i + 1].take();
-        }
-    }
-
-    if let Some(ci) = old_callinfo {
-        if let Some(prev) = &ci.prev {
-            vm.current_callinfo.replace(prev.clone());
-        }
-        vm.current_irep = ci.pc_irep.clone();
-        vm.pc.set(ci.pc);
-        vm.current_regs_offset = ci.current_regs_offset;
-        vm.target_class = ci.target_class.clone();
-    }
-    if let Some(upper) = vm.upper.take() {
-        if let Some(upper) = &upper.as_ref().upper {
-            vm.upper.replace(upper.clone());
+            vm.current_regs()[i].take();
         }
     }
+    vm.upper = prev_upper;
 
     match &res {
         Ok(res) => {
@@ -69,57 +67,182 @@ fn call_block(vm: &mut VM, block: RProc, recv: Rc<RObject>, args: &[Rc<RObject>]
     }   
 }
 
+// The args given to a Rust method, which always end with the block slot
+pub fn without_block(args: &[Rc<RObject>]) -> &[Rc<RObject>] {
+    &args[..args.len().saturating_sub(1)]
+}
+
 pub fn mrb_call_block(vm: &mut VM, block: Rc<RObject>, recv: Option<Rc<RObject>>, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    mrb_call_block_with_kargs(vm, block, recv, args, None)
+}
+
+// mrb_call_block passing keyword args as a hash
+pub fn mrb_call_block_with_kargs(vm: &mut VM, block: Rc<RObject>, recv: Option<Rc<RObject>>, args: &[Rc<RObject>], kargs: Option<Rc<RObject>>) -> Result<Rc<RObject>, Error> {
+    let _entered = vm.enter();
     let block = match &block.value {
         RValue::Proc(p) => p.clone(),
         _ => panic!("Not a block"),
         
     };
+    if !block.is_rb_func {
+        // procs made in Rust, such as the ones by Symbol#to_proc
+        let func = block.func.and_then(|i| vm.get_fn(i))
+            .ok_or_else(|| Error::internal("function not found"))?;
+        let mut args = args.to_vec();
+        vm.kargs_given = kargs.is_some();
+        args.extend(kargs);
+        args.push(RObject::nil().into_rc());
+        return func(vm, &args);
+    }
     let recv = match recv {
         Some(r) => r,
         None => block.block_self.clone().ok_or_else(|| Error::RuntimeError("No block self assigned".to_string()))?,
     };
-    call_block(vm, block, recv, args)
+    call_block(vm, block, recv, args, kargs, None, None)
 }
 
 pub fn mrb_funcall(vm: &mut VM, top_self: Option<Rc<RObject>>, name: &str, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    mrb_funcall_with_block(vm, top_self, name, args, None, None)
+}
+
+// Symbols the host made without VM::intern may have ids of another
+// VM, so the symbol args and keyword names are interned again by name
+fn reintern_symbol(vm: &VM, arg: &Rc<RObject>) -> Rc<RObject> {
+    match &arg.value {
+        RValue::Symbol(sym) if vm.intern(&sym.name).id != sym.id => {
+            RObject::symbol(vm.intern(&sym.name)).into_rc()
+        }
+        _ => arg.clone(),
+    }
+}
+
+fn reintern_kargs(vm: &VM, kargs: &Rc<RObject>) -> Result<Rc<RObject>, Error> {
+    let RValue::Hash(h) = &kargs.value else {
+        return Ok(kargs.clone());
+    };
+    let mut hash = HashMap::new();
+    for (key, value) in h.borrow().values() {
+        let key = reintern_symbol(vm, key);
+        hash.insert(key.as_hash_key()?, (key, value.clone()));
+    }
+    Ok(RObject::hash(hash).into_rc())
+}
+
+// mrb_funcall passing keyword args as a hash, and a block
+pub fn mrb_funcall_with_block(vm: &mut VM, top_self: Option<Rc<RObject>>, name: &str, args: &[Rc<RObject>], kargs: Option<Rc<RObject>>, block: Option<Rc<RObject>>) -> Result<Rc<RObject>, Error> {
+    let _entered = vm.enter();
+    let args: Vec<_> = args.iter().map(|arg| reintern_symbol(vm, arg)).collect();
+    let args = args.as_slice();
+    let kargs = kargs.map(|kargs| reintern_kargs(vm, &kargs)).transpose()?;
     let recv: Rc<RObject> = match top_self {
         Some(obj) => obj,
         None => vm.getself()?,
     };
     let binding = recv.as_ref().get_class(vm);
-    let method = binding.as_ref().find_method(name).ok_or_else(|| Error::NoMethodError(name.to_string()))?;
+    let (owner, method) = binding.find_method_with_owner(name).ok_or_else(|| Error::NoMethodError(name.to_string()))?;
     
     if method.is_rb_func {
-        call_block(vm, method, recv.clone(), args)
+        call_block(vm, method, recv.clone(), args, kargs, block, Some(owner))
     } else {
-        vm.current_regs_offset += 2; // FIXME: magick number?
+        // use the registers next to the caller's ones not to clobber them
+        let offset = vm.current_irep.nregs;
+        vm.current_regs_offset += offset;
         vm.current_regs()[0].replace(recv.clone());
 
+        let mut args = args.to_vec();
+        vm.kargs_given = kargs.is_some();
+        args.extend(kargs);
+        args.push(block.unwrap_or_else(|| RObject::nil().into_rc()));
         let func = vm.fn_table[method.func.unwrap()].clone();
         let res = func(vm, &args);
-        vm.current_regs_offset -= 2;
+        vm.current_regs()[0].take();
+        vm.current_regs_offset -= offset;
 
         res
     }
 }
 
+// mrb_singleton_class returns the singleton class of obj, creating it if needed
+pub fn mrb_singleton_class(vm: &mut VM, obj: &Rc<RObject>) -> Result<Rc<RClass>, Error> {
+    let _entered = vm.enter();
+    match &obj.value {
+        RValue::Class(klass) => Ok(class_singleton_class(vm, klass)),
+        RValue::Instance(ins) => {
+            if let Some(sc) = ins.singleton_class.borrow().as_ref() {
+                return Ok(sc.clone());
+            }
+            let name = format!("#<Class:{}>", ins.class.sym_id.name);
+            let sc = Rc::new(RClass::new_singleton(&name, ins.class.clone()));
+            ins.singleton_class.replace(Some(sc.clone()));
+            Ok(sc)
+        }
+        _ => Err(Error::TypeError("can't define singleton".to_string())),
+    }
+}
+
+// The singleton class of a class inherits the one of its superclass,
+// so that class methods are inherited
+fn class_singleton_class(vm: &mut VM, klass: &Rc<RClass>) -> Rc<RClass> {
+    if let Some(sc) = klass.singleton_class.borrow().as_ref() {
+        return sc.clone();
+    }
+    let super_class = match &klass.super_class {
+        Some(sc) => class_singleton_class(vm, sc),
+        None if klass.is_module => vm.get_class_by_name("Module"),
+        None => vm.get_class_by_name("Class"),
+    };
+    let name = format!("#<Class:{}>", klass.sym_id.name);
+    let sc = Rc::new(RClass::new_singleton(&name, super_class));
+    klass.singleton_class.replace(Some(sc.clone()));
+    sc
+}
+
 pub fn mrb_define_cmethod(vm: &mut VM, klass: Rc<RClass>, name: &str, cmethod: RFn) {
+    let _entered = vm.enter();
     let index = vm.register_fn(cmethod);
-    let method = RProc {
-        is_rb_func: false,
-        sym_id: Some(RSym::new(name.to_string())),
-        next: None,
-        irep: None,
-        func: Some(index),
-        environ: None,
-        block_self: None,
-    };
+    let method = RProc::cfunc(name, index);
     let mut procs = klass.procs.borrow_mut();
-    procs.insert(name.to_string(), method);
+    procs.insert(RSym::from(name), method);
+    vm.expire_method_caches();
 }
 
-pub fn mrb_define_method(_vm: &mut VM, klass: Rc<RClass>, name: &str, method: RProc) {
+pub fn mrb_define_method(vm: &mut VM, klass: Rc<RClass>, name: &str, method: RProc) {
+    let _entered = vm.enter();
     let mut procs = klass.procs.borrow_mut();
-    procs.insert(name.to_string(), method);
+    procs.insert(RSym::from(name), method);
+    vm.expire_method_caches();
+}
+
+pub fn mrb_alias_method(vm: &mut VM, klass: Rc<RClass>, new_name: &str, old_name: &str) -> Result<(), Error> {
+    let _entered = vm.enter();
+    let method = klass.find_method(old_name).ok_or_else(|| {
+        Error::NameError(format!("undefined method '{}' for class '{}'", old_name, klass.sym_id.name))
+    })?;
+    mrb_define_method(vm, klass, new_name, method);
+    Ok(())
+}
+
+// undef hides the method defined in the superclasses as well
+pub fn mrb_undef_method(vm: &mut VM, klass: Rc<RClass>, name: &str) -> Result<(), Error> {
+    let _entered = vm.enter();
+    if klass.find_method(name).is_none() {
+        return Err(Error::NameError(format!("undefined method '{}' for class '{}'", name, klass.sym_id.name)));
+    }
+    mrb_define_method(vm, klass, name, RProc::undefined(name));
+    Ok(())
+}
+
+// remove_method only removes the method of klass itself
+pub fn mrb_remove_method(vm: &mut VM, klass: Rc<RClass>, name: &str) -> Result<(), Error> {
+    let _entered = vm.enter();
+    let sym = RSym::from(name);
+    // an undef entry is not a method and stays in place
+    let defined = klass.procs.borrow().get(&sym)
+        .is_some_and(|m| !m.is_undefined());
+    if !defined {
+        return Err(Error::NameError(format!("method '{}' not defined in {}", name, klass.sym_id.name)));
+    }
+    klass.procs.borrow_mut().remove(&sym);
+    vm.expire_method_caches();
+    Ok(())
 }
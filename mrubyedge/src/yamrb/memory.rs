@@ -0,0 +1,76 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// Accounts the heap held by strings, arrays, hashes, instances and
+// shared memories against an optional limit.
+// Objects are charged to the meter of the VM entered on the thread
+// when they are allocated (see VM::enter), and keep it to be released
+// from the same one, wherever they are dropped.
+#[derive(Debug, Default)]
+pub struct MemoryMeter {
+    used: Cell<usize>,
+    limit: Cell<Option<usize>>,
+    // set when a charge goes over the limit, until the VM raises for it
+    exceeded: Cell<bool>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<MemoryMeter>>> = const { RefCell::new(None) };
+}
+
+impl MemoryMeter {
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit.get()
+    }
+
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit.set(limit);
+    }
+
+    // Whether the bytes can be allocated in addition without going over the limit
+    pub fn can_allocate(&self, bytes: usize) -> bool {
+        match self.limit.get() {
+            Some(limit) => self.used.get().saturating_add(bytes) <= limit,
+            None => true,
+        }
+    }
+
+    pub(crate) fn take_exceeded(&self) -> bool {
+        self.exceeded.replace(false)
+    }
+
+    pub(crate) fn charge(&self, bytes: usize) {
+        self.used.set(self.used.get().saturating_add(bytes));
+        if !self.can_allocate(0) {
+            self.exceeded.set(true);
+        }
+    }
+
+    pub(crate) fn release(&self, bytes: usize) {
+        self.used.set(self.used.get().saturating_sub(bytes));
+    }
+
+    // Accounts an object grown or shrunk from `before` bytes to `after`
+    pub(crate) fn resize(&self, before: usize, after: usize) {
+        if after > before {
+            self.charge(after - before);
+        } else {
+            self.release(before - after);
+        }
+    }
+}
+
+// The meter objects allocated now are charged to
+pub(crate) fn current() -> Option<Rc<MemoryMeter>> {
+    // objects may be allocated while the thread is torn down
+    CURRENT.try_with(|current| current.borrow().clone()).ok().flatten()
+}
+
+// Makes the meter current, returning the previous one
+pub(crate) fn set_current(meter: Option<Rc<MemoryMeter>>) -> Option<Rc<MemoryMeter>> {
+    CURRENT.with(|current| current.replace(meter))
+}
@@ -0,0 +1,53 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use super::value::{RClass, RProc, RSym};
+
+#[derive(Clone)]
+struct CacheEntry {
+    state: u64,
+    class: Rc<RClass>,
+    owner: Rc<RClass>,
+    method: RProc,
+}
+
+// Inline caches of the method lookups done by the SEND ops of an IREP,
+// one entry per op index, keyed by the class of the receiver. Entries
+// made before the VM::method_state given are expired.
+#[derive(Clone, Default)]
+pub struct MethodCache {
+    entries: RefCell<Vec<Option<CacheEntry>>>,
+}
+
+// The cached classes hold methods, whose IREPs hold the caches again
+impl Debug for MethodCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cached = self.entries.borrow().iter().flatten().count();
+        f.debug_struct("MethodCache").field("cached", &cached).finish()
+    }
+}
+
+impl MethodCache {
+    pub(crate) fn find_method(&self, state: u64, site: usize, class: &Rc<RClass>, sym: &RSym) -> Option<(Rc<RClass>, RProc)> {
+        if let Some(Some(entry)) = self.entries.borrow().get(site)
+            && entry.state == state
+            && Rc::ptr_eq(&entry.class, class)
+        {
+            return Some((entry.owner.clone(), entry.method.clone()));
+        }
+
+        let (owner, method) = class.find_method_by_sym(sym)?;
+        let mut entries = self.entries.borrow_mut();
+        if entries.len() <= site {
+            entries.resize(site + 1, None);
+        }
+        entries[site] = Some(CacheEntry {
+            state,
+            class: class.clone(),
+            owner: owner.clone(),
+            method: method.clone(),
+        });
+        Some((owner, method))
+    }
+}
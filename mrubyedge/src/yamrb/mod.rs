@@ -1,6 +1,13 @@
 pub mod optable;
 pub mod value;
+pub mod bigint;
+pub mod memory;
+pub mod gc;
+pub mod method_cache;
+pub mod symbol;
 pub mod shared_memory;
+pub mod fiber;
+pub mod snapshot;
 pub mod vm;
 pub mod op;
 pub mod helpers;
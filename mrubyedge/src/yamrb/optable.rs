@@ -1,4 +1,5 @@
 use std::cell::Cell;
+use std::cell::OnceCell;
 use std::cell::RefCell;
 
 use std::collections::HashMap;
@@ -7,8 +8,12 @@ use std::rc::Rc;
 use crate::rite::insn::{Fetched, OpCode};
 use crate::Error;
 
+use super::prelude::integer::{integer_add, integer_div, integer_mul, integer_sub, numeric_cmp};
 use super::prelude::object::mrb_object_is_equal;
-use super::{helpers::mrb_funcall, value::*, vm::*};
+use super::prelude::symbol::symbol_to_proc;
+use super::bigint::RBigint;
+use super::fiber::with_stack;
+use super::{helpers::{mrb_alias_method, mrb_funcall, mrb_singleton_class, mrb_undef_method}, value::*, vm::*};
 
 // OpCodes of mruby 3.2.0 from mruby/op.h:
 // OPCODE(NOP,        Z)        /* no operation */
@@ -139,347 +144,376 @@ const ENTER_K_MASK: u32 = 0b11111 << 2;
 const ENTER_D_MASK: u32 = 0b1 << 1;
 const ENTER_B_MASK: u32 = 0b1 << 0;
 
+// argc/kargc value meaning the args are packed in a single register
+pub(crate) const CALL_MAXARGS: usize = 15;
+
 pub(crate) fn consume_expr(vm: &mut VM, code: OpCode, operand: &Fetched, pos: usize, len: usize) -> Result<(), Error> {
     use crate::rite::insn::OpCode::*;
     match code {
         NOP => {
-            op_nop(vm, &operand)?;
+            op_nop(vm, operand)?;
         }
         MOVE => {
-            op_move(vm, &operand)?;
+            op_move(vm, operand)?;
         }
         LOADL => {
-            op_loadl(vm, &operand)?;
+            op_loadl(vm, operand)?;
         }
         LOADI => {
-            op_loadi(vm, &operand)?;
+            op_loadi(vm, operand)?;
         }
         LOADINEG => {
-            op_loadineg(vm, &operand)?;
+            op_loadineg(vm, operand)?;
         }
         LOADI__1 => {
-            op_loadi_n(vm, -1, &operand)?;
+            op_loadi_n(vm, -1, operand)?;
         }
         LOADI_0 => {
-            op_loadi_n(vm, 0, &operand)?;
+            op_loadi_n(vm, 0, operand)?;
         }
         LOADI_1 => {
-            op_loadi_n(vm, 1, &operand)?;
+            op_loadi_n(vm, 1, operand)?;
         }
         LOADI_2 => {
-            op_loadi_n(vm, 2, &operand)?;
+            op_loadi_n(vm, 2, operand)?;
         }
         LOADI_3 => {
-            op_loadi_n(vm, 3, &operand)?;
+            op_loadi_n(vm, 3, operand)?;
         }
         LOADI_4 => {
-            op_loadi_n(vm, 4, &operand)?;
+            op_loadi_n(vm, 4, operand)?;
         }
         LOADI_5 => {
-            op_loadi_n(vm, 5, &operand)?;
+            op_loadi_n(vm, 5, operand)?;
         }
         LOADI_6 => {
-            op_loadi_n(vm, 6, &operand)?;
+            op_loadi_n(vm, 6, operand)?;
         }
         LOADI_7 => {
-            op_loadi_n(vm, 7, &operand)?;
+            op_loadi_n(vm, 7, operand)?;
         }
         LOADI16 => {
-            op_loadi16(vm, &operand)?;
+            op_loadi16(vm, operand)?;
         }
         LOADI32 => {
-            op_loadi32(vm, &operand)?;
+            op_loadi32(vm, operand)?;
         }
         LOADSYM => {
-            op_loadsym(vm, &operand)?;
+            op_loadsym(vm, operand)?;
         }
         LOADNIL => {
-            op_loadnil(vm, &operand)?;
+            op_loadnil(vm, operand)?;
         }
         LOADSELF => {
-            op_loadself(vm, &operand)?;
+            op_loadself(vm, operand)?;
         }
         LOADT => {
-            op_loadt(vm, &operand)?;
+            op_loadt(vm, operand)?;
         }
         LOADF => {
-            op_loadf(vm, &operand)?;
+            op_loadf(vm, operand)?;
         }
         GETGV => {
-            op_getgv(vm, &operand)?;
+            op_getgv(vm, operand)?;
         }
         SETGV => {
-            op_setgv(vm, &operand)?;
+            op_setgv(vm, operand)?;
         }
         // GETSV => {
-        //     // op_getsv(vm, &operand)?;
+        //     // op_getsv(vm, operand)?;
         // }
         // SETSV => {
-        //     // op_setsv(vm, &operand)?;
+        //     // op_setsv(vm, operand)?;
         // }
         GETIV => {
-            op_getiv(vm, &operand)?;
+            op_getiv(vm, operand)?;
         }
         SETIV => {
-            op_setiv(vm, &operand)?;
+            op_setiv(vm, operand)?;
+        }
+        GETCV => {
+            op_getcv(vm, operand)?;
+        }
+        SETCV => {
+            op_setcv(vm, operand)?;
         }
-        // GETCV => {
-        //     // op_getcv(vm, &operand)?;
-        // }
-        // SETCV => {
-        //     // op_setcv(vm, &operand)?;
-        // }
         GETCONST => {
-            op_getconst(vm, &operand)?;
+            op_getconst(vm, operand)?;
         }
         SETCONST => {
-            op_setconst(vm, &operand)?;
+            op_setconst(vm, operand)?;
         }
         GETMCNST => {
-            op_getmcnst(vm, &operand)?;
+            op_getmcnst(vm, operand)?;
         }
         // SETMCNST => {
-        //     // op_setmcnst(vm, &operand)?;
+        //     // op_setmcnst(vm, operand)?;
         // }
         GETUPVAR => {
-            op_getupvar(vm, &operand)?;
+            op_getupvar(vm, operand)?;
         }
         SETUPVAR => {
-            op_setupvar(vm, &operand)?;
+            op_setupvar(vm, operand)?;
         }
         GETIDX => {
-            op_getidx(vm, &operand)?;
+            op_getidx(vm, operand)?;
         }
         SETIDX => {
-            op_setidx(vm, &operand)?;
+            op_setidx(vm, operand)?;
         }
         JMP => {
-            op_jmp(vm, &operand, pos + len)?;
+            op_jmp(vm, operand, pos + len)?;
         }
         JMPIF => {
-            op_jmpif(vm, &operand, pos + len)?;
+            op_jmpif(vm, operand, pos + len)?;
         }
         JMPNOT => {
-            op_jmpnot(vm, &operand, pos + len)?;
+            op_jmpnot(vm, operand, pos + len)?;
         }
         JMPNIL => {
-            op_jmpnil(vm, &operand, pos + len)?;
+            op_jmpnil(vm, operand, pos + len)?;
+        }
+        JMPUW => {
+            op_jmpuw(vm, operand, pos + len)?;
         }
-        // JMPUW => {
-        //     // op_jmpuw(vm, &operand)?;
-        // }
         EXCEPT => {
-            op_except(vm, &operand)?;
+            op_except(vm, operand)?;
         }
         RESCUE => {
-            op_rescue(vm, &operand)?;
+            op_rescue(vm, operand)?;
         }
         RAISEIF => {
-            op_raiseif(vm, &operand)?;
+            op_raiseif(vm, operand)?;
         }
         SSEND => {
-            op_ssend(vm, &operand)?;
+            op_ssend(vm, operand)?;
         }
         SSENDB => {
-            op_ssendb(vm, &operand)?;
+            op_ssendb(vm, operand)?;
         }
         SEND => {
-            op_send(vm, &operand)?;
+            op_send(vm, operand)?;
         }
         SENDB => {
-            op_sendb(vm, &operand)?;
+            op_sendb(vm, operand)?;
         }
         CALL => {
-            op_call(vm, &operand)?;
+            op_call(vm, operand)?;
         }
         SUPER => {
-            op_super(vm, &operand)?;
+            op_super(vm, operand)?;
+        }
+        ARGARY => {
+            op_argary(vm, operand)?;
         }
-        // ARGARY => {
-        //     // op_argary(vm, &operand)?;
-        // }
         ENTER => {
-            op_enter(vm, &operand)?;
+            op_enter(vm, operand)?;
+        }
+        KEY_P => {
+            op_key_p(vm, operand)?;
+        }
+        KEYEND => {
+            op_keyend(vm, operand)?;
+        }
+        KARG => {
+            op_karg(vm, operand)?;
         }
-        // KEY_P => {
-        //     // op_key_p(vm, &operand)?;
-        // }
-        // KEYEND => {
-        //     // op_keyend(vm, &operand)?;
-        // }
-        // KARG => {
-        //     // op_karg(vm, &operand)?;
-        // }
         RETURN => {
-            op_return(vm, &operand)?;
+            op_return(vm, operand)?;
+        }
+        RETURN_BLK => {
+            op_return_blk(vm, operand)?;
+        }
+        BREAK => {
+            op_break(vm, operand)?;
+        }
+        BLKPUSH => {
+            op_blkpush(vm, operand)?;
         }
-        // RETURN_BLK => {
-        //     // op_return_blk(vm, &operand)?;
-        // }
-        // BREAK => {
-        //     // op_break(vm, &operand)?;
-        // }
-        // BLKPUSH => {
-        //     // op_blkpush(vm, &operand)?;
-        // }
         ADD => {
-            op_add(vm, &operand)?;
+            op_add(vm, operand)?;
         }
         ADDI => {
-            op_addi(vm, &operand)?;
+            op_addi(vm, operand)?;
         }
         SUB => {
-            op_sub(vm, &operand)?;
+            op_sub(vm, operand)?;
         }
         SUBI => {
-            op_subi(vm, &operand)?;
+            op_subi(vm, operand)?;
         }
         MUL => {
-            op_mul(vm, &operand)?;
+            op_mul(vm, operand)?;
         }
         DIV => {
-            op_div(vm, &operand)?;
+            op_div(vm, operand)?;
         }
         EQ => {
-            op_eq(vm, &operand)?;
+            op_eq(vm, operand)?;
         }
         LT => {
-            op_lt(vm, &operand)?;
+            op_lt(vm, operand)?;
         }
         LE => {
-            op_le(vm, &operand)?;
+            op_le(vm, operand)?;
         }
         GT => {
-            op_gt(vm, &operand)?;
+            op_gt(vm, operand)?;
         }
         GE => {
-            op_ge(vm, &operand)?;
+            op_ge(vm, operand)?;
         }
         ARRAY => {
-            op_array(vm, &operand)?;
+            op_array(vm, operand)?;
         }
         ARRAY2 => {
-            op_array2(vm, &operand)?;
+            op_array2(vm, operand)?;
+        }
+        ARYCAT => {
+            op_arycat(vm, operand)?;
+        }
+        ARYPUSH => {
+            op_arypush(vm, operand)?;
+        }
+        ARYSPLAT => {
+            op_arysplat(vm, operand)?;
+        }
+        AREF => {
+            op_aref(vm, operand)?;
+        }
+        ASET => {
+            op_aset(vm, operand)?;
+        }
+        APOST => {
+            op_apost(vm, operand)?;
         }
-        // ARYCAT => {
-        //     // op_arycat(vm, &operand)?;
-        // }
-        // ARYPUSH => {
-        //     // op_arypush(vm, &operand)?;
-        // }
-        // ARYSPLAT => {
-        //     // op_arysplat(vm, &operand)?;
-        // }
-        // AREF => {
-        //     // op_aref(vm, &operand)?;
-        // }
-        // ASET => {
-        //     // op_aset(vm, &operand)?;
-        // }
-        // APOST => {
-        //     // op_apost(vm, &operand)?;
-        // }
         // INTERN => {
-        //     // op_intern(vm, &operand)?;
+        //     // op_intern(vm, operand)?;
         // }
         SYMBOL => {
-            op_symbol(vm, &operand)?;
+            op_symbol(vm, operand)?;
         }
         STRING => {
-            op_string(vm, &operand)?;
+            op_string(vm, operand)?;
         }
         STRCAT => {
-            op_strcat(vm, &operand)?;
+            op_strcat(vm, operand)?;
         }
         HASH => {
-            op_hash(vm, &operand)?;
+            op_hash(vm, operand)?;
+        }
+        HASHADD => {
+            op_hashadd(vm, operand)?;
+        }
+        HASHCAT => {
+            op_hashcat(vm, operand)?;
         }
-        // HASHADD => {
-        //     // op_hashadd(vm, &operand)?;
-        // }
-        // HASHCAT => {
-        //     // op_hashcat(vm, &operand)?;
-        // }
         LAMBDA => {
-            op_lambda(vm, &operand)?;
+            op_lambda(vm, operand)?;
         }
         BLOCK => {
-            op_block(vm, &operand)?;
+            op_block(vm, operand)?;
         }
         METHOD => {
-            op_method(vm, &operand)?;
+            op_method(vm, operand)?;
         }
         RANGE_INC => {
-            op_range_inc(vm, &operand)?;
+            op_range_inc(vm, operand)?;
         }
         RANGE_EXC => {
-            op_range_exc(vm, &operand)?;
+            op_range_exc(vm, operand)?;
         }
         OCLASS => {
-            op_oclass(vm, &operand)?;
+            op_oclass(vm, operand)?;
         }
         CLASS => {
-            op_class(vm, &operand)?;
+            op_class(vm, operand)?;
+        }
+        MODULE => {
+            op_module(vm, operand)?;
         }
-        // MODULE => {
-        //     // op_module(vm, &operand)?;
-        // }
         EXEC => {
-            op_exec(vm, &operand)?;
+            op_exec(vm, operand)?;
         }
         DEF => {
-            op_def(vm, &operand)?;
+            op_def(vm, operand)?;
+        }
+        ALIAS => {
+            op_alias(vm, operand)?;
+        }
+        UNDEF => {
+            op_undef(vm, operand)?;
+        }
+        SCLASS => {
+            op_sclass(vm, operand)?;
         }
-        // ALIAS => {
-        //     // op_alias(vm, &operand)?;
-        // }
-        // UNDEF => {
-        //     // op_undef(vm, &operand)?;
-        // }
-        // SCLASS => {
-        //     // op_sclass(vm, &operand)?;
-        // }
         TCLASS => {
-            op_tclass(vm, &operand)?;
+            op_tclass(vm, operand)?;
         }
         // DEBUG => {
-        //     // op_debug(vm, &operand)?;
+        //     // op_debug(vm, operand)?;
         // }
         // ERR => {
-        //     // op_err(vm, &operand)?;
+        //     // op_err(vm, operand)?;
         // }
         // EXT1 => {
-        //     // op_ext1(vm, &operand)?;
+        //     // op_ext1(vm, operand)?;
         // }
         // EXT2 => {
-        //     // op_ext2(vm, &operand)?;
+        //     // op_ext2(vm, operand)?;
         // }
         // EXT3 => {
-        //     // op_ext3(vm, &operand)?;
+        //     // op_ext3(vm, operand)?;
         // }
         STOP => {
-            op_stop(vm, &operand)?;
+            op_stop(vm, operand)?;
         }
         _ => { unimplemented!("{:?}: Not supported yet", code)}
     }
     Ok(())
 }
 
-pub(crate) fn push_callinfo(vm: &mut VM, method_id: RSym, n_args: usize) {
-    let callinfo = CALLINFO {
+pub(crate) fn push_callinfo(vm: &mut VM, method_id: RSym, n_args: usize) -> Result<(), Error> {
+    let callinfo = new_callinfo(vm, method_id, n_args)?;
+    vm.current_callinfo = Some(Rc::new(callinfo));
+    Ok(())
+}
+
+// Fails with SystemStackError when the call would nest deeper than allowed
+pub(crate) fn new_callinfo(vm: &VM, method_id: RSym, n_args: usize) -> Result<CALLINFO, Error> {
+    let depth = vm.current_callinfo.as_ref().map_or(0, |ci| ci.depth) + 1;
+    if depth > vm.max_call_depth {
+        return Err(Error::SystemStackError("stack level too deep".to_string()));
+    }
+    Ok(CALLINFO {
         prev: vm.current_callinfo.clone(),
         method_id,
         pc_irep: vm.current_irep.clone(),
         pc: vm.pc.get(),
         current_regs_offset: vm.current_regs_offset,
         n_args,
+        depth,
+        kdict_index: Cell::new(None),
+        called_from_rust: false,
+        host_entry: false,
+        is_lambda: true,
         target_class: vm.target_class.clone(),
-    };
-    vm.current_callinfo = Some(Rc::new(callinfo));
+        upper: vm.upper.clone(),
+        block_env: None,
+    })
+}
+
+// Jump offsets are signed 16-bit values relative to the end of the op
+fn jump_target(end_pos: usize, offset: u16) -> usize {
+    (end_pos as isize + offset as i16 as isize) as usize
 }
 
 fn calcurate_pc(irep: &IREP, pc: usize, original_pc: usize) -> usize {
-    let mut next_pc = pc;
+    // backward jump: scan from the beginning
+    let mut next_pc = match irep.code.get(pc) {
+        Some(op) if op.pos <= original_pc => pc,
+        _ => 0,
+    };
     loop {
         let op = irep.code.get(next_pc).expect("cannot fetch op anymore");
         // dbg!((&op, original_pc));
@@ -500,44 +534,53 @@ pub(crate) fn op_nop(_vm: &mut VM, _operand: &Fetched) -> Result<(), Error> {
 pub(crate) fn op_loadi_n(vm: &mut VM, n: i32, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()? as usize;
     let val = RObject::integer(n as i64);
-    vm.current_regs()[a].replace(Rc::new(val));
+    vm.current_regs()[a].replace(val.into_rc());
     Ok(())
 }
 
 pub(crate) fn op_loadl(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
-    let val = vm.current_irep.pool[b as usize].clone();
-    // TODO: support other rpool types?
-    let val = Rc::new(RObject::string(val.as_str().to_string()));
-    vm.current_regs()[a as usize].replace(val);
+    let val = match &vm.current_irep.pool[b as usize] {
+        RPool::Str(s) => RObject::string(s.clone()),
+        RPool::Int(n) => RObject::integer(*n),
+        RPool::Float(f) => RObject::float(*f),
+        RPool::BigInt { base, negative, digits } => {
+            let sign = if *negative { "-" } else { "" };
+            let n = RBigint::from_str_radix(&format!("{}{}", sign, digits), *base)
+                .ok_or_else(|| Error::internal("LOADL: invalid bigint literal"))?;
+            RObject::bigint(n)
+        }
+        RPool::Data(_) => return Err(Error::internal("LOADL: unsupported pool type")),
+    };
+    vm.current_regs()[a as usize].replace(val.into_rc());
     Ok(())
 }
 
 pub(crate) fn op_loadi16(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bs()?;
     let val = RObject::integer(b as i64);
-    vm.current_regs()[a as usize].replace(Rc::new(val));
+    vm.current_regs()[a as usize].replace(val.into_rc());
     Ok(())
 }
 
 pub(crate) fn op_loadi32(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b, c) = operand.as_bss()?;
-    let val = RObject::integer((b as i64) << 16 | c as i64);
-    vm.current_regs()[a as usize].replace(Rc::new(val));
+    let val = RObject::integer(((b as u32) << 16 | c as u32) as i32 as i64);
+    vm.current_regs()[a as usize].replace(val.into_rc());
     Ok(())
 }
 
 pub(crate) fn op_loadi(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let val = RObject::integer(b as i64);
-    vm.current_regs()[a as usize].replace(Rc::new(val));
+    vm.current_regs()[a as usize].replace(val.into_rc());
     Ok(())
 }
 
 pub(crate) fn op_loadineg(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let val = RObject::integer(-(b as i64));
-    vm.current_regs()[a as usize].replace(Rc::new(val));
+    vm.current_regs()[a as usize].replace(val.into_rc());
     Ok(())
 }
 
@@ -551,7 +594,7 @@ pub(crate) fn op_loadsym(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
 pub(crate) fn op_loadnil(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()? as usize;
     let val = RObject::nil();
-    vm.current_regs()[a].replace(Rc::new(val));
+    vm.current_regs()[a].replace(val.into_rc());
     Ok(())
 }
 
@@ -565,14 +608,14 @@ pub(crate) fn op_loadself(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
 pub(crate) fn op_loadt(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()? as usize;
     let val = RObject::boolean(true);
-    vm.current_regs()[a].replace(Rc::new(val));
+    vm.current_regs()[a].replace(val.into_rc());
     Ok(())
 }
 
 pub(crate) fn op_loadf(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()? as usize;
     let val = RObject::boolean(false);
-    vm.current_regs()[a].replace(Rc::new(val));
+    vm.current_regs()[a].replace(val.into_rc());
     Ok(())
 }
 
@@ -595,12 +638,10 @@ pub(crate) fn op_setgv(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
 pub(crate) fn op_getiv(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let this = vm.getself()?;
-    let ivar = match &this.value {
-        RValue::Instance(ins) => ins.ivar.borrow().get(
-            &vm.current_irep.syms[b as usize].name,
-        ).ok_or_else(|| Error::internal(format!("symbol not found {}", b)))?.clone(),
-        _ => unreachable!("getiv must be called on instance")
-    };
+    // unset variables and objects without them read as nil
+    let ivar = this.ivars()
+        .and_then(|ivar| ivar.borrow().get(&vm.current_irep.syms[b as usize]).cloned())
+        .unwrap_or_else(|| RObject::nil().into_rc());
     vm.current_regs()[a as usize].replace(ivar);
     Ok(())
 }
@@ -609,23 +650,62 @@ pub(crate) fn op_setiv(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let this = vm.getself()?;
     let val = vm.get_current_regs_cloned(a as usize)?;
-    match &this.value {
-        RValue::Instance(ins) => {
-            let mut ivar = ins.ivar.borrow_mut();
+    match this.ivars() {
+        Some(ivar) => {
+            let mut ivar = ivar.borrow_mut();
             ivar.insert(
-                vm.current_irep.syms[b as usize].name.clone(),
+                vm.current_irep.syms[b as usize].clone(),
                 val,
             )
         },
-        _ => unreachable!("setiv must be called on instance")
+        None => {
+            let name = &vm.current_irep.syms[b as usize].name;
+            return Err(Error::TypeError(format!("can't set instance variable {} on {}", name, this.get_class(vm).sym_id.name)));
+        }
     };
     Ok(())
 }
 
+// class variables belong to the class where the method is defined;
+// singleton methods use the class of self instead
+fn cvar_base(vm: &mut VM) -> Result<Rc<RClass>, Error> {
+    if !vm.target_class.is_singleton {
+        return Ok(vm.target_class.clone());
+    }
+    let this = vm.getself()?;
+    match &this.value {
+        RValue::Class(klass) => Ok(klass.clone()),
+        RValue::Instance(ins) => Ok(ins.class.clone()),
+        _ => Ok(this.get_class(vm)),
+    }
+}
+
+pub(crate) fn op_getcv(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let name = vm.current_irep.syms[b as usize].name.clone();
+    let klass = cvar_base(vm)?;
+    let val = klass.cvar_get(&name).ok_or_else(|| {
+        Error::NameError(format!("uninitialized class variable {} in {}", name, klass.sym_id.name))
+    })?;
+    vm.current_regs()[a as usize].replace(val);
+    Ok(())
+}
+
+pub(crate) fn op_setcv(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let name = vm.current_irep.syms[b as usize].name.clone();
+    let val = vm.get_current_regs_cloned(a as usize)?;
+    let klass = cvar_base(vm)?;
+    klass.cvar_set(&name, val);
+    Ok(())
+}
+
 pub(crate) fn op_getconst(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let name = &vm.current_irep.syms[b as usize].name;
-    let cval = vm.consts.get(name).cloned();
+    // the class body or method owner first, then the top level
+    let cval = vm.target_class.getmcnst(name)
+        .or_else(|| vm.consts.get(name).cloned());
     match cval {
         Some(val) => {
             vm.current_regs()[a as usize].replace(val);
@@ -641,7 +721,14 @@ pub(crate) fn op_setconst(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let name = vm.current_irep.syms[b as usize].name.clone();
     let val = vm.get_current_regs_cloned(a as usize)?;
-    vm.consts.insert(name, val);
+    match nesting(vm) {
+        Some(outer) => {
+            outer.consts.borrow_mut().insert(name, val);
+        }
+        None => {
+            vm.consts.insert(name, val);
+        }
+    }
     Ok(())
 }
 
@@ -672,9 +759,10 @@ pub(crate) fn op_getupvar(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
         environ = environ.upper.as_ref().ok_or_else(|| Error::internal("op_getupvar failed to find upvar"))?;
     }
     let environ = environ.clone();
-    let up_regs = &vm.regs[environ.current_regs_offset..];
     if !environ.expired() {
-        if let Some(val) = up_regs[b as usize].as_ref().cloned() {
+        let index = environ.current_regs_offset + b as usize;
+        let val = with_stack(vm, &environ.stack, |regs| regs[index].clone())?;
+        if let Some(val) = val {
             vm.current_regs()[a as usize].replace(val);
         } else {
             return Err(Error::internal(format!("register {} is empty", b)));
@@ -700,9 +788,8 @@ pub(crate) fn op_setupvar(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
 
     let val = vm.get_current_regs_cloned(a as usize)?;
     if !environ.expired() {
-        let up_regs = &mut vm.regs[current_regs_offset..];
-        let target = &mut up_regs[b as usize];
-        target.replace(val);
+        let index = current_regs_offset + b as usize;
+        with_stack(vm, &environ.stack, |regs| regs[index].replace(val))?;
     } else {
         let mut captured = environ.captured.borrow_mut();
         let captured = captured.as_mut().ok_or_else(|| Error::internal("captured environment not found"))?;
@@ -735,7 +822,23 @@ pub(crate) fn op_setidx(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     
 pub(crate) fn op_jmp(vm: &mut VM, operand: &Fetched, end_pos: usize) -> Result<(), Error> {
     let a = operand.as_s()?;
-    let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), end_pos + a as usize);
+    let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), jump_target(end_pos, a));
+    vm.pc.set(next_pc);
+    Ok(())
+}
+
+pub(crate) fn op_jmpuw(vm: &mut VM, operand: &Fetched, end_pos: usize) -> Result<(), Error> {
+    let a = operand.as_s()?;
+    let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), jump_target(end_pos, a));
+    if find_ensure_handler(vm, Some(next_pc)).is_some() {
+        vm.break_object = Some(Rc::new(RBreak {
+            target: vm.current_callinfo.clone(),
+            home: None,
+            jump: Some(next_pc),
+            value: RObject::nil().into_rc(),
+        }));
+        return Err(Error::Break);
+    }
     vm.pc.set(next_pc);
     Ok(())
 }
@@ -744,7 +847,7 @@ pub(crate) fn op_jmpif(vm: &mut VM, operand: &Fetched, end_pos: usize) -> Result
     let (a, b) = operand.as_bs()?;
     let val = vm.get_current_regs_cloned(a as usize)?;
     if val.is_truthy() {
-        let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), end_pos + b as usize);
+        let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), jump_target(end_pos, b));
         vm.pc.set(next_pc);
     }
     Ok(())
@@ -754,7 +857,7 @@ pub(crate) fn op_jmpnot(vm: &mut VM, operand: &Fetched, end_pos: usize) -> Resul
     let (a, b) = operand.as_bs()?;
     let val = vm.get_current_regs_cloned(a as usize)?;
     if val.is_falsy() {
-        let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), end_pos + b as usize);
+        let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), jump_target(end_pos, b));
         vm.pc.set(next_pc);
     }
     Ok(())
@@ -764,7 +867,7 @@ pub(crate) fn op_jmpnil(vm: &mut VM, operand: &Fetched, end_pos: usize) -> Resul
     let (a, b) = operand.as_bs()?;
     let val = vm.get_current_regs_cloned(a as usize)?;
     if val.is_nil() {
-        let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), end_pos + b as usize);
+        let next_pc = calcurate_pc(&vm.current_irep, vm.pc.get(), jump_target(end_pos, b));
         vm.pc.set(next_pc);
     }
     Ok(())
@@ -772,8 +875,11 @@ pub(crate) fn op_jmpnil(vm: &mut VM, operand: &Fetched, end_pos: usize) -> Resul
 
 pub(crate) fn op_except(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()?;
-    let val = vm.exception.take().ok_or_else(|| Error::internal("exception not found"))?;
-    let exc = Rc::new(RObject::exception(val));
+    // nil when entering an ensure clause normally
+    let exc = match vm.exception.take() {
+        Some(val) => val.to_object(),
+        None => RObject::nil().into_rc(),
+    };
     vm.current_regs()[a as usize].replace(exc);
     Ok(())
 }
@@ -784,8 +890,7 @@ pub(crate) fn op_rescue(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let exc_klass = vm.take_current_regs(b as usize)?;
     match (&val.value, exc_klass.value.clone()) {
         (RValue::Exception(exc), RValue::Class(klass)) => {
-            let etype = exc.error_type.borrow();
-            let is_rescued = etype.is_a(vm, klass);
+            let is_rescued = exc.is_a(&klass);
             let val = RObject::boolean(is_rescued);
             vm.current_regs()[b as usize].replace(val.to_refcount_assigned());
         }
@@ -801,7 +906,11 @@ pub(crate) fn op_raiseif(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
         Some(val) => {
             match &val.value {
                 RValue::Exception(e) => {
-                    return Err(e.as_ref().error_type.borrow().clone());
+                    // resume the break or return suspended by the ensure clause
+                    if let Some(brk) = &e.break_object {
+                        vm.break_object = Some(brk.clone());
+                    }
+                    return Err(e.to_error());
                 }
                 _ => {}
             }
@@ -820,61 +929,118 @@ pub(crate) fn op_move(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
 
 pub(crate) fn op_ssend(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b, c) = operand.as_bbb()?;
-    do_op_send(vm, 0, None, a, b, c)
+    do_op_send(vm, 0, false, a, b, c)
 }
 
 pub(crate) fn op_ssendb(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b, c) = operand.as_bbb()?;
-    do_op_send(vm, 0, Some(a as usize + c as usize + 1), a, b, c)
+    do_op_send(vm, 0, true, a, b, c)
 }
 
 pub(crate) fn op_send(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b, c) = operand.as_bbb()?;
-    do_op_send(vm, a as usize, None, a, b, c)
+    do_op_send(vm, a as usize, false, a, b, c)
 }
 
 pub(crate) fn op_sendb(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b, c) = operand.as_bbb()?;
-    do_op_send(vm, a as usize, Some(a as usize + c as usize + 1), a, b, c)
+    do_op_send(vm, a as usize, true, a, b, c)
 }
 
-pub(crate) fn do_op_send(vm: &mut VM, recv_index: usize, blk_index: Option<usize>, a: u8, b: u8, c: u8) -> Result<(), Error> {
-    let block_index = (a + c + 1) as usize;
+// Operand c of SEND family is packed as n|nk<<4:
+// n is the count of positional args, and nk is the count of
+// keyword pairs (15 means a keyword hash is passed in a single register)
+pub(crate) fn do_op_send(vm: &mut VM, recv_index: usize, with_block: bool, a: u8, b: u8, c: u8) -> Result<(), Error> {
+    let a = a as usize;
+    let n = (c & 0x0f) as usize;
+    let nk = (c >> 4) as usize;
+    let nregs = if n == CALL_MAXARGS { 1 } else { n };
+    let kidx = a + nregs + 1;
+    let block_index = match nk {
+        0 => kidx,
+        CALL_MAXARGS => kidx + 1,
+        nk => kidx + nk * 2,
+    };
 
     let recv = vm.get_current_regs_cloned(recv_index)?;
-    let mut args = (0..c)
-        .map(|i| vm.get_current_regs_cloned((a + i + 1) as usize).expect("args too short for required"))
-        .collect::<Vec<_>>();
-    if let Some(blk_index) = blk_index {
-        args.push(vm.get_current_regs_cloned(blk_index)?);
+    let kdict = match nk {
+        0 => None,
+        CALL_MAXARGS => Some(vm.get_current_regs_cloned(kidx)?),
+        nk => Some(pack_kargs(vm, kidx, nk)?),
+    };
+    let block = if with_block {
+        let block = vm.get_current_regs_cloned(block_index)?;
+        // `&:name` passes a symbol, which is converted as Symbol#to_proc
+        match &block.value {
+            RValue::Symbol(sym) => symbol_to_proc(vm, sym),
+            _ => block,
+        }
     } else {
-        args.push(Rc::new(RObject::nil()));
-    }
+        RObject::nil().into_rc()
+    };
 
     let method_id = vm.current_irep.syms[b as usize].clone();
     let klass = recv.get_class(vm);
-    let method = klass.find_method(&method_id.name).ok_or_else(|| {
+    // the op being executed is the call site
+    let site = vm.pc.get() - 1;
+    let (owner, method) = vm.current_irep.method_cache.find_method(vm.method_state, site, &klass, &method_id).ok_or_else(|| {
         Error::NoMethodError(method_id.name.clone())
     })?;
 
-    vm.current_regs()[a as usize].replace(recv.clone());
-    if !method.is_rb_func {
+    // a block literal of this frame is orphaned when the call returns
+    let block_env = match &block.value {
+        RValue::Proc(p) if !p.is_lambda => p.environ.clone()
+            .filter(|env| same_frame(&env.callinfo, &vm.current_callinfo)),
+        _ => None,
+    };
+
+    // `yield` and Proc#call run a block on the frames of this run
+    // rather than in a nested one, so that Fiber.yield and VM::step
+    // can suspend it
+    let called_block = match &recv.value {
+        RValue::Proc(p) if p.is_rb_func && !method.is_rb_func
+            && Rc::ptr_eq(&owner, &vm.get_class_by_name("Proc")) => Some(p.clone()),
+        _ => None,
+    };
+
+    vm.current_regs()[a].replace(recv.clone());
+    if !method.is_rb_func && called_block.is_none() {
         let func = vm.get_fn(method.func.unwrap()).ok_or_else(|| Error::internal("function not found"))?;
-        vm.current_regs_offset += a as usize;
+        let mut args = if n == CALL_MAXARGS {
+            splat_args(vm.get_current_regs_cloned(a + 1)?)?
+        } else {
+            (0..n)
+                .map(|i| vm.get_current_regs_cloned(a + i + 1))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        vm.kargs_given = kdict.is_some();
+        if let Some(kdict) = kdict {
+            args.push(kdict);
+        }
+        args.push(block);
+        vm.current_regs_offset += a;
 
         let res = func(vm, &args);
+        if let Some(env) = &block_env {
+            env.is_orphan.set(true);
+        }
 
-        vm.current_regs_offset -= a as usize;
-        for i in (a as usize + 1)..block_index {
+        vm.current_regs_offset -= a;
+        for i in (a + 1)..block_index {
             vm.current_regs()[i].take();
         }
 
         match res {
             Ok(val) => {
-                vm.current_regs()[a as usize].replace(val);
+                vm.current_regs()[a].replace(val);
+            }
+            Err(Error::Break) if is_break_target(vm) => {
+                // `break` in the block given to this call
+                let brk = vm.break_object.take().ok_or_else(|| Error::internal("break object not found"))?;
+                vm.current_regs()[a].replace(brk.value.clone());
             }
             Err(e) => {
-                vm.current_regs()[a as usize].replace(Rc::new(RObject::nil()));
+                vm.current_regs()[a].replace(RObject::nil().into_rc());
                 return Err(e);
             }
         }
@@ -882,16 +1048,70 @@ pub(crate) fn do_op_send(vm: &mut VM, recv_index: usize, blk_index: Option<usize
         return Ok(());
     }
 
-    push_callinfo(vm, method_id, c as usize);
+    // Callee expects the keyword hash right after positional args,
+    // and the block next to it
+    let has_kdict = kdict.is_some();
+    match kdict {
+        Some(kdict) => {
+            vm.current_regs()[kidx].replace(kdict);
+            vm.current_regs()[kidx + 1].replace(block);
+        }
+        None => {
+            vm.current_regs()[kidx].replace(block);
+        }
+    }
+
+    let method_id = match &called_block {
+        Some(block) => block.sym_id.clone().unwrap_or_else(|| RSym::new("<block>".to_string())),
+        None => method_id,
+    };
+    let mut callinfo = new_callinfo(vm, method_id, n)?;
+    callinfo.block_env = block_env;
+    if has_kdict {
+        callinfo.kdict_index.set(Some(nregs + 1));
+    }
+    let method = match called_block {
+        Some(block) => {
+            callinfo.is_lambda = block.is_lambda;
+            let block_self = block.block_self.clone()
+                .ok_or_else(|| Error::RuntimeError("No block self assigned".to_string()))?;
+            vm.current_regs()[a].replace(block_self);
+            block
+        }
+        None => {
+            vm.target_class = owner;
+            method
+        }
+    };
+    vm.current_callinfo = Some(Rc::new(callinfo));
 
     vm.pc.set(0);
+    // methods defined by blocks keep their environment
+    vm.upper = method.environ.clone();
     vm.current_irep = method.irep.ok_or_else(|| Error::internal("empry irep"))?;
-    vm.current_regs_offset += a as usize;
+    vm.current_regs_offset += a;
     Ok(())
 }
 
+fn pack_kargs(vm: &mut VM, start: usize, nk: usize) -> Result<Rc<RObject>, Error> {
+    let mut hash = HashMap::new();
+    for i in 0..nk {
+        let key = vm.take_current_regs(start + i * 2)?;
+        let val = vm.take_current_regs(start + i * 2 + 1)?;
+        hash.insert(key.as_hash_key()?, (key, val));
+    }
+    Ok(RObject::hash(hash).into_rc())
+}
+
+fn splat_args(packed: Rc<RObject>) -> Result<Vec<Rc<RObject>>, Error> {
+    match &packed.value {
+        RValue::Array(ary) => Ok(ary.borrow().clone()),
+        _ => Err(Error::internal("packed arguments must be an array")),
+    }
+}
+
 pub(crate) fn op_call(vm: &mut VM, _operand: &Fetched) -> Result<(), Error> {
-    push_callinfo(vm, "<tailcall>".into(), 0);
+    push_callinfo(vm, "<tailcall>".into(), 0)?;
 
     vm.pc.set(0);
     let proc = vm.current_regs()[0].as_ref().cloned().ok_or_else(|| Error::internal("proc not found"))?;
@@ -908,25 +1128,34 @@ pub(crate) fn op_super(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let sym_id = vm.current_callinfo.as_ref()
         .ok_or_else(|| Error::internal("no current callinfo"))?
-        .method_id.name.clone();
+        .method_id.clone();
     let recv = vm.getself()?;
-    let args = (0..b)
-        .map(|i| vm.get_current_regs_cloned((a + i + 1) as usize).expect("args too short for super"))
-        .collect::<Vec<_>>();
-
-    let klass = match &recv.value {
-        RValue::Instance(ins) => ins.class.as_ref(),
-        _ => unreachable!("super must be called on instance"),
+    let nregs = if b as usize == CALL_MAXARGS { 1 } else { b };
+    let args = if b as usize == CALL_MAXARGS {
+        splat_args(vm.get_current_regs_cloned(a as usize + 1)?)?
+    } else {
+        (0..b)
+            .map(|i| vm.get_current_regs_cloned((a + i + 1) as usize).expect("args too short for super"))
+            .collect::<Vec<_>>()
     };
-    let superclass = klass.super_class.as_ref().ok_or_else(|| Error::internal("superclass not found"))?;
-    let sc_procs = superclass.procs.borrow();
-    let method = sc_procs.get(&sym_id)
-        .ok_or_else(|| Error::NoMethodError(sym_id.clone()))?;
+
+    // search the method next to the current method's owner
+    let ancestors = recv.get_class(vm).ancestors();
+    let (owner, method) = ancestors.iter()
+        .position(|k| Rc::ptr_eq(k, &vm.target_class))
+        .and_then(|pos| {
+            ancestors[(pos + 1)..].iter().find_map(|k| {
+                k.procs.borrow().get(&sym_id).map(|m| (k.clone(), m.clone()))
+            })
+        })
+        .filter(|(_, m)| !m.is_undefined())
+        .ok_or_else(|| Error::NoMethodError(sym_id.name.clone()))?;
     if !method.is_rb_func {
         let func = vm.get_fn(method.func.unwrap())
-            .ok_or_else(|| Error::internal(format!("functon registerd but no entry found: {}", &sym_id)))?;
+            .ok_or_else(|| Error::internal(format!("functon registerd but no entry found: {}", &sym_id.name)))?;
+        vm.kargs_given = false;
         let res = func(vm, &args);
-        for i in (a as usize + 1)..(a as usize + b as usize + 1) {
+        for i in (a as usize + 1)..(a as usize + nregs as usize + 1) {
             vm.current_regs()[i].take();
         }
         match res {
@@ -934,7 +1163,7 @@ pub(crate) fn op_super(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
                 vm.current_regs()[a as usize].replace(val);
             }
             Err(e) => {
-                vm.current_regs()[a as usize].replace(Rc::new(RObject::nil()));
+                vm.current_regs()[a as usize].replace(RObject::nil().into_rc());
                 return Err(e);
             }
             
@@ -943,9 +1172,11 @@ pub(crate) fn op_super(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     }
 
     vm.current_regs()[a as usize].replace(recv.clone());
-    push_callinfo(vm, method.sym_id.clone().unwrap(), b as usize);
+    push_callinfo(vm, method.sym_id.clone().unwrap(), b as usize)?;
 
     vm.pc.set(0);
+    vm.upper = method.environ.clone();
+    vm.target_class = owner;
     vm.current_irep = method.irep.as_ref().ok_or_else(|| Error::internal("empty irep"))?.clone();
     vm.current_regs_offset += a as usize;
     Ok(())
@@ -979,20 +1210,224 @@ impl From<u32> for EnterArgInfo {
 pub(crate) fn op_enter(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_w()?;
     let arg_info = EnterArgInfo::from(a);
-    let m1_argc = arg_info.m1 as usize;
-    for i in 0..m1_argc {
-        match vm.current_regs()[i + 1].as_ref() {
-            Some(_) => {}
-            None => {
-                unreachable!("argument {} not passed", i + 1);
-            }
+    let ci = match vm.current_callinfo.clone() {
+        Some(ci) => ci,
+        None => return Ok(()),
+    };
+    let m1 = arg_info.m1 as usize;
+    let o = arg_info.o as usize;
+    let r = arg_info.r as usize;
+    let m2 = arg_info.m2 as usize;
+    let kd = arg_info.k > 0 || arg_info.d > 0;
+    let len = m1 + o + r + m2;
+    // blocks (not lambdas nor methods) accept any number of arguments
+    let strict = ci.is_lambda;
+
+    // n_args == 15 means the arguments are packed into an array at R[1]
+    let packed = ci.n_args == CALL_MAXARGS;
+    let nregs = if packed { 1 } else { ci.n_args };
+    let kdict_index = ci.kdict_index.get();
+    let blk_index = nregs + 1 + kdict_index.is_some() as usize;
+    let blk = vm.current_regs()[blk_index].take();
+    let mut kdict = match kdict_index {
+        Some(i) => vm.current_regs()[i].take(),
+        None => None,
+    };
+
+    let mut argv = Vec::with_capacity(nregs);
+    for i in 1..=nregs {
+        argv.push(vm.current_regs()[i].take().unwrap_or_else(|| RObject::nil().into_rc()));
+    }
+    if packed {
+        argv = match &argv[0].value {
+            RValue::Array(ary) => ary.borrow().clone(),
+            _ => return Err(Error::internal("packed arguments must be an array")),
+        };
+    }
+
+    // yield packs its keywords into a trailing hash, which a block
+    // with keyword params takes as the keywords
+    if kd && !strict && kdict.is_none() && argv.len() > m1 + m2
+        && argv.last().is_some_and(|h| matches!(h.value, RValue::Hash(_)))
+    {
+        kdict = argv.pop();
+    }
+
+    if !kd {
+        // keywords passed to a method without keyword params
+        // are treated as a trailing positional hash
+        if let Some(h) = kdict.take() && !hash_is_empty(&h) {
+            argv.push(h);
+        }
+    }
+
+    if !strict && len > 1 && argv.len() == 1 {
+        let first = argv[0].clone();
+        if let RValue::Array(ary) = &first.value {
+            argv = ary.borrow().clone();
+        }
+    }
+    let argc = argv.len();
+
+    if strict && (argc < m1 + m2 || (r == 0 && argc > len)) {
+        let expected = if r > 0 {
+            format!("{}+", m1 + m2)
+        } else if o > 0 {
+            format!("{}..{}", m1 + m2, len)
+        } else {
+            (m1 + m2).to_string()
+        };
+        return Err(Error::ArgumentError(format!(
+            "wrong number of arguments (given {}, expected {})",
+            argc, expected
+        )));
+    }
+
+    for i in 1..=len {
+        vm.current_regs()[i].replace(RObject::nil().into_rc());
+    }
+    let skip = if argc < len {
+        // post args are filled before optional ones
+        let mlen = if argc < m1 + m2 {
+            argc.saturating_sub(m1)
+        } else {
+            m2
+        };
+        for (i, v) in argv[..(argc - mlen)].iter().enumerate() {
+            vm.current_regs()[i + 1].replace(v.clone());
+        }
+        for (i, v) in argv[(argc - mlen)..].iter().enumerate() {
+            vm.current_regs()[len - m2 + i + 1].replace(v.clone());
+        }
+        if r > 0 {
+            vm.current_regs()[m1 + o + 1].replace(RObject::array(vec![]).into_rc());
+        }
+        (argc.saturating_sub(m1 + m2)).min(o)
+    } else {
+        for (i, v) in argv[..(m1 + o)].iter().enumerate() {
+            vm.current_regs()[i + 1].replace(v.clone());
         }
+        let rest_end = argc - m2;
+        if r > 0 {
+            let rest = argv[(m1 + o)..rest_end].to_vec();
+            vm.current_regs()[m1 + o + 1].replace(RObject::array(rest).into_rc());
+        }
+        for (i, v) in argv[rest_end..].iter().enumerate() {
+            vm.current_regs()[m1 + o + r + i + 1].replace(v.clone());
+        }
+        o
+    };
+    // skip the jump table of optional args initializers
+    vm.pc.set(vm.pc.get() + skip);
+
+    let kw_pos = len + 1;
+    let blk_pos = kw_pos + kd as usize;
+    for i in kw_pos..=blk_index.max(blk_pos) {
+        vm.current_regs()[i].take();
     }
+    if kd {
+        let kdict = match kdict {
+            Some(h) => hash_dup(&h)?,
+            None => RObject::hash(HashMap::new()).into_rc(),
+        };
+        vm.current_regs()[kw_pos].replace(kdict);
+        ci.kdict_index.set(Some(kw_pos));
+    } else {
+        ci.kdict_index.set(None);
+    }
+    vm.current_regs()[blk_pos].replace(blk.unwrap_or_else(|| RObject::nil().into_rc()));
     Ok(())
 }
 
+fn hash_is_empty(hash: &RObject) -> bool {
+    match &hash.value {
+        RValue::Hash(h) => h.borrow().is_empty(),
+        _ => false,
+    }
+}
+
+fn hash_dup(hash: &RObject) -> Result<Rc<RObject>, Error> {
+    match &hash.value {
+        RValue::Hash(h) => Ok(RObject::hash(h.borrow().clone()).into_rc()),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn current_kdict(vm: &mut VM) -> Option<Rc<RObject>> {
+    let index = vm.current_callinfo.as_ref()?.kdict_index.get()?;
+    vm.current_regs()[index].clone()
+}
+
+fn inspect_key(key: &RObject) -> String {
+    match &key.value {
+        RValue::Symbol(sym) => format!(":{}", sym.name),
+        RValue::String(s) => format!("{:?}", String::from_utf8_lossy(&s.borrow())),
+        RValue::Integer(i) => i.to_string(),
+        v => format!("{:?}", v),
+    }
+}
+
+pub(crate) fn op_key_p(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let key = RObject::symbol(vm.current_irep.syms[b as usize].clone());
+    let found = match current_kdict(vm) {
+        Some(kdict) => match &kdict.value {
+            RValue::Hash(h) => h.borrow().contains_key(&key.as_hash_key()?),
+            _ => false,
+        },
+        None => false,
+    };
+    vm.current_regs()[a as usize].replace(RObject::boolean(found).into_rc());
+    Ok(())
+}
+
+pub(crate) fn op_keyend(vm: &mut VM, _operand: &Fetched) -> Result<(), Error> {
+    if let Some(kdict) = current_kdict(vm)
+        && let RValue::Hash(h) = &kdict.value
+        && let Some((key, _)) = h.borrow().values().next()
+    {
+        return Err(Error::ArgumentError(format!("unknown keyword: {}", inspect_key(key))));
+    }
+    Ok(())
+}
+
+pub(crate) fn op_karg(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let key = RObject::symbol(vm.current_irep.syms[b as usize].clone());
+    let value = match current_kdict(vm) {
+        Some(kdict) => match &kdict.value {
+            RValue::Hash(h) => {
+                let key = key.as_hash_key()?;
+                kdict.with_resize(|| h.borrow_mut().remove(&key)).map(|(_, v)| v)
+            }
+            _ => None,
+        },
+        None => None,
+    };
+    match value {
+        Some(value) => {
+            vm.current_regs()[a as usize].replace(value);
+            Ok(())
+        }
+        None => Err(Error::ArgumentError(format!("missing keyword: {}", inspect_key(&key)))),
+    }
+}
+
 pub(crate) fn op_return(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()? as usize;
+    // leave the method via the ensure clauses protecting this position
+    if vm.break_object.is_none() && vm.exception.is_none()
+        && let Some(ci) = vm.current_callinfo.clone()
+        && find_ensure_handler(vm, None).is_some() {
+        let value = vm.get_current_regs_cloned(a)?;
+        vm.break_object = Some(Rc::new(RBreak {
+            target: ci.prev.clone(),
+            home: Some(ci),
+            jump: None,
+            value,
+        }));
+        return Err(Error::Break);
+    }
     let old_irep = vm.current_irep.clone();
     let nregs = old_irep.nregs;
 
@@ -1009,17 +1444,15 @@ pub(crate) fn op_return(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     if let Some(regs_a) = regs0[a].take() {
         regs0[0].replace(regs_a);
     }
-    if nregs > 0 {
-        for i in 1..=nregs {
-            regs0[i].take();
-        }
+    for reg in regs0.iter_mut().take(nregs).skip(1) {
+        reg.take();
     }
 
     let ci = vm.current_callinfo.take();
     if ci.is_none() {
         // When called from mrb_funcall, return error if there's an exception
         if let Some(e) = &vm.exception {
-            return Err(e.error_type.borrow().clone());
+            return Err(e.to_error());
         }
         // For normal completion, set preemption flag and terminate
         vm.flag_preemption.set(true);
@@ -1027,6 +1460,9 @@ pub(crate) fn op_return(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     }
 
     let ci = ci.unwrap();
+    if let Some(env) = &ci.block_env {
+        env.is_orphan.set(true);
+    }
     if let Some(prev) = &ci.prev {
         vm.current_callinfo.replace(prev.clone());
     }
@@ -1034,20 +1470,201 @@ pub(crate) fn op_return(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     vm.pc.set(ci.pc);
     vm.current_regs_offset = ci.current_regs_offset;
     vm.target_class = ci.target_class.clone();
+    vm.upper = ci.upper.clone();
     if vm.current_regs()[0].is_none() {
         todo!("debug");
     }
+    if ci.called_from_rust {
+        // Back to the Rust caller: leave the nested VM::run loop
+        if let Some(e) = &vm.exception {
+            return Err(e.to_error());
+        }
+        vm.flag_preemption.set(true);
+    }
     Ok(())
 }
 
+fn same_frame(a: &Option<Rc<CALLINFO>>, b: &Option<Rc<CALLINFO>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// Whether the frame is still on the call stack
+fn is_live_frame(vm: &VM, frame: &Option<Rc<CALLINFO>>) -> bool {
+    let mut ci = vm.current_callinfo.clone();
+    loop {
+        if same_frame(&ci, frame) {
+            return true;
+        }
+        match ci {
+            Some(c) => ci = c.prev.clone(),
+            None => return false,
+        }
+    }
+}
+
+fn is_break_target(vm: &VM) -> bool {
+    match &vm.break_object {
+        Some(brk) => brk.home.is_none() && same_frame(&vm.current_callinfo, &brk.target),
+        None => false,
+    }
+}
+
+// lambdas and methods defined by blocks return from themselves
+// on `break` or `return`
+fn in_lambda(vm: &VM) -> bool {
+    match &vm.current_callinfo {
+        Some(ci) => ci.is_lambda,
+        None => false,
+    }
+}
+
+pub(crate) fn op_break(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    if in_lambda(vm) {
+        return op_return(vm, operand);
+    }
+    let a = operand.as_b()? as usize;
+    let value = vm.get_current_regs_cloned(a)?;
+    let environ = vm.upper.as_ref().ok_or_else(|| Error::LocalJumpError("break from proc-closure".to_string()))?;
+    let target = environ.callinfo.clone();
+    if environ.is_orphan.get() || !is_live_frame(vm, &target) {
+        return Err(Error::LocalJumpError("break from proc-closure".to_string()));
+    }
+    vm.break_object = Some(Rc::new(RBreak { target, home: None, jump: None, value }));
+    Err(Error::Break)
+}
+
+pub(crate) fn op_return_blk(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    if in_lambda(vm) || vm.upper.is_none() {
+        return op_return(vm, operand);
+    }
+    let a = operand.as_b()? as usize;
+    let value = vm.get_current_regs_cloned(a)?;
+    // the outermost environment belongs to the method defining the block
+    let mut environ = vm.upper.clone().ok_or_else(|| Error::internal("block env not found"))?;
+    while let Some(upper) = environ.upper.clone() {
+        environ = upper;
+    }
+    let home = match &environ.callinfo {
+        Some(home) if is_live_frame(vm, &environ.callinfo) => home.clone(),
+        _ => return Err(Error::LocalJumpError("unexpected return".to_string())),
+    };
+    vm.break_object = Some(Rc::new(RBreak {
+        target: home.prev.clone(),
+        home: Some(home),
+        jump: None,
+        value,
+    }));
+    Err(Error::Break)
+}
+
+// The innermost ensure handler covering the current op in the current frame.
+// For a jump, handlers also covering the destination are not left.
+fn find_ensure_handler(vm: &VM, jump: Option<usize>) -> Option<CatchHandler> {
+    let pc = vm.pc.get().checked_sub(1)?;
+    vm.current_irep.catch_handlers.iter().rev()
+        .find(|h| {
+            h.type_ == CatchType::Ensure && h.covers(pc)
+                && !jump.map(|j| h.covers(j)).unwrap_or(false)
+        })
+        .cloned()
+}
+
+// Pops the frames entered in the current VM::run without running
+// any rescue or ensure clause, for an error Ruby must not handle.
+pub(crate) fn unwind_run(vm: &mut VM, e: &Error) {
+    vm.break_object.take();
+    let exception = RException::from_error(vm, e);
+    vm.exception = Some(Rc::new(exception));
+    // op_return fails at the frame entered from Rust, or at the top level
+    while vm.current_callinfo.is_some() && op_return(vm, &Fetched::B(0)).is_ok() {}
+    vm.exception.take();
+}
+
+// Pops frames for a pending break or return-from-block.
+// Returns Err(Error::Break) when a frame entered from Rust is popped,
+// so that the nested VM::run exits and the Rust caller propagates it.
+// An ensure clause on the way suspends the unwinding; RAISEIF at its end
+// resumes it.
+pub(crate) fn unwind_break(vm: &mut VM) -> Result<(), Error> {
+    let brk = vm.break_object.clone().ok_or_else(|| Error::internal("break object not found"))?;
+    loop {
+        if let Some(handler) = find_ensure_handler(vm, brk.jump) {
+            vm.break_object.take();
+            let exception = RException::from_break(vm, brk);
+            vm.exception = Some(Rc::new(exception));
+            vm.pc.set(handler.target);
+            return Ok(());
+        }
+        if let Some(pc) = brk.jump {
+            vm.break_object.take();
+            vm.pc.set(pc);
+            return Ok(());
+        }
+        if brk.home.is_none() && same_frame(&vm.current_callinfo, &brk.target) {
+            vm.break_object.take();
+            return Ok(());
+        }
+        let ci = match vm.current_callinfo.clone() {
+            Some(ci) => ci,
+            None => {
+                vm.break_object.take();
+                return Err(Error::LocalJumpError("break from proc-closure".to_string()));
+            }
+        };
+        vm.current_regs()[0].replace(brk.value.clone());
+        op_return(vm, &Fetched::B(0))?;
+
+        let is_home = match &brk.home {
+            Some(home) => Rc::ptr_eq(home, &ci),
+            None => false,
+        };
+        if is_home {
+            // a method entered from Rust returns normally
+            vm.break_object.take();
+            return Ok(());
+        }
+        if ci.called_from_rust {
+            vm.flag_preemption.set(false);
+            return Err(Error::Break);
+        }
+    }
+}
+
+pub(crate) fn op_blkpush(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bs()?;
+    let m1 = ((b >> 11) & 0x3f) as usize;
+    let r = ((b >> 10) & 0x1) as usize;
+    let m2 = ((b >> 5) & 0x1f) as usize;
+    let kd = ((b >> 4) & 0x1) as usize;
+    let lv = (b & 0xf) as usize;
+
+    let idx = m1 + r + m2 + kd + 1;
+    let block = if lv == 0 {
+        vm.current_regs()[idx].clone()
+    } else {
+        get_upvar_reg(vm, idx, lv - 1)?
+    };
+    match block {
+        Some(block) if !block.is_nil() => {
+            vm.current_regs()[a as usize].replace(block);
+            Ok(())
+        }
+        _ => Err(Error::LocalJumpError("no block given (yield)".to_string())),
+    }
+}
+
 pub(crate) fn op_add(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()? as usize;
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
     let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            Rc::new(RObject::integer(n1 + n2))
+        (RValue::Integer(_) | RValue::Bigint(_), RValue::Integer(_) | RValue::Bigint(_)) => {
+            integer_add(&val1, &val2).unwrap().into_rc()
         }
         (RValue::Float(n1), RValue::Float(n2)) => {
             Rc::new(RObject::float(n1 + n2))
@@ -1059,11 +1676,10 @@ pub(crate) fn op_add(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
             Rc::new(RObject::float(n1 + *n2 as f64))
         }
         (RValue::String(n1), RValue::String(n2)) => {
-            let mut n1 = n1.borrow_mut();
-            let n2 = n2.borrow();
-            for c in n2.iter() {
-                n1.push(*c);
-            }
+            vm.check_memory(n2.borrow().len())?;
+            val1.with_resize(|| {
+                n1.borrow_mut().extend_from_slice(&n2.borrow());
+            });
             val1.clone()
         }
         _ => {
@@ -1078,16 +1694,14 @@ pub(crate) fn op_add(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
 pub(crate) fn op_addi(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let val1 = vm.take_current_regs(a as usize)?;
-    let val2 = b as i64;
-    let result = match &val1.value {
-        RValue::Integer(n1) => {
-            RObject::integer(*n1 + val2)
-        }
-        _ => {
+    let val2 = RObject::integer(b as i64);
+    let result = match integer_add(&val1, &val2) {
+        Some(v) => v,
+        None => {
             unreachable!("addi supports only integer")
         }
     };
-    vm.current_regs()[a as usize].replace(Rc::new(result));
+    vm.current_regs()[a as usize].replace(result.into_rc());
     Ok(())
 }
 
@@ -1096,31 +1710,28 @@ pub(crate) fn op_sub(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
-    let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            RObject::integer(n1 - n2)
-        }
-        _ => {
-            unreachable!("sub supports only integer")
+    let result = match integer_sub(&val1, &val2) {
+        Some(v) => v.into_rc(),
+        None => {
+            let args = vec![val2.clone()];
+            mrb_funcall(vm, Some(val1.clone()), "-", &args)?
         }
     };
-    vm.current_regs()[a].replace(Rc::new(result));
+    vm.current_regs()[a].replace(result);
     Ok(())
 }
 
 pub(crate) fn op_subi(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let val1 = vm.take_current_regs(a as usize)?;
-    let val2 = b as i64;
-    let result = match &val1.value {
-        RValue::Integer(n1) => {
-            RObject::integer(*n1 - val2)
-        }
-        _ => {
+    let val2 = RObject::integer(b as i64);
+    let result = match integer_sub(&val1, &val2) {
+        Some(v) => v,
+        None => {
             unreachable!("subi supports only integer")
         }
     };
-    vm.current_regs()[a as usize].replace(Rc::new(result));
+    vm.current_regs()[a as usize].replace(result.into_rc());
     Ok(())
 }
 
@@ -1129,15 +1740,14 @@ pub(crate) fn op_mul(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
-    let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            RObject::integer(n1 * n2)
-        }
-        _ => {
-            unreachable!("mul supports only integer")
+    let result = match integer_mul(&val1, &val2) {
+        Some(v) => v.into_rc(),
+        None => {
+            let args = vec![val2.clone()];
+            mrb_funcall(vm, Some(val1.clone()), "*", &args)?
         }
     };
-    vm.current_regs()[a].replace(Rc::new(result));
+    vm.current_regs()[a].replace(result);
     Ok(())
 }
 
@@ -1146,15 +1756,14 @@ pub(crate) fn op_div(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
-    let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            RObject::integer(n1 / n2)
-        }
-        _ => {
-            unreachable!("div supports only integer")
+    let result = match integer_div(&val1, &val2) {
+        Some(v) => v?.into_rc(),
+        None => {
+            let args = vec![val2.clone()];
+            mrb_funcall(vm, Some(val1.clone()), "/", &args)?
         }
     };
-    vm.current_regs()[a].replace(Rc::new(result));
+    vm.current_regs()[a].replace(result);
     Ok(())
 }
 
@@ -1163,15 +1772,14 @@ pub(crate) fn op_lt(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
-    let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            RObject::boolean(n1 < n2)
-        }
-        _ => {
-            unreachable!("lt supports only integer")
+    let result = match numeric_cmp(&val1, &val2) {
+        Some(ord) => RObject::boolean(ord.is_lt()).into_rc(),
+        None => {
+            let args = vec![val2.clone()];
+            mrb_funcall(vm, Some(val1.clone()), "<", &args)?
         }
     };
-    vm.current_regs()[a].replace(Rc::new(result));
+    vm.current_regs()[a].replace(result);
     Ok(())
 }
 
@@ -1180,15 +1788,14 @@ pub(crate) fn op_le(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
-    let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            RObject::boolean(n1 <= n2)
-        }
-        _ => {
-            unreachable!("le supports only integer")
+    let result = match numeric_cmp(&val1, &val2) {
+        Some(ord) => RObject::boolean(ord.is_le()).into_rc(),
+        None => {
+            let args = vec![val2.clone()];
+            mrb_funcall(vm, Some(val1.clone()), "<=", &args)?
         }
     };
-    vm.current_regs()[a].replace(Rc::new(result));
+    vm.current_regs()[a].replace(result);
     Ok(())
 }
 
@@ -1207,15 +1814,14 @@ pub(crate) fn op_gt(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
-    let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            RObject::boolean(n1 > n2)
-        }
-        _ => {
-            unreachable!("gt supports only integer")
+    let result = match numeric_cmp(&val1, &val2) {
+        Some(ord) => RObject::boolean(ord.is_gt()).into_rc(),
+        None => {
+            let args = vec![val2.clone()];
+            mrb_funcall(vm, Some(val1.clone()), ">", &args)?
         }
     };
-    vm.current_regs()[a].replace(Rc::new(result));
+    vm.current_regs()[a].replace(result);
     Ok(())
 }
 
@@ -1224,15 +1830,14 @@ pub(crate) fn op_ge(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let b = a + 1;
     let val1 = vm.take_current_regs(a)?;
     let val2 = vm.get_current_regs_cloned(b)?;
-    let result = match (&val1.value, &val2.value) {
-        (RValue::Integer(n1), RValue::Integer(n2)) => {
-            RObject::boolean(n1 >= n2)
-        }
-        _ => {
-            unreachable!("ge supports only integer")
+    let result = match numeric_cmp(&val1, &val2) {
+        Some(ord) => RObject::boolean(ord.is_ge()).into_rc(),
+        None => {
+            let args = vec![val2.clone()];
+            mrb_funcall(vm, Some(val1.clone()), ">=", &args)?
         }
     };
-    vm.current_regs()[a].replace(Rc::new(result));
+    vm.current_regs()[a].replace(result);
     Ok(())
 }
 
@@ -1256,7 +1861,171 @@ fn do_op_array(vm: &mut VM, this: usize, start: usize, n: usize) -> Result<(), E
         }
     }
     let val = RObject::array(ary);
-    vm.current_regs()[this].replace(Rc::new(val));
+    vm.current_regs()[this].replace(val.into_rc());
+    Ok(())
+}
+
+// Converts a value into a fresh array as `*v` does
+fn splat_value(vm: &mut VM, v: Rc<RObject>) -> Result<Vec<Rc<RObject>>, Error> {
+    match &v.value {
+        RValue::Array(ary) => return Ok(ary.borrow().clone()),
+        RValue::Nil => return Ok(vec![]),
+        _ => {}
+    }
+    if v.get_class(vm).find_method("to_a").is_none() {
+        return Ok(vec![v]);
+    }
+    let ary = mrb_funcall(vm, Some(v.clone()), "to_a", &[])?;
+    match &ary.value {
+        RValue::Array(ary) => Ok(ary.borrow().clone()),
+        RValue::Nil => Ok(vec![v]),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn array_ref(v: &RObject) -> Result<&RefCell<Vec<Rc<RObject>>>, Error> {
+    match &v.value {
+        RValue::Array(ary) => Ok(ary),
+        _ => Err(Error::internal("array expected")),
+    }
+}
+
+pub(crate) fn op_argary(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bs()?;
+    let a = a as usize;
+    let m1 = ((b >> 11) & 0x3f) as usize;
+    let r = ((b >> 10) & 0x1) as usize;
+    let m2 = ((b >> 5) & 0x1f) as usize;
+    let kd = ((b >> 4) & 0x1) as usize;
+    let lv = (b & 0xf) as usize;
+
+    if vm.current_callinfo.is_none() {
+        return Err(Error::NoMethodError("super called outside of method".to_string()));
+    }
+    let n = m1 + r + m2 + kd + 1;
+    let mut stack = Vec::with_capacity(n);
+    for i in 1..=n {
+        let v = if lv == 0 {
+            vm.current_regs()[i].clone()
+        } else {
+            get_upvar_reg(vm, i, lv - 1)?
+        };
+        stack.push(v.unwrap_or_else(|| RObject::nil().into_rc()));
+    }
+
+    let mut ary: Vec<Rc<RObject>> = stack[..m1].to_vec();
+    if r > 0 && let RValue::Array(rest) = &stack[m1].value {
+        ary.extend(rest.borrow().iter().cloned());
+    }
+    ary.extend(stack[(m1 + r)..(m1 + r + m2)].iter().cloned());
+    vm.current_regs()[a].replace(RObject::array(ary).into_rc());
+    vm.current_regs()[a + 1].replace(stack[m1 + r + m2].clone());
+    if kd > 0 {
+        vm.current_regs()[a + 2].replace(stack[m1 + r + m2 + 1].clone());
+    }
+    Ok(())
+}
+
+fn get_upvar_reg(vm: &VM, i: usize, up: usize) -> Result<Option<Rc<RObject>>, Error> {
+    let mut environ = vm.upper.as_ref().ok_or_else(|| Error::internal("super called outside of method"))?;
+    for _ in 0..up {
+        environ = environ.upper.as_ref().ok_or_else(|| Error::internal("super called outside of method"))?;
+    }
+    if !environ.expired() {
+        Ok(vm.regs[environ.current_regs_offset + i].clone())
+    } else {
+        let captured = environ.captured.borrow();
+        let captured = captured.as_ref().ok_or_else(|| Error::internal("captured environment not found"))?;
+        Ok(captured.get(i).cloned().flatten())
+    }
+}
+
+pub(crate) fn op_arycat(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let a = operand.as_b()? as usize;
+    let val = vm.get_current_regs_cloned(a + 1)?;
+    let splat = splat_value(vm, val)?;
+    let this = vm.get_current_regs_cloned(a)?;
+    if let RValue::Nil = &this.value {
+        vm.current_regs()[a].replace(RObject::array(splat).into_rc());
+    } else {
+        let ary = array_ref(&this)?;
+        this.with_resize(|| ary.borrow_mut().extend(splat));
+    }
+    Ok(())
+}
+
+pub(crate) fn op_arypush(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let a = a as usize;
+    let this = vm.get_current_regs_cloned(a)?;
+    let ary = array_ref(&this)?;
+    for i in 0..(b as usize) {
+        let v = vm.get_current_regs_cloned(a + i + 1)?;
+        this.with_resize(|| ary.borrow_mut().push(v));
+    }
+    Ok(())
+}
+
+pub(crate) fn op_arysplat(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let a = operand.as_b()? as usize;
+    let val = vm.get_current_regs_cloned(a)?;
+    let splat = splat_value(vm, val)?;
+    vm.current_regs()[a].replace(RObject::array(splat).into_rc());
+    Ok(())
+}
+
+pub(crate) fn op_aref(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b, c) = operand.as_bbb()?;
+    let v = vm.get_current_regs_cloned(b as usize)?;
+    let val = match &v.value {
+        RValue::Array(ary) => ary.borrow().get(c as usize).cloned().unwrap_or_else(|| RObject::nil().into_rc()),
+        _ if c == 0 => v.clone(),
+        _ => RObject::nil().into_rc(),
+    };
+    vm.current_regs()[a as usize].replace(val);
+    Ok(())
+}
+
+pub(crate) fn op_aset(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b, c) = operand.as_bbb()?;
+    let val = vm.get_current_regs_cloned(a as usize)?;
+    let this = vm.get_current_regs_cloned(b as usize)?;
+    let ary = array_ref(&this)?;
+    let c = c as usize;
+    this.with_resize(|| {
+        let mut ary = ary.borrow_mut();
+        if ary.len() <= c {
+            ary.resize_with(c + 1, || RObject::nil().into_rc());
+        }
+        ary[c] = val;
+    });
+    Ok(())
+}
+
+pub(crate) fn op_apost(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, pre, post) = operand.as_bbb()?;
+    let a = a as usize;
+    let pre = pre as usize;
+    let post = post as usize;
+    let v = vm.get_current_regs_cloned(a)?;
+    let ary = match &v.value {
+        RValue::Array(ary) => ary.borrow().clone(),
+        _ => vec![v.clone()],
+    };
+    let len = ary.len();
+    if len > pre + post {
+        let rest = ary[pre..(len - post)].to_vec();
+        vm.current_regs()[a].replace(RObject::array(rest).into_rc());
+        for (i, v) in ary[(len - post)..].iter().enumerate() {
+            vm.current_regs()[a + i + 1].replace(v.clone());
+        }
+    } else {
+        vm.current_regs()[a].replace(RObject::array(vec![]).into_rc());
+        for i in 0..post {
+            let v = ary.get(pre + i).cloned().unwrap_or_else(|| RObject::nil().into_rc());
+            vm.current_regs()[a + i + 1].replace(v);
+        }
+    }
     Ok(())
 }
 
@@ -1284,18 +2053,16 @@ pub(crate) fn op_strcat(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let val2 = vm.get_current_regs_cloned(b)?;
     match (&val1.value, &val2.value) {
         (RValue::String(s1), RValue::String(s2)) => {
-            let mut s1 = s1.borrow_mut();
-            let s2 = s2.borrow();
-            for c in s2.iter() {
-                s1.push(*c);
-            }
+            vm.check_memory(s2.borrow().len())?;
+            val1.with_resize(|| {
+                s1.borrow_mut().extend_from_slice(&s2.borrow());
+            });
         }
         (RValue::String(s1), RValue::Integer(s2)) => {
-            let mut s1 = s1.borrow_mut();
             let s2 = s2.to_string();
-            for c in s2.as_bytes() {
-                s1.push(*c);
-            }
+            val1.with_resize(|| {
+                s1.borrow_mut().extend_from_slice(s2.as_bytes());
+            });
         }
         _ => {
             unreachable!("strcat supports only string")
@@ -1315,7 +2082,42 @@ pub(crate) fn op_hash(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
         hash.insert(key.as_hash_key()?, (key, val));
     }
     let val = RObject::hash(hash);
-    vm.current_regs()[a as usize].replace(Rc::new(val));
+    vm.current_regs()[a].replace(val.into_rc());
+    Ok(())
+}
+
+pub(crate) fn op_hashadd(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let a = a as usize;
+    let b = b as usize;
+    let this = vm.get_current_regs_cloned(a)?;
+    let hash = match &this.value {
+        RValue::Hash(h) => h,
+        _ => unreachable!("hashadd must be called on hash"),
+    };
+    for i in 0..b {
+        let key = vm.get_current_regs_cloned(a + i * 2 + 1)?;
+        let val = vm.get_current_regs_cloned(a + i * 2 + 2)?;
+        let hashed = key.as_hash_key()?;
+        this.with_resize(|| hash.borrow_mut().insert(hashed, (key, val)));
+    }
+    Ok(())
+}
+
+pub(crate) fn op_hashcat(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let a = operand.as_b()? as usize;
+    let hash = vm.get_current_regs_cloned(a)?;
+    let other = vm.get_current_regs_cloned(a + 1)?;
+    match (&hash.value, &other.value) {
+        (RValue::Hash(h), RValue::Hash(o)) => {
+            let o = o.borrow().clone();
+            hash.with_resize(|| h.borrow_mut().extend(o));
+        }
+        (RValue::Hash(_), RValue::Nil) => {}
+        _ => {
+            return Err(Error::TypeMismatch);
+        }
+    }
     Ok(())
 }
 
@@ -1324,9 +2126,12 @@ pub(crate) fn op_lambda(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let irep = Some(vm.current_irep.reps[b as usize].clone());
     let environ = ENV {
         upper: vm.upper.clone(),
+        callinfo: vm.current_callinfo.clone(),
         current_regs_offset: vm.current_regs_offset,
         is_expired: Cell::new(false),
+        is_orphan: Cell::new(false),
         captured: RefCell::new(None),
+        stack: vm.fibers.last().map(Rc::downgrade),
     };
     //let nregs = vm.current_irep.nregs;
     //environ.capture(&vm.current_regs()[0..nregs]);
@@ -1339,6 +2144,7 @@ pub(crate) fn op_lambda(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
         value: RValue::Proc(RProc {
             irep,
             is_rb_func: true,
+            is_lambda: true,
             sym_id: Some("<lambda>".into()),
             next: None,
             func: None,
@@ -1346,6 +2152,7 @@ pub(crate) fn op_lambda(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
             block_self: Some(vm.getself()?),
         }),
         object_id: u64::MAX.into(),
+        meter: OnceCell::new(),
     };
     vm.current_regs()[a as usize].replace(val.to_refcount_assigned());
     Ok(())
@@ -1356,9 +2163,12 @@ pub(crate) fn op_block(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let irep = Some(vm.current_irep.reps[b as usize].clone());
     let environ = ENV {
         upper: vm.upper.clone(),
+        callinfo: vm.current_callinfo.clone(),
         current_regs_offset: vm.current_regs_offset,
         is_expired: Cell::new(false),
+        is_orphan: Cell::new(false),
         captured: RefCell::new(None),
+        stack: vm.fibers.last().map(Rc::downgrade),
     };
     let environ = Rc::new(environ);
     vm.cur_env.insert(vm.current_irep.__id, environ.clone());
@@ -1369,6 +2179,7 @@ pub(crate) fn op_block(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
         value: RValue::Proc(RProc {
             irep,
             is_rb_func: true,
+            is_lambda: false,
             sym_id: Some("<block>".into()),
             next: None,
             func: None,
@@ -1376,6 +2187,7 @@ pub(crate) fn op_block(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
             block_self: Some(vm.getself()?),
         }),
         object_id: u64::MAX.into(),
+        meter: OnceCell::new(),
     };
     vm.current_regs()[a as usize].replace(val.to_refcount_assigned());
     Ok(())
@@ -1389,6 +2201,7 @@ pub(crate) fn op_method(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
         value: super::value::RValue::Proc(super::value::RProc {
             irep,
             is_rb_func: true,
+            is_lambda: true,
             sym_id: None,
             next: None,
             func: None,
@@ -1396,6 +2209,7 @@ pub(crate) fn op_method(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
             block_self: None,
         }),
         object_id: u64::MAX.into(),
+        meter: OnceCell::new(),
     };
     vm.current_regs()[a as usize].replace(val.to_refcount_assigned());
     Ok(())
@@ -1418,6 +2232,7 @@ fn do_op_range(vm: &mut VM, a: usize, b: usize, exclusive: bool) -> Result<(), E
         tt: super::value::RType::Range,
         value: super::value::RValue::Range(val1, val2, exclusive),
         object_id: u64::MAX.into(),
+        meter: OnceCell::new(),
     };
     vm.current_regs()[a as usize].replace(val.to_refcount_assigned());
     Ok(())
@@ -1430,41 +2245,104 @@ pub(crate) fn op_oclass(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     Ok(())
 }
 
+// the class body the constant belongs to, None for the top level
+fn nesting(vm: &VM) -> Option<Rc<RClass>> {
+    let target = &vm.target_class;
+    (!Rc::ptr_eq(target, &vm.object_class)).then(|| target.clone())
+}
+
+// R[a] holds the outer class of `class A::B`, nil for a bare name
+fn outer_class(vm: &mut VM, a: u8) -> Option<Rc<RClass>> {
+    let outer = vm.current_regs()[a as usize].clone();
+    match outer.as_ref().map(|o| &o.value) {
+        Some(RValue::Class(klass)) if !Rc::ptr_eq(klass, &vm.object_class) => Some(klass.clone()),
+        Some(RValue::Class(_)) => None,
+        _ => nesting(vm),
+    }
+}
+
 pub(crate) fn op_class(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let superclass = vm.current_regs()[a as usize + 1].as_ref().cloned();
-    let name = vm.current_irep.syms[b as usize].clone();
-    let superclass = match superclass {
-        Some(superclass) => {
-            if let RValue::Class(klass) = &superclass.value {
-                klass.clone()
-            } else {
-                vm.object_class.clone()
+    let name = vm.current_irep.syms[b as usize].clone().name;
+    // nil means no superclass is given
+    let superclass = match superclass.as_ref().map(|s| &s.value) {
+        Some(RValue::Class(klass)) if !klass.is_module => Some(klass.clone()),
+        Some(RValue::Nil) | None => None,
+        Some(_) => {
+            return Err(Error::TypeError("superclass must be a Class".to_string()));
+        }
+    };
+    let outer = outer_class(vm, a);
+    let existing = match &outer {
+        Some(outer) => outer.getmcnst(&name),
+        None => vm.consts.get(&name).cloned(),
+    };
+    let klass = match existing.as_ref().map(|obj| &obj.value) {
+        Some(RValue::Class(klass)) if !klass.is_module => {
+            if let Some(superclass) = &superclass {
+                let same = klass.super_class.as_ref()
+                    .map(|sc| Rc::ptr_eq(sc, superclass))
+                    .unwrap_or(false);
+                if !same {
+                    return Err(Error::TypeError(format!("superclass mismatch for class {}", name)));
+                }
             }
+            klass.clone()
         }
-        None => {
-            vm.object_class.clone()
+        Some(_) => {
+            return Err(Error::TypeError(format!("{} is not a class", name)));
         }
+        None => match &outer {
+            Some(outer) => vm.define_nested_class(outer, &name, superclass),
+            None => vm.define_class(&name, superclass),
+        },
     };
-    let name = name.name;
-    let klass = vm.define_class(&name, Some(superclass));
 
     vm.current_regs()[a as usize].replace(Rc::new(klass.into()));
     Ok(())
 }
 
+pub(crate) fn op_module(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let name = vm.current_irep.syms[b as usize].clone();
+    let outer = outer_class(vm, a);
+    let existing = match &outer {
+        Some(outer) => outer.getmcnst(&name.name),
+        None => vm.consts.get(&name.name).cloned(),
+    };
+    let module = match existing.as_ref().map(|obj| &obj.value) {
+        Some(RValue::Class(klass)) if klass.is_module => klass.clone(),
+        Some(_) => {
+            return Err(Error::TypeError(format!("{} is not a module", name.name)));
+        }
+        None => match &outer {
+            Some(outer) => vm.define_nested_module(outer, &name.name),
+            None => vm.define_module(&name.name),
+        },
+    };
+
+    vm.current_regs()[a as usize].replace(Rc::new(module.into()));
+    Ok(())
+}
+
 pub(crate) fn op_exec(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let (a, b) = operand.as_bb()?;
     let recv = vm.get_current_regs_cloned(a as usize)?;
 
     vm.current_regs()[a as usize].replace(recv.clone());
-    push_callinfo(vm, "<exec>".into(), 0);
+    push_callinfo(vm, "<exec>".into(), 0)?;
 
     vm.pc.set(0);
-    let irep = vm.irep.reps[b as usize].clone();
+    vm.upper = None;
+    let irep = vm.current_irep.reps[b as usize].clone();
     vm.current_irep = irep;
     vm.current_regs_offset += a as usize;
-    vm.target_class = recv.get_class(vm);
+    // a class body defines its methods in the class itself
+    vm.target_class = match &recv.value {
+        RValue::Class(klass) => klass.clone(),
+        _ => recv.get_class(vm),
+    };
     Ok(())
 }
 
@@ -1480,7 +2358,8 @@ pub(crate) fn op_def(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
         let mut procs = klass.procs.borrow_mut();
         let mut method = method.clone();
         method.sym_id = Some(sym.clone());
-        procs.insert(sym.name.clone(), method);
+        procs.insert(sym.clone(), method);
+        vm.expire_method_caches();
     } else {
         unreachable!("DEF must be called on class");
     }
@@ -1488,9 +2367,32 @@ pub(crate) fn op_def(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     Ok(())
 }
 
+pub(crate) fn op_alias(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let (a, b) = operand.as_bb()?;
+    let new_name = vm.current_irep.syms[a as usize].clone();
+    let old_name = vm.current_irep.syms[b as usize].clone();
+    let klass = vm.target_class.clone();
+    mrb_alias_method(vm, klass, &new_name.name, &old_name.name)
+}
+
+pub(crate) fn op_undef(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let a = operand.as_b()?;
+    let name = vm.current_irep.syms[a as usize].clone();
+    let klass = vm.target_class.clone();
+    mrb_undef_method(vm, klass, &name.name)
+}
+
+pub(crate) fn op_sclass(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
+    let a = operand.as_b()? as usize;
+    let obj = vm.get_current_regs_cloned(a)?;
+    let singleton = mrb_singleton_class(vm, &obj)?;
+    vm.current_regs()[a].replace(Rc::new(singleton.into()));
+    Ok(())
+}
+
 pub(crate) fn op_tclass(vm: &mut VM, operand: &Fetched) -> Result<(), Error> {
     let a = operand.as_b()? as usize;
-    let klass = vm.object_class.clone();
+    let klass = vm.target_class.clone();
     let val: RObject = klass.into();
     vm.current_regs()[a].replace(val.to_refcount_assigned());
     Ok(())
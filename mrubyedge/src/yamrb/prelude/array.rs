@@ -1,6 +1,6 @@
 use std::rc::Rc;
 
-use crate::{yamrb::{helpers::{mrb_call_block, mrb_define_cmethod}, value::{RObject, RValue}, vm::VM}, Error};
+use crate::{yamrb::{helpers::{mrb_define_cmethod, without_block}, value::{RObject, RValue}, vm::VM}, Error};
 
 pub(crate) fn initialize_array(vm: &mut VM) {
     let array_class = vm.define_standard_class("Array");
@@ -8,7 +8,6 @@ pub(crate) fn initialize_array(vm: &mut VM) {
     mrb_define_cmethod(vm, array_class.clone(), "push", Box::new(mrb_array_push_self));
     mrb_define_cmethod(vm, array_class.clone(), "[]", Box::new(mrb_array_get_index_self));
     mrb_define_cmethod(vm, array_class.clone(), "[]=", Box::new(mrb_array_set_index_self));
-    mrb_define_cmethod(vm, array_class.clone(), "each", Box::new(mrb_array_each));
     mrb_define_cmethod(vm, array_class.clone(), "size", Box::new(mrb_array_size));
     mrb_define_cmethod(vm, array_class.clone(), "length", Box::new(mrb_array_size));
     mrb_define_cmethod(vm, array_class.clone(), "pack", Box::new(mrb_array_pack));
@@ -16,16 +15,13 @@ pub(crate) fn initialize_array(vm: &mut VM) {
 
 fn mrb_array_push_self(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     let this = vm.getself()?;
-    mrb_array_push(this, args)
+    mrb_array_push(this, without_block(args))
 }
 
 pub fn mrb_array_push(this: Rc<RObject>, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     match &this.value {
         RValue::Array(a) => {
-            let mut array = a.borrow_mut();
-            for arg in args {
-                array.push(arg.clone());
-            }
+            this.with_resize(|| a.borrow_mut().extend(args.iter().cloned()));
             Ok(this.clone())
         },
         _ => {
@@ -61,8 +57,7 @@ pub fn mrb_array_set_index(this: Rc<RObject>, args: &[Rc<RObject>]) -> Result<Rc
     let value = &args[1];
     match &this.value {
         RValue::Array(a) => {
-            let mut a = a.borrow_mut();
-            a.insert(index, value.clone());
+            this.with_resize(|| a.borrow_mut().insert(index, value.clone()));
         }
         _ => {
             return Err(Error::RuntimeError("Array#push must be called on an Array".to_string()));
@@ -71,24 +66,6 @@ pub fn mrb_array_set_index(this: Rc<RObject>, args: &[Rc<RObject>]) -> Result<Rc
     Ok(value.clone())
 }
 
-fn mrb_array_each(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
-    let this = vm.getself()?;
-    let block = &args[0];
-    match &this.value {
-        RValue::Array(a) => {
-            let a = a.borrow();
-            for elem in a.iter() {
-                let args = vec![elem.clone()];
-                mrb_call_block(vm, block.clone(), None, &args)?;
-            }
-        }
-        _ => {
-            return Err(Error::RuntimeError("Array#each must be called on an Array".to_string()));
-        }
-    };
-    Ok(this.clone())
-}
-
 fn mrb_array_pack(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     let this = vm.getself()?;
     let format: Vec<u8> = args[0].as_ref().try_into()?;
@@ -167,11 +144,11 @@ fn test_mrb_array_push_and_index() {
     let mut vm = VM::empty();
     prelude::prelude(&mut vm);
 
-    let array = Rc::new(RObject::array(vec![]));
+    let array = RObject::array(vec![]).into_rc();
     let args = vec![
-        Rc::new(RObject::integer(1)),
-        Rc::new(RObject::integer(2)),
-        Rc::new(RObject::integer(3)),
+        RObject::integer(1).into_rc(),
+        RObject::integer(2).into_rc(),
+        RObject::integer(3).into_rc(),
     ];
     mrb_array_push(array.clone(), &args).expect("push failed");
 
@@ -182,7 +159,7 @@ fn test_mrb_array_push_and_index() {
     ];
 
     for (i, expected) in answers.iter().enumerate() {
-        let args = vec![Rc::new(RObject::integer(i as i64))];
+        let args = vec![RObject::integer(i as i64).into_rc()];
         let value = mrb_array_get_index(array.clone(), &args).expect("getting index failed");
         let value: i64 = value.as_ref().try_into().expect("value is not integer");
         assert_eq!(value, *expected);
@@ -195,16 +172,16 @@ fn test_mrb_array_set_and_index() {
     let mut vm = VM::empty();
     prelude::prelude(&mut vm);
 
-    let array = Rc::new(RObject::array(vec![]));
+    let array = RObject::array(vec![]).into_rc();
     let args = vec![
-        Rc::new(RObject::nil()),
-        Rc::new(RObject::nil()),
-        Rc::new(RObject::integer(0)),
+        RObject::nil().into_rc(),
+        RObject::nil().into_rc(),
+        RObject::integer(0).into_rc(),
     ];
     mrb_array_push(array.clone(), &args).expect("push failed");
 
-    let upd_index = Rc::new(RObject::integer(2));
-    let newval = Rc::new(RObject::integer(42));
+    let upd_index = RObject::integer(2).into_rc();
+    let newval = RObject::integer(42).into_rc();
     let args = vec![
         upd_index,
         newval,
@@ -223,12 +200,12 @@ fn test_mrb_array_pack() {
     let mut vm = VM::empty();
     prelude::prelude(&mut vm);
 
-    let array = Rc::new(RObject::array(vec![
-        Rc::new(RObject::integer(1)),
-        Rc::new(RObject::integer(2)),
-        Rc::new(RObject::integer(3)),
-        Rc::new(RObject::integer(4)),
-    ]));
+    let array = RObject::array(vec![
+        RObject::integer(1).into_rc(),
+        RObject::integer(2).into_rc(),
+        RObject::integer(3).into_rc(),
+        RObject::integer(4).into_rc(),
+    ]).into_rc();
     vm.current_regs()[0].replace(array);
     let format = Rc::new(RObject::string(
         "c s l q".to_string(),
@@ -251,7 +228,7 @@ fn test_mrb_array_pack() {
 fn mrb_array_size(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     let this = vm.getself()?;
     let value: Vec<Rc<RObject>> = this.as_ref().try_into()?;
-    Ok(Rc::new(RObject::integer(value.len() as i64)))
+    Ok(RObject::integer(value.len() as i64).into_rc())
 }
 
 #[test]
@@ -260,14 +237,14 @@ fn test_mrb_array_size() {
 
     let mut vm = VM::empty();
 
-    let data = Rc::new(RObject::array(vec![]));
+    let data = RObject::array(vec![]).into_rc();
     let ret = helpers::mrb_funcall(&mut vm, Some(data.clone()), "size", &[]).expect("size failed");
     let ret: i64 = ret.as_ref().try_into().expect("size is not integer");
     assert_eq!(ret, 0);
 
-    mrb_array_push(data.clone(), &[Rc::new(RObject::integer(1))]).expect("push failed");
-    mrb_array_push(data.clone(), &[Rc::new(RObject::integer(2))]).expect("push failed");
-    mrb_array_push(data.clone(), &[Rc::new(RObject::integer(3))]).expect("push failed");
+    mrb_array_push(data.clone(), &[RObject::integer(1).into_rc()]).expect("push failed");
+    mrb_array_push(data.clone(), &[RObject::integer(2).into_rc()]).expect("push failed");
+    mrb_array_push(data.clone(), &[RObject::integer(3).into_rc()]).expect("push failed");
 
     let ret = helpers::mrb_funcall(&mut vm, Some(data), "size", &[]).expect("size failed");
     let ret: i64 = ret.as_ref().try_into().expect("size is not integer");
@@ -1,11 +1,23 @@
+use std::mem;
 use std::rc::Rc;
 
-use crate::{yamrb::{helpers::{mrb_define_cmethod, mrb_funcall}, value::*, vm::VM}, Error};
+use crate::{yamrb::{helpers::{mrb_alias_method, mrb_define_cmethod, mrb_define_method, mrb_funcall_with_block, mrb_remove_method, mrb_undef_method}, snapshot::MadeFn, value::*, vm::VM}, Error};
 
 use super::shared_memory::mrb_shared_memory_new;
 
 pub(crate) fn initialize_class(vm: &mut VM) {
-    let class_class = vm.define_standard_class("Class");
+    let module_class = vm.define_standard_class("Module");
+    let class_class = vm.define_standard_class_under("Class", module_class.clone());
+
+    mrb_define_cmethod(vm, module_class.clone(), "include", Box::new(mrb_module_include));
+    mrb_define_cmethod(vm, module_class.clone(), "prepend", Box::new(mrb_module_prepend));
+    mrb_define_cmethod(vm, module_class.clone(), "ancestors", Box::new(mrb_module_ancestors));
+    mrb_define_cmethod(vm, module_class.clone(), "include?", Box::new(mrb_module_include_p));
+    mrb_define_cmethod(vm, module_class.clone(), "alias_method", Box::new(mrb_module_alias_method));
+    mrb_define_cmethod(vm, module_class.clone(), "undef_method", Box::new(mrb_module_undef_method));
+    mrb_define_cmethod(vm, module_class.clone(), "remove_method", Box::new(mrb_module_remove_method));
+    mrb_define_cmethod(vm, module_class.clone(), "class_variable_get", Box::new(mrb_module_class_variable_get));
+    mrb_define_cmethod(vm, module_class.clone(), "class_variable_set", Box::new(mrb_module_class_variable_set));
 
     mrb_define_cmethod(vm, class_class.clone(), "new", Box::new(mrb_class_new));
     mrb_define_cmethod(vm, class_class.clone(), "attr_reader", Box::new(mrb_class_attr_reader));
@@ -14,7 +26,133 @@ pub(crate) fn initialize_class(vm: &mut VM) {
     mrb_define_cmethod(vm, class_class.clone(), "attr", Box::new(mrb_class_attr_acceccor));
 }
 
+fn self_module(vm: &mut VM, name: &str) -> Result<Rc<RClass>, Error> {
+    let this = vm.getself()?;
+    match &this.value {
+        RValue::Class(c) => Ok(c.clone()),
+        _ => Err(Error::RuntimeError(format!("Module#{} must be called from class or module", name))),
+    }
+}
+
+// module args given to include/prepend/extend, the last nil is the block slot
+pub(crate) fn module_args(args: &[Rc<RObject>]) -> Result<Vec<Rc<RClass>>, Error> {
+    let mut modules = Vec::new();
+    for arg in args.iter() {
+        match &arg.value {
+            RValue::Class(m) if m.is_module => modules.push(m.clone()),
+            RValue::Nil => {}
+            _ => return Err(Error::TypeMismatch),
+        }
+    }
+    Ok(modules)
+}
+
+fn mrb_module_include(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "include")?;
+    // `include A, B` makes A come first in the ancestors
+    for module in module_args(args)?.into_iter().rev() {
+        klass.include_module(module);
+    }
+    vm.expire_method_caches();
+    vm.getself()
+}
+
+fn mrb_module_prepend(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "prepend")?;
+    for module in module_args(args)?.into_iter().rev() {
+        klass.prepend_module(module);
+    }
+    vm.expire_method_caches();
+    vm.getself()
+}
+
+fn mrb_module_ancestors(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "ancestors")?;
+    let ancestors = klass.ancestors().into_iter()
+        .map(|k| Rc::new(RObject::class(k)))
+        .collect();
+    Ok(RObject::array(ancestors).into_rc())
+}
+
+fn mrb_module_include_p(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "include?")?;
+    let module = match &args[0].value {
+        RValue::Class(m) if m.is_module => m.clone(),
+        _ => return Err(Error::TypeMismatch),
+    };
+    let included = !Rc::ptr_eq(&klass, &module) && module.is_ancestor_of(&klass);
+    Ok(RObject::boolean(included).into_rc())
+}
+
+// method names given as symbols or strings, the last nil is the block slot
+fn method_name_args(args: &[Rc<RObject>]) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+    for arg in args.iter() {
+        match &arg.value {
+            RValue::Symbol(sym) => names.push(sym.name.clone()),
+            RValue::String(s) => names.push(String::from_utf8_lossy(&s.borrow()).to_string()),
+            RValue::Nil => {}
+            _ => return Err(Error::TypeMismatch),
+        }
+    }
+    Ok(names)
+}
+
+fn mrb_module_alias_method(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "alias_method")?;
+    let names = method_name_args(args)?;
+    if names.len() != 2 {
+        return Err(Error::ArgumentError(format!("wrong number of arguments (given {}, expected 2)", names.len())));
+    }
+    mrb_alias_method(vm, klass, &names[0], &names[1])?;
+    Ok(Rc::new(RObject::symbol(RSym::new(names[0].clone()))))
+}
+
+fn mrb_module_undef_method(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "undef_method")?;
+    for name in method_name_args(args)? {
+        mrb_undef_method(vm, klass.clone(), &name)?;
+    }
+    vm.getself()
+}
+
+fn mrb_module_remove_method(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "remove_method")?;
+    for name in method_name_args(args)? {
+        mrb_remove_method(vm, klass.clone(), &name)?;
+    }
+    vm.getself()
+}
+
+fn cvar_name_arg(arg: &RObject) -> Result<String, Error> {
+    let name = match &arg.value {
+        RValue::Symbol(sym) => sym.name.clone(),
+        RValue::String(s) => String::from_utf8_lossy(&s.borrow()).to_string(),
+        _ => return Err(Error::TypeMismatch),
+    };
+    if !name.starts_with("@@") || name.len() == 2 {
+        return Err(Error::NameError(format!("'{}' is not allowed as a class variable name", name)));
+    }
+    Ok(name)
+}
+
+fn mrb_module_class_variable_get(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "class_variable_get")?;
+    let name = cvar_name_arg(&args[0])?;
+    klass.cvar_get(&name).ok_or_else(|| {
+        Error::NameError(format!("uninitialized class variable {} in {}", name, klass.sym_id.name))
+    })
+}
+
+fn mrb_module_class_variable_set(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let klass = self_module(vm, "class_variable_set")?;
+    let name = cvar_name_arg(&args[0])?;
+    klass.cvar_set(&name, args[1].clone());
+    Ok(args[1].clone())
+}
+
 fn mrb_class_new(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let kargs_given = mem::take(&mut vm.kargs_given);
     let class = vm.getself()?;
     let class = match &class.value {
         RValue::Class(c) => c.clone(),
@@ -40,9 +178,24 @@ fn mrb_class_new(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error
         _ => {}        
     }
 
-    let obj = RObject::instance(class).to_refcount_assigned();
+    let obj = if vm.get_class_by_name("Exception").is_ancestor_of(&class) {
+        // the message defaults to the class name
+        let message = class.sym_id.name.clone();
+        RObject::exception(Rc::new(RException::new(class, message))).to_refcount_assigned()
+    } else {
+        RObject::instance(class).to_refcount_assigned()
+    };
 
-    mrb_funcall(vm, Some(obj.clone()), "initialize", args)?;
+    // the block slot comes last, after the keyword hash if given
+    let (block, args) = match args.split_last() {
+        Some((block, args)) => (Some(block.clone()), args),
+        None => (None, args),
+    };
+    let (kargs, args) = match args.split_last() {
+        Some((kargs, args)) if kargs_given => (Some(kargs.clone()), args),
+        _ => (None, args),
+    };
+    mrb_funcall_with_block(vm, Some(obj.clone()), "initialize", args, kargs, block)?;
 
     Ok(obj)
 }
@@ -58,24 +211,8 @@ fn mrb_class_attr_reader(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject
     for arg in args.iter() {
         match arg.value {
             RValue::Symbol(ref sym) => {
-                let sym_id: &'static str = sym.name.clone().leak();
-                let method = move |vm: &mut VM, _args: &[Rc<RObject>]| {
-                    let this = vm.getself()?;
-                    let key = format!("@{}", sym_id);
-                    let value = match &this.value {
-                        RValue::Instance(i) => {
-                            match i.ivar.borrow().get(&key) {
-                                Some(v) => v.clone(),
-                                None => Rc::new(RObject::nil())
-                            }
-                        },
-                        _ => {
-                            return Err(Error::RuntimeError("attr_reader defined method must be called from instance".to_string()));
-                        }
-                    };
-                    Ok(value)
-                };
-                mrb_define_cmethod(vm, class.clone(), &sym_id, Box::new(method));
+                let index = vm.register_made_fn(MadeFn::AttrReader(sym.name.clone()));
+                mrb_define_method(vm, class.clone(), &sym.name, RProc::cfunc(&sym.name, index));
             }
             RValue::Nil => {
                 // skip
@@ -85,7 +222,7 @@ fn mrb_class_attr_reader(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject
             }
         }
     }
-    Ok(Rc::new(RObject::nil()))
+    Ok(RObject::nil().into_rc())
 }
 
 fn mrb_class_attr_writer(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
@@ -99,23 +236,9 @@ fn mrb_class_attr_writer(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject
     for arg in args.iter() {
         match arg.value {
             RValue::Symbol(ref sym) => {
-                let sym_id: &'static str = sym.name.clone().leak();
-                let method = move |vm: &mut VM, args: &[Rc<RObject>]| {
-                    let this = vm.getself()?;
-                    let key = format!("@{}", sym_id);
-                    let value = args[0].clone();
-                    match &this.value {
-                        RValue::Instance(i) => {
-                            i.ivar.borrow_mut().insert(key, value.clone());
-                        },
-                        _ => {
-                            return Err(Error::RuntimeError("attr_reader defined method must be called from instance".to_string()));
-                        }
-                    };
-                    Ok(value)
-                };
-                let sym_id = format!("{}=", sym_id);
-                mrb_define_cmethod(vm, class.clone(), &sym_id, Box::new(method));
+                let name = format!("{}=", sym.name);
+                let index = vm.register_made_fn(MadeFn::AttrWriter(sym.name.clone()));
+                mrb_define_method(vm, class.clone(), &name, RProc::cfunc(&name, index));
             }
             RValue::Nil => {
                 // skip
@@ -125,7 +248,38 @@ fn mrb_class_attr_writer(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject
             }
         }
     }
-    Ok(Rc::new(RObject::nil()))
+    Ok(RObject::nil().into_rc())
+}
+
+// The method made by attr_reader, returning the ivar of the name
+pub(crate) fn attr_reader_fn(name: &str) -> RFn {
+    let key = RSym::new(format!("@{}", name));
+    Box::new(move |vm: &mut VM, _args: &[Rc<RObject>]| {
+        let this = vm.getself()?;
+        // reads nil like an unset @var, also for objects without ivars
+        let value = this.ivars()
+            .and_then(|ivar| ivar.borrow().get(&key).cloned())
+            .unwrap_or_else(|| RObject::nil().into_rc());
+        Ok(value)
+    })
+}
+
+// The method made by attr_writer, setting the ivar of the name
+pub(crate) fn attr_writer_fn(name: &str) -> RFn {
+    let key = RSym::new(format!("@{}", name));
+    Box::new(move |vm: &mut VM, args: &[Rc<RObject>]| {
+        let this = vm.getself()?;
+        let value = args[0].clone();
+        match this.ivars() {
+            Some(ivar) => {
+                ivar.borrow_mut().insert(key.clone(), value.clone());
+            },
+            None => {
+                return Err(Error::TypeError(format!("can't set instance variable {} on {}", key.name, this.get_class(vm).sym_id.name)));
+            }
+        };
+        Ok(value)
+    })
 }
 
 fn mrb_class_attr_acceccor(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
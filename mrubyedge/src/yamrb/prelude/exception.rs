@@ -20,17 +20,51 @@ pub(crate) fn initialize_exception(vm: &mut VM) {
     let _ = vm.define_standard_class_under("SystemExit", exp_class.clone());
     let _ = vm.define_standard_class_under("SystemStackError", exp_class.clone());
     let _ = vm.define_standard_class_under("SystemCallError", std_exp_class.clone());
+    let _ = vm.define_standard_class_under("TypeError", std_exp_class.clone());
     let _ = vm.define_standard_class_under("NoMethodError", std_exp_class.clone());
     let _ = vm.define_standard_class_under("NameError", std_exp_class.clone());
+    let _ = vm.define_standard_class_under("ArgumentError", std_exp_class.clone());
+    let _ = vm.define_standard_class_under("RangeError", std_exp_class.clone());
+    let _ = vm.define_standard_class_under("ZeroDivisionError", std_exp_class.clone());
+    let _ = vm.define_standard_class_under("LocalJumpError", std_exp_class.clone());
+    let _ = vm.define_standard_class_under("FiberError", std_exp_class.clone());
 
-    mrb_define_cmethod(vm, exp_class, "message", Box::new(mrb_exception_message));
+    mrb_define_cmethod(vm, exp_class.clone(), "initialize", Box::new(mrb_exception_initialize));
+    mrb_define_cmethod(vm, exp_class.clone(), "message", Box::new(mrb_exception_message));
+    mrb_define_cmethod(vm, exp_class.clone(), "to_s", Box::new(mrb_exception_message));
+    mrb_define_cmethod(vm, exp_class, "backtrace", Box::new(mrb_exception_backtrace));
+}
+
+pub fn mrb_exception_backtrace(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let exp = vm.getself()?;
+    match &exp.value {
+        RValue::Exception(e) => {
+            let backtrace = e.backtrace.borrow().iter()
+                .map(|line| Rc::new(RObject::string(line.clone())))
+                .collect();
+            Ok(RObject::array(backtrace).into_rc())
+        },
+        _ => {
+            Err(Error::RuntimeError("Exception#backtrace must be called on an Exception".to_string()))
+        }
+    }
+}
+
+pub fn mrb_exception_initialize(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let exp = vm.getself()?;
+    if let (RValue::Exception(e), Some(message)) = (&exp.value, args.first())
+        && !message.is_nil() {
+        let message: String = message.as_ref().try_into()?;
+        e.message.replace(message);
+    }
+    Ok(RObject::nil().into_rc())
 }
 
 pub fn mrb_exception_message(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     let exp = vm.getself()?;
     match &exp.value {
         RValue::Exception(e) => {
-            let message = e.as_ref().message.clone();
+            let message = e.as_ref().message.borrow().clone();
             Ok(RObject::string(message).to_refcount_assigned())
         },
         _ => {
@@ -0,0 +1,68 @@
+use std::cell::OnceCell;
+use std::rc::Rc;
+
+use crate::yamrb::fiber::{self, FiberState, RFiber};
+use crate::{yamrb::{helpers::{mrb_define_cmethod, mrb_singleton_class, without_block}, value::*, vm::VM}, Error};
+
+pub(crate) fn initialize_fiber(vm: &mut VM) {
+    let fiber_class = vm.define_standard_class("Fiber");
+
+    mrb_define_cmethod(vm, fiber_class.clone(), "resume", Box::new(mrb_fiber_resume));
+    mrb_define_cmethod(vm, fiber_class.clone(), "alive?", Box::new(mrb_fiber_alive));
+
+    let fiber_class_obj = Rc::new(RObject::class(fiber_class));
+    let singleton = mrb_singleton_class(vm, &fiber_class_obj).expect("Fiber has a singleton class");
+    mrb_define_cmethod(vm, singleton.clone(), "new", Box::new(mrb_fiber_new));
+    mrb_define_cmethod(vm, singleton, "yield", Box::new(mrb_fiber_yield));
+}
+
+pub fn mrb_fiber_new(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let class = match &vm.getself()?.value {
+        RValue::Class(c) => c.clone(),
+        _ => {
+            return Err(Error::RuntimeError("Fiber.new must be called from class".to_string()));
+        }
+    };
+    let block = match args.last().map(|b| &b.value) {
+        Some(RValue::Proc(p)) => p.clone(),
+        _ => {
+            return Err(Error::ArgumentError("tried to create Fiber object without a block".to_string()));
+        }
+    };
+    let fiber = RFiber::new(vm, class, block)?;
+    let obj = RObject {
+        tt: RType::Fiber,
+        value: RValue::Fiber(Rc::new(fiber)),
+        object_id: u64::MAX.into(),
+        meter: OnceCell::new(),
+    };
+    Ok(obj.to_refcount_assigned())
+}
+
+fn mrb_fiber_resume(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let fiber = match &this.value {
+        RValue::Fiber(f) => f.clone(),
+        _ => {
+            return Err(Error::RuntimeError("Fiber#resume must be called on a Fiber".to_string()));
+        }
+    };
+    fiber::resume(vm, &fiber, without_block(args))
+}
+
+fn mrb_fiber_alive(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let fiber = match &this.value {
+        RValue::Fiber(f) => f.clone(),
+        _ => {
+            return Err(Error::RuntimeError("Fiber#alive? must be called on a Fiber".to_string()));
+        }
+    };
+    Ok(RObject::boolean(fiber.state() != FiberState::Terminated).into_rc())
+}
+
+// Fiber.yield cannot be called from a block run by a Rust method,
+// see fiber::fiber_yield
+fn mrb_fiber_yield(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    fiber::fiber_yield(vm, without_block(args))
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{yamrb::{helpers::{mrb_define_cmethod, mrb_singleton_class}, prelude::hash::mrb_hash_set_index, value::*, vm::VM}, Error};
+
+pub(crate) fn initialize_gc(vm: &mut VM) {
+    let gc_module = vm.define_module("GC");
+    let gc_module_obj = Rc::new(RObject::class(gc_module));
+    let singleton = mrb_singleton_class(vm, &gc_module_obj).expect("GC has a singleton class");
+    mrb_define_cmethod(vm, singleton.clone(), "start", Box::new(mrb_gc_start));
+    mrb_define_cmethod(vm, singleton, "count", Box::new(mrb_gc_count));
+
+    let object_space = vm.define_module("ObjectSpace");
+    let object_space_obj = Rc::new(RObject::class(object_space));
+    let singleton = mrb_singleton_class(vm, &object_space_obj).expect("ObjectSpace has a singleton class");
+    mrb_define_cmethod(vm, singleton, "count_objects", Box::new(mrb_object_space_count_objects));
+}
+
+fn mrb_gc_start(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    vm.gc_start();
+    Ok(RObject::nil().into_rc())
+}
+
+fn mrb_gc_count(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    Ok(RObject::integer(vm.gc_count() as i64).into_rc())
+}
+
+// Live objects able to hold others, which are the ones the collector
+// tracks, keyed by :TOTAL and :T_ types as CRuby does
+fn mrb_object_space_count_objects(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let counts = vm.gc.count_objects();
+    let result = RObject::hash(HashMap::new()).into_rc();
+    let total: usize = counts.values().sum();
+    let key = RObject::symbol(RSym::new("TOTAL".to_string())).into_rc();
+    mrb_hash_set_index(result.clone(), key, RObject::integer(total as i64).into_rc())?;
+    let types = [
+        (RType::Instance, "T_OBJECT"),
+        (RType::Array, "T_ARRAY"),
+        (RType::Hash, "T_HASH"),
+        (RType::Proc, "T_PROC"),
+        (RType::Exception, "T_EXCEPTION"),
+    ];
+    for (tt, name) in types {
+        let count = counts.get(&tt).copied().unwrap_or(0);
+        let key = RObject::symbol(RSym::new(name.to_string())).into_rc();
+        mrb_hash_set_index(result.clone(), key, RObject::integer(count as i64).into_rc())?;
+    }
+    Ok(result)
+}
@@ -28,7 +28,7 @@ pub fn mrb_hash_get_index(this: Rc<RObject>, key: Rc<RObject>) -> Result<Rc<RObj
     let key  = key.as_ref().as_hash_key()?;
     match hash.get(&key).clone() {
         Some((_, value)) => Ok(value.clone()),
-        None => Ok(Rc::new(RObject::nil())),
+        None => Ok(RObject::nil().into_rc()),
     }
 }
 
@@ -46,9 +46,8 @@ pub fn mrb_hash_set_index(this: Rc<RObject>, key: Rc<RObject>, value: Rc<RObject
             return Err(Error::RuntimeError("Hash#[] must called on a hash".to_string()));
         }
     };
-    let mut hash = hash.borrow_mut();
     let hashed  = key.as_hash_key()?;
-    hash.insert(hashed, (key.clone(), value.clone()));
+    this.with_resize(|| hash.borrow_mut().insert(hashed, (key.clone(), value.clone())));
     Ok(value.clone())
 }
 
@@ -84,16 +83,16 @@ fn test_mrb_hash_set_and_index() {
     let mut vm = VM::empty();
     prelude::prelude(&mut vm);
 
-    let hash = Rc::new(RObject::hash(HashMap::new()));
+    let hash = RObject::hash(HashMap::new()).into_rc();
     let keys = vec![
         Rc::new(RObject::string("key".to_string())),
-        Rc::new(RObject::integer(1234)),
+        RObject::integer(1234).into_rc(),
         Rc::new(RObject::symbol("key2".into())),
     ];
     let values = vec![
-        Rc::new(RObject::integer(1)),
-        Rc::new(RObject::integer(2)),
-        Rc::new(RObject::integer(42)),
+        RObject::integer(1).into_rc(),
+        RObject::integer(2).into_rc(),
+        RObject::integer(42).into_rc(),
     ];
 
     for (i, key) in keys.iter().enumerate() {
@@ -116,9 +115,9 @@ fn test_mrb_hash_set_and_index_not_found() {
     let mut vm = VM::empty();
     prelude::prelude(&mut vm);
 
-    let hash = Rc::new(RObject::hash(HashMap::new()));
+    let hash = RObject::hash(HashMap::new()).into_rc();
     let key = Rc::new(RObject::string("key".to_string()));
-    let value = Rc::new(RObject::integer(42));
+    let value = RObject::integer(42).into_rc();
 
     mrb_hash_set_index(hash.clone(), key.clone(), value.clone()).expect("set index failed");
 
@@ -137,7 +136,7 @@ fn mrb_hash_size(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Erro
         }
     };
     let hash = hash.borrow();
-    Ok(Rc::new(RObject::integer(hash.len() as i64)))
+    Ok(RObject::integer(hash.len() as i64).into_rc())
 }
 
 #[test]
@@ -145,9 +144,9 @@ fn test_mrb_hash_size() {
     use std::collections::HashMap;
     let mut vm = VM::empty();
 
-    let hash = Rc::new(RObject::hash(HashMap::new()));
+    let hash = RObject::hash(HashMap::new()).into_rc();
     let key = Rc::new(RObject::string("key".to_string()));
-    let value = Rc::new(RObject::integer(42));
+    let value = RObject::integer(42).into_rc();
     vm.current_regs()[0].replace(hash.clone());
 
     let size = mrb_hash_size(&mut vm, &[]).expect("getting size failed");
@@ -1,30 +1,168 @@
+use std::cmp::Ordering;
 use std::rc::Rc;
 
+use crate::yamrb::bigint::RBigint;
 use crate::yamrb::helpers::mrb_define_cmethod;
 use crate::Error;
 
-use crate::yamrb::{helpers::mrb_call_block, value::RObject, vm::VM};
+use crate::yamrb::{value::{RObject, RValue}, vm::VM};
 
 pub(crate) fn initialize_integer(vm: &mut VM) {
     let integer_class = vm.define_standard_class("Integer");
 
     mrb_define_cmethod(vm, integer_class.clone(), "%", Box::new(mrb_integer_mod));
-    mrb_define_cmethod(vm, integer_class.clone(), "times", Box::new(mrb_integer_times));
+    mrb_define_cmethod(vm, integer_class.clone(), "**", Box::new(mrb_integer_pow));
+    mrb_define_cmethod(vm, integer_class.clone(), "to_s", Box::new(mrb_integer_to_s));
+    mrb_define_cmethod(vm, integer_class.clone(), "inspect", Box::new(mrb_integer_to_s));
 }
 
-fn mrb_integer_times(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
-    let this: i64 = vm.getself()?.as_ref().try_into()?;
-    for i in 0..this {
-        let block = args[0].clone();
-        let args = vec![Rc::new(RObject::integer(i))];
-        mrb_call_block(vm, block, None, &args)?;
+fn mrb_integer_mod(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let lhs = vm.getself()?;
+    match integer_mod(&lhs, &args[0]) {
+        Some(v) => Ok(v?.into_rc()),
+        None => Err(Error::TypeMismatch),
     }
-    vm.getself()
 }
 
-fn mrb_integer_mod(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
-    let lhs: i64 = vm.getself()?.as_ref().try_into()?;
-    let rhs: i64 = args[0].as_ref().try_into()?;
+fn mrb_integer_pow(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let lhs = vm.getself()?;
+    match integer_pow(&lhs, &args[0]) {
+        Some(v) => Ok(v?.into_rc()),
+        None => Err(Error::TypeMismatch),
+    }
+}
+
+fn mrb_integer_to_s(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let s = match &this.value {
+        RValue::Integer(n) => n.to_string(),
+        RValue::Bigint(b) => b.to_string(),
+        _ => return Err(Error::TypeMismatch),
+    };
+    Ok(Rc::new(RObject::string(s)))
+}
+
+// Integer arithmetic shared by the ops and the methods. Each returns
+// None unless both operands are integers, and promotes the result
+// to a bigint only when it overflows i64.
+
+fn to_bigint(v: &RObject) -> Option<RBigint> {
+    match &v.value {
+        RValue::Integer(n) => Some(RBigint::from_i64(*n)),
+        RValue::Bigint(b) => Some(b.clone()),
+        _ => None,
+    }
+}
+
+fn integer_binop(
+    lhs: &RObject,
+    rhs: &RObject,
+    fast: fn(i64, i64) -> Option<i64>,
+    slow: fn(&RBigint, &RBigint) -> RBigint,
+) -> Option<RObject> {
+    if let (RValue::Integer(n1), RValue::Integer(n2)) = (&lhs.value, &rhs.value)
+        && let Some(n) = fast(*n1, *n2)
+    {
+        return Some(RObject::integer(n));
+    }
+    let b1 = to_bigint(lhs)?;
+    let b2 = to_bigint(rhs)?;
+    Some(RObject::bigint(slow(&b1, &b2)))
+}
+
+fn is_zero(v: &RObject) -> bool {
+    matches!(v.value, RValue::Integer(0))
+}
+
+pub(crate) fn integer_add(lhs: &RObject, rhs: &RObject) -> Option<RObject> {
+    integer_binop(lhs, rhs, i64::checked_add, RBigint::add)
+}
+
+pub(crate) fn integer_sub(lhs: &RObject, rhs: &RObject) -> Option<RObject> {
+    integer_binop(lhs, rhs, i64::checked_sub, RBigint::sub)
+}
 
-    Ok(Rc::new(RObject::integer(lhs % rhs)))
+pub(crate) fn integer_mul(lhs: &RObject, rhs: &RObject) -> Option<RObject> {
+    integer_binop(lhs, rhs, i64::checked_mul, RBigint::mul)
+}
+
+// Floored division as Ruby does: -7 / 2 == -4
+pub(crate) fn integer_div(lhs: &RObject, rhs: &RObject) -> Option<Result<RObject, Error>> {
+    if is_zero(rhs) {
+        return to_bigint(lhs).map(|_| Err(Error::ZeroDivisionError("divided by 0".to_string())));
+    }
+    fn fast(n1: i64, n2: i64) -> Option<i64> {
+        let q = n1.checked_div(n2)?;
+        if n1 % n2 != 0 && (n1 < 0) != (n2 < 0) {
+            Some(q - 1)
+        } else {
+            Some(q)
+        }
+    }
+    integer_binop(lhs, rhs, fast, |b1, b2| b1.divmod(b2).unwrap().0).map(Ok)
+}
+
+// Modulo taking the sign of the divisor: -7 % 2 == 1
+pub(crate) fn integer_mod(lhs: &RObject, rhs: &RObject) -> Option<Result<RObject, Error>> {
+    if is_zero(rhs) {
+        return to_bigint(lhs).map(|_| Err(Error::ZeroDivisionError("divided by 0".to_string())));
+    }
+    fn fast(n1: i64, n2: i64) -> Option<i64> {
+        let r = n1.checked_rem(n2)?;
+        if r != 0 && (r < 0) != (n2 < 0) {
+            Some(r + n2)
+        } else {
+            Some(r)
+        }
+    }
+    integer_binop(lhs, rhs, fast, |b1, b2| b1.divmod(b2).unwrap().1).map(Ok)
+}
+
+// A negative exponent gives a float: 2 ** -1 == 0.5
+pub(crate) fn integer_pow(lhs: &RObject, rhs: &RObject) -> Option<Result<RObject, Error>> {
+    let base = to_bigint(lhs)?;
+    let exp = match &rhs.value {
+        RValue::Integer(n) if *n < 0 => return Some(Ok(RObject::float(to_f64(lhs)?.powf(*n as f64)))),
+        RValue::Integer(n) => *n as u64,
+        RValue::Bigint(_) => return Some(Err(Error::ArgumentError("exponent too large".to_string()))),
+        _ => return None,
+    };
+    if let RValue::Integer(n) = &lhs.value
+        && let Ok(exp) = u32::try_from(exp)
+        && let Some(n) = n.checked_pow(exp)
+    {
+        return Some(Ok(RObject::integer(n)));
+    }
+    // exponentiation by squaring
+    let (mut base, mut exp) = (base, exp);
+    let mut result = RBigint::from_i64(1);
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.mul(&base);
+        }
+        exp >>= 1;
+        if exp > 0 {
+            base = base.mul(&base);
+        }
+    }
+    Some(Ok(RObject::bigint(result)))
+}
+
+// Compares numbers, including integers against floats
+pub(crate) fn numeric_cmp(lhs: &RObject, rhs: &RObject) -> Option<Ordering> {
+    match (&lhs.value, &rhs.value) {
+        (RValue::Integer(n1), RValue::Integer(n2)) => Some(n1.cmp(n2)),
+        (RValue::Float(f1), _) => f1.partial_cmp(&to_f64(rhs)?),
+        (_, RValue::Float(f2)) => to_f64(lhs)?.partial_cmp(f2),
+        _ => Some(to_bigint(lhs)?.cmp(&to_bigint(rhs)?)),
+    }
+}
+
+fn to_f64(v: &RObject) -> Option<f64> {
+    match &v.value {
+        RValue::Integer(n) => Some(*n as f64),
+        RValue::Bigint(b) => Some(b.to_f64()),
+        RValue::Float(f) => Some(*f),
+        _ => None,
+    }
 }
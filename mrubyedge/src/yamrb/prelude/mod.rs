@@ -4,20 +4,30 @@ pub mod object;
 pub mod exception;
 pub mod class;
 pub mod integer;
+pub mod symbol;
 pub mod string;
 pub mod array;
 pub mod hash;
 pub mod range;
+pub mod proc;
 pub mod shared_memory;
+pub mod fiber;
+pub mod gc;
+pub mod mrblib;
 
 pub fn prelude(vm: &mut VM) {
     object::initialize_object(vm);
     exception::initialize_exception(vm);
     class::initialize_class(vm);
     integer::initialize_integer(vm);
+    symbol::initialize_symbol(vm);
     string::initialize_string(vm);
     array::initialize_array(vm);
     hash::initialize_hash(vm);
     range::initialize_range(vm);
+    proc::initialize_proc(vm);
     shared_memory::initialize_shared_memory(vm);
+    fiber::initialize_fiber(vm);
+    gc::initialize_gc(vm);
+    mrblib::initialize_mrblib(vm);
 }
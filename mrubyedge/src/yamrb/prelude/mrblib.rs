@@ -0,0 +1,21 @@
+use std::mem;
+
+use crate::rite::{self, Rite};
+use crate::yamrb::vm::VM;
+
+const ITERATOR_MRB: &[u8] = include_bytes!("mrblib/iterator.mrb");
+
+// The binary of the methods written in Ruby, see mrblib/iterator.rb
+pub(crate) fn load() -> Rite<'static> {
+    rite::load(ITERATOR_MRB).expect("iterator.mrb is not a valid binary")
+}
+
+// Runs the top level of the mrblib, which defines the methods. The VM
+// has not run yet, so it is left as it was.
+pub(crate) fn initialize_mrblib(vm: &mut VM) {
+    let main = mem::replace(&mut vm.current_irep, vm.mrblib.clone());
+    vm.run().expect("mrblib failed to run");
+    vm.current_irep = main;
+    vm.pc.set(0);
+    vm.regs.clear();
+}
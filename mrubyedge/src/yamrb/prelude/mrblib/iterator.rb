@@ -0,0 +1,42 @@
+# Iterators written in Ruby run their blocks on the frames of the VM,
+# which lets Fiber.yield and VM#step suspend them.
+# After editing, update iterator.mrb by
+#   MRBLIB_WRITE=1 cargo test --test mrblib
+
+class Array
+  def each
+    i = 0
+    while i < size
+      yield self[i]
+      i += 1
+    end
+    self
+  end
+end
+
+class Integer
+  def times
+    i = 0
+    while i < self
+      yield i
+      i += 1
+    end
+    self
+  end
+end
+
+class Range
+  def each
+    i = self.begin
+    last = self.end
+    unless i.class == Integer && last.class == Integer
+      raise "Range#each must be called on a integer Range with block (for now)"
+    end
+    last -= 1 if exclude_end?
+    while i <= last
+      yield i
+      i += 1
+    end
+    self
+  end
+end
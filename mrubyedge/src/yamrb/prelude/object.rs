@@ -1,6 +1,8 @@
 use std::rc::Rc;
 
-use crate::{yamrb::{helpers::{mrb_define_cmethod, mrb_funcall}, value::*, vm::VM}, Error};
+use crate::{yamrb::{helpers::{mrb_define_cmethod, mrb_define_method, mrb_funcall, mrb_singleton_class, without_block}, value::*, vm::VM}, Error};
+
+use super::class::module_args;
 
 pub(crate) fn initialize_object(vm: &mut VM) {
     let object_class = vm.object_class.clone();
@@ -23,6 +25,10 @@ pub(crate) fn initialize_object(vm: &mut VM) {
     mrb_define_cmethod(vm, object_class.clone(), "to_s", Box::new(mrb_object_to_s));
     mrb_define_cmethod(vm, object_class.clone(), "inspect", Box::new(mrb_object_to_s));
     mrb_define_cmethod(vm, object_class.clone(), "raise", Box::new(mrb_object_raise));
+    mrb_define_cmethod(vm, object_class.clone(), "class", Box::new(mrb_object_class));
+    mrb_define_cmethod(vm, object_class.clone(), "extend", Box::new(mrb_object_extend));
+    mrb_define_cmethod(vm, object_class.clone(), "singleton_class", Box::new(mrb_object_singleton_class));
+    mrb_define_cmethod(vm, object_class.clone(), "define_singleton_method", Box::new(mrb_object_define_singleton_method));
 
     // define global consts:
     vm.consts.insert("RUBY_VERSION".to_string(), Rc::new(RObject::string(crate::yamrb::vm::VERSION.to_string())));
@@ -47,7 +53,7 @@ pub fn mrb_kernel_puts(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>,
             println!("{}", inspect);
         }
     }
-    Ok(Rc::new(RObject::nil()))
+    Ok(RObject::nil().into_rc())
 }
 
 #[cfg(feature = "wasi")]
@@ -56,7 +62,7 @@ pub fn mrb_kernel_p(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Er
     let inspect = mrb_funcall(vm, Some(msg), "inspect", &[])?;
     let inspect: String = inspect.as_ref().try_into()?;
     println!("{}", inspect);
-    Ok(Rc::new(RObject::nil()))
+    Ok(RObject::nil().into_rc())
 }
 
 #[cfg(feature = "wasi")]
@@ -64,15 +70,15 @@ pub fn mrb_kernel_debug(_vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject
     for (i, obj) in args.iter().enumerate() {
         dbg!(i, obj.clone());
     }
-    Ok(Rc::new(RObject::nil()))
+    Ok(RObject::nil().into_rc())
 }
 
 pub fn mrb_object_is_equal(_vm: &mut VM, lhs: Rc<RObject>, rhs: Rc<RObject>) -> Rc<RObject> {
-    RObject::boolean(lhs.as_eq_value() == rhs.as_eq_value()).to_refcount_assigned()
+    RObject::boolean(lhs.as_eq_value() == rhs.as_eq_value()).into_rc()
 }
 
 pub fn mrb_object_is_not_equal(_vm: &mut VM, lhs: Rc<RObject>, rhs: Rc<RObject>) -> Rc<RObject> {
-    RObject::boolean(lhs.as_eq_value() != rhs.as_eq_value()).to_refcount_assigned()
+    RObject::boolean(lhs.as_eq_value() != rhs.as_eq_value()).into_rc()
 }
 
 pub fn mrb_object_double_eq(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
@@ -87,25 +93,28 @@ pub fn mrb_object_triple_eq(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObj
 
     match (&lhs.value, &rhs.value) {
         (RValue::Integer(i1), RValue::Integer(i2)) => {
-            Ok(Rc::new(RObject::boolean(*i1 == *i2)))
+            Ok(RObject::boolean(*i1 == *i2).into_rc())
+        }
+        (RValue::Bigint(b1), RValue::Bigint(b2)) => {
+            Ok(RObject::boolean(b1 == b2).into_rc())
         }
         (RValue::Float(f1), RValue::Float(f2)) => {
-            Ok(Rc::new(RObject::boolean(*f1 == *f2)))
+            Ok(RObject::boolean(*f1 == *f2).into_rc())
         }
         (RValue::Symbol(sym1), RValue::Symbol(sym2)) => {
-            Ok(Rc::new(RObject::boolean(sym1 == sym2)))
+            Ok(RObject::boolean(sym1 == sym2).into_rc())
         }
         (RValue::String(s1), RValue::String(s2)) => {
-            Ok(Rc::new(RObject::boolean(s1 == s2)))
+            Ok(RObject::boolean(s1 == s2).into_rc())
         }
         (RValue::Class(c1), _) => {
             match &lhs.value {
                 RValue::Class(c2) => {
-                    Ok(Rc::new(RObject::boolean(c1.sym_id == c2.sym_id)))
+                    Ok(RObject::boolean(c1.sym_id == c2.sym_id).into_rc())
                 }
                 _ => {
                     let c2 = lhs.get_class(vm);
-                    Ok(Rc::new(RObject::boolean(c1.sym_id == c2.sym_id)))
+                    Ok(RObject::boolean(c1.sym_id == c2.sym_id).into_rc())
                 }
             }
         }
@@ -115,7 +124,7 @@ pub fn mrb_object_triple_eq(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObj
         }
         // TODO: Implement object id for generic instance
         _ => {
-            Ok(Rc::new(RObject::boolean(false)))
+            Ok(RObject::boolean(false).into_rc())
         }
     }
 }
@@ -125,7 +134,7 @@ pub fn mrb_object_object_id(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<ROb
     let x = vm.getself()?.object_id.get();
     // ref: https://stackoverflow.com/questions/74491204/how-do-i-represent-an-i64-in-the-u64-domain
     let to_i64 = ((x as i64) ^ (1 << 63)) & (1 << 63) | (x & (u64::MAX >> 1)) as i64;
-    Ok(Rc::new(RObject::integer(to_i64)))
+    Ok(RObject::integer(to_i64).into_rc())
 }
 
 pub fn mrb_object_to_s(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
@@ -133,16 +142,83 @@ pub fn mrb_object_to_s(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>
     Ok(Rc::new(RObject::string(format!("{:?}", obj))))
 }
 
-pub fn mrb_object_raise(_vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
-    // TODO: accept exception class
-    let msg = args[0].as_ref().try_into()?;
-    let err = Error::RuntimeError(msg);
-    Err(err)
+pub fn mrb_object_raise(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let args = without_block(args);
+    let exc = match args.first().map(|arg| &arg.value) {
+        None => {
+            return Err(Error::RuntimeError("unhandled exception".to_string()));
+        }
+        Some(RValue::String(_)) => {
+            let msg = args[0].as_ref().try_into()?;
+            return Err(Error::RuntimeError(msg));
+        }
+        Some(RValue::Class(_)) => {
+            // `raise Klass, msg` is `raise Klass.new(msg)`
+            mrb_funcall(vm, Some(args[0].clone()), "new", &args[1..])?
+        }
+        Some(_) => args[0].clone(),
+    };
+    match &exc.value {
+        RValue::Exception(e) => {
+            e.object.replace(Rc::downgrade(&exc));
+            Err(Error::Exception(e.clone()))
+        }
+        _ => Err(Error::TypeError("exception class/object expected".to_string())),
+    }
+}
+
+pub fn mrb_object_class(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let mut klass = this.get_class(vm);
+    // singleton classes are not the class of the object
+    while klass.is_singleton {
+        match klass.super_class.clone() {
+            Some(sc) => klass = sc,
+            None => break,
+        }
+    }
+    Ok(Rc::new(klass.into()))
 }
 
 pub fn mrb_object_initialize(_vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     // Abstract method; do nothing
-    Ok(Rc::new(RObject::nil()))
+    Ok(RObject::nil().into_rc())
+}
+
+pub fn mrb_object_extend(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let modules = module_args(args)?;
+    let singleton = mrb_singleton_class(vm, &this)?;
+    for module in modules.into_iter().rev() {
+        singleton.include_module(module);
+    }
+    vm.expire_method_caches();
+    Ok(this)
+}
+
+pub fn mrb_object_singleton_class(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let singleton = mrb_singleton_class(vm, &this)?;
+    Ok(Rc::new(singleton.into()))
+}
+
+pub fn mrb_object_define_singleton_method(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let name = match &args[0].value {
+        RValue::Symbol(sym) => sym.clone(),
+        RValue::String(s) => RSym::new(String::from_utf8_lossy(&s.borrow()).to_string()),
+        _ => return Err(Error::TypeMismatch),
+    };
+    let mut method = match args.get(1).map(|b| &b.value) {
+        Some(RValue::Proc(p)) => p.clone(),
+        _ => return Err(Error::ArgumentError("tried to create Proc object without a block".to_string())),
+    };
+    // the block body runs as a method named after it
+    method.sym_id = Some(name.clone());
+    method.is_lambda = true;
+    let singleton = mrb_singleton_class(vm, &this)?;
+    mrb_define_method(vm, singleton, &name.name, method);
+    Ok(Rc::new(RObject::symbol(name)))
 }
 
 #[test]
@@ -179,18 +255,18 @@ fn test_mrb_object_is_equal() {
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(!ret);
 
-    let lhs = RObject::boolean(true).to_refcount_assigned();
-    let rhs = RObject::boolean(true).to_refcount_assigned();
+    let lhs = RObject::boolean(true).into_rc();
+    let rhs = RObject::boolean(true).into_rc();
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(ret);
 
-    let lhs = RObject::boolean(false).to_refcount_assigned();
-    let rhs = RObject::boolean(false).to_refcount_assigned();
+    let lhs = RObject::boolean(false).into_rc();
+    let rhs = RObject::boolean(false).into_rc();
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(ret);
 
-    let lhs = RObject::boolean(true).to_refcount_assigned();
-    let rhs = RObject::boolean(false).to_refcount_assigned();
+    let lhs = RObject::boolean(true).into_rc();
+    let rhs = RObject::boolean(false).into_rc();
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(!ret);
 
@@ -204,18 +280,18 @@ fn test_mrb_object_is_equal() {
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(!ret);
 
-    let lhs = RObject::nil().to_refcount_assigned();
-    let rhs = RObject::nil().to_refcount_assigned();
+    let lhs = RObject::nil().into_rc();
+    let rhs = RObject::nil().into_rc();
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(ret);
 
     let lhs = RObject::integer(100).to_refcount_assigned();
-    let rhs = RObject::nil().to_refcount_assigned();
+    let rhs = RObject::nil().into_rc();
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(!ret);
 
     let lhs = RObject::integer(100).to_refcount_assigned();
-    let rhs = RObject::nil().to_refcount_assigned();
+    let rhs = RObject::nil().into_rc();
     let ret: bool = mrb_object_is_equal(&mut vm, lhs, rhs).as_ref().try_into().expect("must return bool");
     assert!(!ret);
 }
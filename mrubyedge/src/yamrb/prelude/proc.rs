@@ -0,0 +1,26 @@
+use std::mem;
+use std::rc::Rc;
+
+use crate::{yamrb::{helpers::{mrb_call_block_with_kargs, mrb_define_cmethod, without_block}, value::*, vm::VM}, Error};
+
+pub(crate) fn initialize_proc(vm: &mut VM) {
+    let proc_class = vm.define_standard_class("Proc");
+
+    mrb_define_cmethod(vm, proc_class.clone(), "call", Box::new(mrb_proc_call));
+    mrb_define_cmethod(vm, proc_class.clone(), "[]", Box::new(mrb_proc_call));
+}
+
+fn mrb_proc_call(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let kargs_given = mem::take(&mut vm.kargs_given);
+    let this = vm.getself()?;
+    if !matches!(&this.value, RValue::Proc(_)) {
+        return Err(Error::RuntimeError("Proc#call must be called on a Proc".to_string()));
+    }
+    // the keyword hash comes last when given
+    let args = without_block(args);
+    let (kargs, args) = match args.split_last() {
+        Some((kargs, args)) if kargs_given => (Some(kargs.clone()), args),
+        _ => (None, args),
+    };
+    mrb_call_block_with_kargs(vm, this, None, args, kargs)
+}
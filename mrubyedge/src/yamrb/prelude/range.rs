@@ -1,12 +1,14 @@
 use std::rc::Rc;
 
-use crate::{yamrb::{helpers::{mrb_call_block, mrb_define_cmethod}, value::{RObject, RValue}, vm::VM}, Error};
+use crate::{yamrb::{helpers::mrb_define_cmethod, value::{RObject, RValue}, vm::VM}, Error};
 
 pub(crate) fn initialize_range(vm: &mut VM) {
     let range_class = vm.define_standard_class("Range");
     
     mrb_define_cmethod(vm, range_class.clone(), "include?", Box::new(mrb_range_is_include));
-    mrb_define_cmethod(vm, range_class.clone(), "each", Box::new(mrb_range_each));
+    mrb_define_cmethod(vm, range_class.clone(), "begin", Box::new(mrb_range_begin));
+    mrb_define_cmethod(vm, range_class.clone(), "end", Box::new(mrb_range_end));
+    mrb_define_cmethod(vm, range_class.clone(), "exclude_end?", Box::new(mrb_range_is_exclude_end));
 }
 
 pub fn mrb_range_is_include(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
@@ -17,22 +19,20 @@ pub fn mrb_range_is_include(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObj
             match (&start.value, &end.value, &obj.value) {
                 (RValue::Integer(start), RValue::Integer(end), RValue::Integer(obj)) => {
                     if *exclusive {
-                        Ok(Rc::new(RObject::boolean(*start <= *obj && *obj < *end)))
+                        Ok(RObject::boolean(*start <= *obj && *obj < *end).into_rc())
                     } else {
-                        Ok(Rc::new(RObject::boolean(*start <= *obj && *obj <= *end)))
+                        Ok(RObject::boolean(*start <= *obj && *obj <= *end).into_rc())
                     }
                 }
                 (RValue::Integer(start), RValue::Integer(end), RValue::Float(obj)) => {
                     let obj = *obj as i64;
                     if *exclusive {
-                        Ok(Rc::new(RObject::boolean(*start <= obj && obj < *end)))
+                        Ok(RObject::boolean(*start <= obj && obj < *end).into_rc())
                     } else {
-                        Ok(Rc::new(RObject::boolean(*start <= obj && obj <= *end)))
+                        Ok(RObject::boolean(*start <= obj && obj <= *end).into_rc())
                     }
                 }
-                _ => {
-                    return Ok(Rc::new(RObject::boolean(false)));
-                }
+                _ => Ok(RObject::boolean(false).into_rc()),
             }
         }
         _ => {
@@ -41,31 +41,26 @@ pub fn mrb_range_is_include(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObj
     }
 }
 
-pub fn mrb_range_each(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+pub fn mrb_range_begin(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     let this = vm.getself()?;
-    let block = &args[0];
     match &this.value {
-        RValue::Range(start, end, exclusive) => {
-            match (&start.value, &end.value) {
-                (RValue::Integer(start), RValue::Integer(end)) => {
-                    let start = *start;
-                    let mut end = *end;
-                    if *exclusive {
-                        end = end - 1;
-                    }
-                    for i in start..=end {
-                        let args = vec![Rc::new(RObject::integer(i))];
-                        mrb_call_block(vm, block.clone(), None, &args)?;
-                    }
-                }
-                _ => {
-                    return Err(Error::RuntimeError("Range#each must be called on a integer Range with block (for now)".to_string()));
-                }
-            }
-        }
-        _ => {
-            return Err(Error::RuntimeError("Range#each must be called on a Range".to_string()));
-        }
+        RValue::Range(start, _, _) => Ok(start.clone()),
+        _ => Err(Error::RuntimeError("Range#begin must be called on a Range".to_string())),
     }
-    Ok(this.clone())
-}
\ No newline at end of file
+}
+
+pub fn mrb_range_end(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    match &this.value {
+        RValue::Range(_, end, _) => Ok(end.clone()),
+        _ => Err(Error::RuntimeError("Range#end must be called on a Range".to_string())),
+    }
+}
+
+pub fn mrb_range_is_exclude_end(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    match &this.value {
+        RValue::Range(_, _, exclusive) => Ok(RObject::boolean(*exclusive).into_rc()),
+        _ => Err(Error::RuntimeError("Range#exclude_end? must be called on a Range".to_string())),
+    }
+}
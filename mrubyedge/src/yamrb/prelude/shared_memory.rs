@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 use std::rc::Rc;
 
 use crate::yamrb::shared_memory::SharedMemory;
@@ -16,14 +16,16 @@ pub(crate) fn initialize_shared_memory(vm: &mut VM) {
     mrb_define_cmethod(vm, shared_memory_class.clone(), "read_by_size", Box::new(mrb_shared_memory_read_by_size));
 }
 
-pub fn mrb_shared_memory_new(_vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+pub fn mrb_shared_memory_new(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     let size: u64 = args[0].as_ref().try_into().expect("arg[0] must be integer");
+    vm.check_memory(size as usize)?;
     let obj = RObject {
         tt: RType::SharedMemory,
         value: RValue::SharedMemory(Rc::new(RefCell::new(
             SharedMemory::new(size as usize),
         ))),
-        object_id: u64::MAX.into()
+        object_id: u64::MAX.into(),
+        meter: OnceCell::new(),
     };
     Ok(obj.to_refcount_assigned())
 }
@@ -37,7 +39,7 @@ fn mrb_shared_memory_offset_in_memory(vm: &mut VM, _args: &[Rc<RObject>]) -> Res
         }
     };
     let offset = sm.borrow().offset_in_memory();
-    Ok(Rc::new(RObject::integer(offset as i64)))
+    Ok(RObject::integer(offset as i64).into_rc())
 }
 
 fn mrb_shared_memory_set_index_range(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
@@ -124,14 +126,14 @@ fn mrb_shared_memory_read_by_size(vm: &mut VM, args: &[Rc<RObject>]) -> Result<R
     match size {
         1 => {
             let value = sm.borrow().memory.as_ref()[offset];
-            Ok(Rc::new(RObject::integer(value as i64)))
+            Ok(RObject::integer(value as i64).into_rc())
         }
         2 => {
             let value = u16::from_le_bytes([
                 sm.borrow().memory.as_ref()[offset],
                 sm.borrow().memory.as_ref()[offset + 1],
             ]);
-            Ok(Rc::new(RObject::integer(value as i64)))
+            Ok(RObject::integer(value as i64).into_rc())
         }
         4 => {
             let sm_borrowed = sm.borrow();
@@ -142,7 +144,7 @@ fn mrb_shared_memory_read_by_size(vm: &mut VM, args: &[Rc<RObject>]) -> Result<R
                 memory[offset + 2],
                 memory[offset + 3],
             ]);
-            Ok(Rc::new(RObject::integer(value as i64)))
+            Ok(RObject::integer(value as i64).into_rc())
         }
         8 => {
             let sm_borrowed = sm.borrow();
@@ -157,7 +159,7 @@ fn mrb_shared_memory_read_by_size(vm: &mut VM, args: &[Rc<RObject>]) -> Result<R
                 memory[offset + 6],
                 memory[offset + 7],
             ]);
-            Ok(Rc::new(RObject::integer(value as i64)))
+            Ok(RObject::integer(value as i64).into_rc())
         }
         _ => {
             Err(Error::RuntimeError("Invalid size passed".to_string()))
@@ -170,7 +172,7 @@ fn test_mrb_shared_memory_new() {
     let mut vm = VM::empty();
     initialize_shared_memory(&mut vm);
 
-    let args = vec![Rc::new(RObject::integer(10))];
+    let args = vec![RObject::integer(10).into_rc()];
     let sm = mrb_shared_memory_new(&mut vm, &args).expect("failed to create SharedMemory");
     match &sm.value {
         RValue::SharedMemory(s) => {
@@ -187,11 +189,11 @@ fn test_mrb_shared_memory_read_by_size() {
     let mut vm = VM::empty();
     initialize_shared_memory(&mut vm);
 
-    let args = vec![Rc::new(RObject::integer(10))];
+    let args = vec![RObject::integer(10).into_rc()];
     let sm = mrb_shared_memory_new(&mut vm, &args).expect("failed to create SharedMemory");
     vm.current_regs()[0].replace(sm);
 
-    let args = vec![Rc::new(RObject::integer(1)), Rc::new(RObject::integer(0))];
+    let args = vec![RObject::integer(1).into_rc(), RObject::integer(0).into_rc()];
     let result = mrb_shared_memory_read_by_size(&mut vm, &args).expect("failed to read");
     let result: i64 = result.as_ref().try_into().expect("not an integer");
     assert_eq!(result, 0);
@@ -207,17 +209,17 @@ fn test_mrb_shared_memory_read_by_size() {
         }
     }
 
-    let args = vec![Rc::new(RObject::integer(1)), Rc::new(RObject::integer(0))];
+    let args = vec![RObject::integer(1).into_rc(), RObject::integer(0).into_rc()];
     let result = mrb_shared_memory_read_by_size(&mut vm, &args).expect("failed to read");
     let result: i64 = result.as_ref().try_into().expect("not an integer");
     assert_eq!(result, 1);
 
-    let args = vec![Rc::new(RObject::integer(2)), Rc::new(RObject::integer(1))];
+    let args = vec![RObject::integer(2).into_rc(), RObject::integer(1).into_rc()];
     let result = mrb_shared_memory_read_by_size(&mut vm, &args).expect("failed to read");
     let result: i64 = result.as_ref().try_into().expect("not an integer");
     assert_eq!(result, 770);
 
-    let args = vec![Rc::new(RObject::integer(4)), Rc::new(RObject::integer(3))];
+    let args = vec![RObject::integer(4).into_rc(), RObject::integer(3).into_rc()];
     let result = mrb_shared_memory_read_by_size(&mut vm, &args).expect("failed to read");
     let result: i64 = result.as_ref().try_into().expect("not an integer");
     assert_eq!(result, 117835012);
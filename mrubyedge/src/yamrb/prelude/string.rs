@@ -1,6 +1,6 @@
 use std::rc::Rc;
 
-use crate::{yamrb::{helpers::mrb_define_cmethod, value::RObject, vm::VM}, Error};
+use crate::{yamrb::{helpers::mrb_define_cmethod, value::{RObject, RValue}, vm::VM}, Error};
 
 use super::array::mrb_array_push;
 
@@ -11,6 +11,7 @@ pub(crate) fn initialize_string(vm: &mut VM) {
     mrb_define_cmethod(vm, string_class.clone(), "unpack", Box::new(mrb_string_unpack));
     mrb_define_cmethod(vm, string_class.clone(), "size", Box::new(mrb_string_size));
     mrb_define_cmethod(vm, string_class.clone(), "length", Box::new(mrb_string_size));
+    mrb_define_cmethod(vm, string_class.clone(), "*", Box::new(mrb_string_repeat));
 }
 
 fn bytes_of<const N: usize>(value: &[u8], cursor: usize) -> Result<[u8; N], Error> {
@@ -38,7 +39,7 @@ fn mrb_string_unpack(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, E
     let value: Vec<u8> = this.as_ref().try_into()?;
     let format: Vec<u8> = args[0].as_ref().try_into()?;
     let mut cursor: usize = 0;
-    let result = Rc::new(RObject::array(Vec::new()));
+    let result = RObject::array(Vec::new()).into_rc();
 
     for c in format.iter() {
         let value = match c {
@@ -90,7 +91,7 @@ fn mrb_string_unpack(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, E
                 return Err(Error::RuntimeError("Unsupported format".to_string()));
             }
         };
-        mrb_array_push(result.clone(), &[Rc::new(RObject::integer(value as i64))])?;
+        mrb_array_push(result.clone(), &[RObject::integer(value).into_rc()])?;
     }
 
     Ok(result)
@@ -126,7 +127,7 @@ fn test_mrb_string_unpack() {
     ];
 
     for (i, expected) in answers.iter().enumerate() {
-        let args = vec![Rc::new(RObject::integer(i as i64))];
+        let args = vec![RObject::integer(i as i64).into_rc()];
         let value = prelude::array::mrb_array_get_index(ret.clone(), &args).expect("getting index failed");
         let value: i64 = value.as_ref().try_into().expect("value is not integer");
         assert_eq!(value, *expected);
@@ -136,7 +137,7 @@ fn test_mrb_string_unpack() {
 fn mrb_string_size(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
     let this = vm.getself()?;
     let value: Vec<u8> = this.as_ref().try_into()?;
-    Ok(Rc::new(RObject::integer(value.len() as i64)))
+    Ok(RObject::integer(value.len() as i64).into_rc())
 }
 
 #[test]
@@ -154,4 +155,20 @@ fn test_mrb_string_size() {
     let ret = helpers::mrb_funcall(&mut vm, Some(data), "length", &[]).expect("size failed");
     let ret: i64 = ret.as_ref().try_into().expect("size is not integer");
     assert_eq!(ret, 12);
-}
\ No newline at end of file
+}
+
+fn mrb_string_repeat(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let this = vm.getself()?;
+    let value: Vec<u8> = this.as_ref().try_into()?;
+    let count = match &args[0].value {
+        RValue::Integer(n) if *n < 0 => return Err(Error::ArgumentError("negative argument".to_string())),
+        RValue::Integer(n) => *n as usize,
+        RValue::Bigint(_) => return Err(Error::ArgumentError("argument too big".to_string())),
+        _ => return Err(Error::TypeMismatch),
+    };
+    let bytes = value.len().checked_mul(count)
+        .ok_or_else(|| Error::ArgumentError("argument too big".to_string()))?;
+    // check before allocating, which may abort the process
+    vm.check_memory(bytes)?;
+    Ok(RObject::string_from_vec(value.repeat(count)).into_rc())
+}
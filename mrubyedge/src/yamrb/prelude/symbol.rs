@@ -0,0 +1,104 @@
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::{yamrb::{helpers::{mrb_define_cmethod, mrb_funcall, mrb_singleton_class, without_block}, snapshot::MadeFn, value::*, vm::VM}, Error};
+
+pub(crate) fn initialize_symbol(vm: &mut VM) {
+    let symbol_class = vm.define_standard_class("Symbol");
+
+    mrb_define_cmethod(vm, symbol_class.clone(), "to_s", Box::new(mrb_symbol_to_s));
+    mrb_define_cmethod(vm, symbol_class.clone(), "id2name", Box::new(mrb_symbol_to_s));
+    mrb_define_cmethod(vm, symbol_class.clone(), "to_sym", Box::new(mrb_symbol_to_sym));
+    mrb_define_cmethod(vm, symbol_class.clone(), "inspect", Box::new(mrb_symbol_inspect));
+    mrb_define_cmethod(vm, symbol_class.clone(), "<=>", Box::new(mrb_symbol_cmp));
+    mrb_define_cmethod(vm, symbol_class.clone(), "to_proc", Box::new(mrb_symbol_to_proc));
+
+    let symbol_class_obj = Rc::new(RObject::class(symbol_class));
+    let singleton = mrb_singleton_class(vm, &symbol_class_obj).expect("Symbol has a singleton class");
+    mrb_define_cmethod(vm, singleton, "all_symbols", Box::new(mrb_symbol_all_symbols));
+}
+
+fn this_symbol(vm: &mut VM) -> Result<RSym, Error> {
+    match &vm.getself()?.value {
+        RValue::Symbol(sym) => Ok(sym.clone()),
+        _ => Err(Error::TypeMismatch),
+    }
+}
+
+fn mrb_symbol_to_s(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let sym = this_symbol(vm)?;
+    Ok(Rc::new(RObject::string(sym.name)))
+}
+
+fn mrb_symbol_to_sym(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    vm.getself()
+}
+
+fn mrb_symbol_inspect(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let sym = this_symbol(vm)?;
+    Ok(Rc::new(RObject::string(format!(":{}", sym.name))))
+}
+
+// Symbols are ordered by their names; nil for non symbols
+fn mrb_symbol_cmp(vm: &mut VM, args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let sym = this_symbol(vm)?;
+    let other = match &args[0].value {
+        RValue::Symbol(other) => other,
+        _ => return Ok(RObject::nil().into_rc()),
+    };
+    let n = match sym.name.cmp(&other.name) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    };
+    Ok(RObject::integer(n).into_rc())
+}
+
+fn mrb_symbol_to_proc(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let sym = this_symbol(vm)?;
+    Ok(symbol_to_proc(vm, &sym))
+}
+
+// The function of the proc made by Symbol#to_proc
+pub(crate) fn symbol_proc_fn(name: &str) -> RFn {
+    let name = name.to_string();
+    Box::new(move |vm: &mut VM, args: &[Rc<RObject>]| {
+        let (recv, args) = without_block(args).split_first()
+            .ok_or_else(|| Error::ArgumentError("no receiver given".to_string()))?;
+        mrb_funcall(vm, Some(recv.clone()), &name, args)
+    })
+}
+
+// A proc calling the method named by the symbol on its first argument.
+// The proc is made once per symbol and kept in VM::sym_proc_cache.
+pub(crate) fn symbol_to_proc(vm: &mut VM, sym: &RSym) -> Rc<RObject> {
+    if let Some(proc) = vm.sym_proc_cache.get(sym) {
+        return proc.clone();
+    }
+    let index = vm.register_made_fn(MadeFn::SymbolProc(sym.name.clone()));
+    let proc = RObject {
+        tt: RType::Proc,
+        value: RValue::Proc(RProc {
+            is_rb_func: false,
+            is_lambda: true,
+            sym_id: Some(sym.clone()),
+            next: None,
+            irep: None,
+            func: Some(index),
+            environ: None,
+            block_self: None,
+        }),
+        object_id: u64::MAX.into(),
+        meter: OnceCell::new(),
+    }.to_refcount_assigned();
+    vm.sym_proc_cache.insert(sym.clone(), proc.clone());
+    proc
+}
+
+fn mrb_symbol_all_symbols(vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    let symbols = vm.symbols.all().into_iter()
+        .map(|sym| Rc::new(RObject::symbol(sym)))
+        .collect();
+    Ok(RObject::array(symbols).into_rc())
+}
@@ -1,16 +1,24 @@
 use std::pin::Pin;
+use std::rc::Rc;
+
+use super::memory::{self, MemoryMeter};
 
 #[derive(Debug)]
 pub struct SharedMemory {
     pub memory: Pin<Box<[u8]>>,
     pub size: usize,
+    meter: Option<Rc<MemoryMeter>>,
 }
 
 impl SharedMemory {
     pub fn new(size: usize) -> Self {
+        let meter = memory::current();
+        if let Some(meter) = &meter {
+            meter.charge(size);
+        }
         let memory = vec![0u8; size].into_boxed_slice();
         let memory = Pin::new(memory);
-        SharedMemory { memory, size }
+        SharedMemory { memory, size, meter }
     }
 
     pub fn offset_in_memory(&self) -> usize {
@@ -32,4 +40,12 @@ impl SharedMemory {
     pub fn read_u8(&self, offset: usize) -> u8 {
         self.memory[offset]
     }
-}
\ No newline at end of file
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        if let Some(meter) = &self.meter {
+            meter.release(self.size);
+        }
+    }
+}
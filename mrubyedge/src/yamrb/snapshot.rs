@@ -0,0 +1,1159 @@
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+use crate::Error;
+
+use super::bigint::RBigint;
+use super::prelude::class::{attr_reader_fn, attr_writer_fn};
+use super::prelude::symbol::symbol_proc_fn;
+use super::shared_memory::SharedMemory;
+use super::value::*;
+use super::vm::{ENV, IREP, VM};
+
+// Snapshots of an idle VM: classes with their method tables, block
+// environments, the object graph, consts, globals and the register stack.
+// Code is not included; a snapshot is restored into a VM opened from the
+// same Rite, whose prelude classes and Rust functions are reused.
+//
+// The blob is the magic and version followed by an Image, written with
+// little endian integers and length prefixed strings and lists.
+const MAGIC: &[u8; 4] = b"MRES";
+const VERSION: u32 = 1;
+
+// How to make again a Rust function made by the program, which is
+// not in a freshly opened VM
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum MadeFn {
+    AttrReader(String),
+    AttrWriter(String),
+    SymbolProc(String),
+}
+
+impl MadeFn {
+    pub(crate) fn make(&self) -> RFn {
+        match self {
+            MadeFn::AttrReader(name) => attr_reader_fn(name),
+            MadeFn::AttrWriter(name) => attr_writer_fn(name),
+            MadeFn::SymbolProc(name) => symbol_proc_fn(name),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Image {
+    // code length of each IREP, to check the snapshot against the program
+    ireps: Vec<u32>,
+    classes: Vec<ClassImage>,
+    envs: Vec<EnvImage>,
+    objects: Vec<ObjectImage>,
+    consts: Vec<(String, u32)>,
+    globals: Vec<(String, u32)>,
+    regs: Vec<Option<u32>>,
+    symbol_procs: Vec<(String, u32)>,
+}
+
+#[derive(Debug)]
+struct ClassImage {
+    // constant path for the classes reachable from Object, e.g. "A::B"
+    path: Option<String>,
+    name: String,
+    super_class: Option<u32>,
+    is_module: bool,
+    is_singleton: bool,
+    procs: Vec<(String, ProcImage)>,
+    consts: Vec<(String, u32)>,
+    cvars: Vec<(String, u32)>,
+    ivar: Vec<(String, u32)>,
+    included: Vec<u32>,
+    prepended: Vec<u32>,
+    singleton_class: Option<u32>,
+}
+
+#[derive(Debug)]
+struct ProcImage {
+    is_rb_func: bool,
+    is_lambda: bool,
+    sym_id: Option<String>,
+    next: Option<Box<ProcImage>>,
+    irep: Option<u32>,
+    func: Option<FuncImage>,
+    environ: Option<u32>,
+    block_self: Option<u32>,
+}
+
+#[derive(Debug)]
+enum FuncImage {
+    // defined before the program runs, by the prelude or the host
+    Index(u32),
+    Made(MadeFn),
+}
+
+#[derive(Debug)]
+struct EnvImage {
+    upper: Option<u32>,
+    captured: Option<Vec<Option<u32>>>,
+    current_regs_offset: u64,
+    is_expired: bool,
+    is_orphan: bool,
+}
+
+#[derive(Debug)]
+enum ObjectImage {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Bigint(String),
+    Symbol(String),
+    String(Vec<u8>),
+    Array(Vec<u32>),
+    Hash(Vec<(u32, u32)>),
+    Range(u32, u32, bool),
+    Class(u32),
+    Instance {
+        class: u32,
+        singleton_class: Option<u32>,
+        ivar: Vec<(String, u32)>,
+        data: Vec<u8>,
+        // the top level self, whose object_id is 0
+        is_main: bool,
+    },
+    Exception {
+        class: u32,
+        message: String,
+        ivar: Vec<(String, u32)>,
+        backtrace: Vec<String>,
+    },
+    Proc(ProcImage),
+    SharedMemory(Vec<u8>),
+    Data,
+}
+
+pub(crate) fn snapshot(vm: &VM) -> Result<Vec<u8>, Error> {
+    if vm.current_callinfo.is_some() || vm.exception.is_some() {
+        return Err(Error::InvalidSnapshot("VM is running".to_string()));
+    }
+    let image = Dumper::new(vm).dump()?;
+    let mut w = Writer::default();
+    w.buf.extend_from_slice(MAGIC);
+    w.u32(VERSION);
+    image.write(&mut w);
+    Ok(w.buf)
+}
+
+pub(crate) fn restore(vm: &mut VM, snapshot: &[u8]) -> Result<(), Error> {
+    if vm.current_callinfo.is_some() {
+        return Err(Error::InvalidSnapshot("VM is running".to_string()));
+    }
+    let mut r = Reader { buf: snapshot, pos: 0 };
+    if r.take(4)? != MAGIC {
+        return Err(Error::InvalidSnapshot("not a snapshot".to_string()));
+    }
+    let version = r.u32()?;
+    if version != VERSION {
+        return Err(Error::InvalidSnapshot(format!("unsupported version {}", version)));
+    }
+    let image = Image::read(&mut r)?;
+    let ireps = flatten_ireps(vm);
+    let code_lens: Vec<u32> = ireps.iter().map(|irep| irep.code.len() as u32).collect();
+    if code_lens != image.ireps {
+        return Err(Error::InvalidSnapshot("snapshot is made from another program".to_string()));
+    }
+    Loader::new(vm, ireps, &image).load()
+}
+
+// IREPs of the program and then of the mrblib in preorder, which is
+// stable for a Rite
+fn flatten_ireps(vm: &VM) -> Vec<Rc<IREP>> {
+    let mut ireps = Vec::new();
+    let mut stack = vec![vm.mrblib.clone(), vm.irep.clone()];
+    while let Some(irep) = stack.pop() {
+        stack.extend(irep.reps.iter().rev().cloned());
+        ireps.push(irep);
+    }
+    ireps
+}
+
+// Classes reachable by constants from Object, with their paths
+fn class_paths(vm: &VM) -> Vec<(String, Rc<RClass>)> {
+    let mut paths = vec![("Object".to_string(), vm.object_class.clone())];
+    let mut seen = HashSet::from([Rc::as_ptr(&vm.object_class)]);
+    let mut top: Vec<(String, Rc<RObject>)> = vm.consts.iter()
+        .chain(vm.object_class.consts.borrow().iter())
+        .map(|(name, obj)| (name.clone(), obj.clone()))
+        .collect();
+    top.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut queue = VecDeque::from(top);
+    while let Some((path, obj)) = queue.pop_front() {
+        let RValue::Class(class) = &obj.value else {
+            continue;
+        };
+        if !seen.insert(Rc::as_ptr(class)) {
+            continue;
+        }
+        let mut nested: Vec<(String, Rc<RObject>)> = class.consts.borrow().iter()
+            .map(|(name, obj)| (format!("{}::{}", path, name), obj.clone()))
+            .collect();
+        nested.sort_by(|a, b| a.0.cmp(&b.0));
+        queue.extend(nested);
+        paths.push((path, class.clone()));
+    }
+    paths
+}
+
+fn sorted<T: Clone>(map: &HashMap<String, T>) -> Vec<(String, T)> {
+    let mut entries: Vec<(String, T)> = map.iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+fn sorted_ivar(map: &HashMap<RSym, Rc<RObject>>) -> Vec<(String, Rc<RObject>)> {
+    let mut entries: Vec<(String, Rc<RObject>)> = map.iter()
+        .map(|(k, v)| (k.name.clone(), v.clone()))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+enum Work {
+    Class(Rc<RClass>),
+    Env(Rc<ENV>),
+    Object(Rc<RObject>),
+}
+
+struct Dumper<'a> {
+    vm: &'a VM,
+    ireps: HashMap<*const IREP, u32>,
+    paths: HashMap<*const RClass, String>,
+    classes: HashMap<*const RClass, u32>,
+    envs: HashMap<*const ENV, u32>,
+    objects: HashMap<*const RObject, u32>,
+    image: Image,
+    pending: Vec<Work>,
+}
+
+impl<'a> Dumper<'a> {
+    fn new(vm: &'a VM) -> Self {
+        let ireps = flatten_ireps(vm);
+        let image = Image {
+            ireps: ireps.iter().map(|irep| irep.code.len() as u32).collect(),
+            ..Image::default()
+        };
+        Dumper {
+            vm,
+            ireps: ireps.iter().enumerate()
+                .map(|(i, irep)| (Rc::as_ptr(irep), i as u32))
+                .collect(),
+            paths: class_paths(vm).into_iter()
+                .map(|(path, class)| (Rc::as_ptr(&class), path))
+                .collect(),
+            classes: HashMap::new(),
+            envs: HashMap::new(),
+            objects: HashMap::new(),
+            image,
+            pending: Vec::new(),
+        }
+    }
+
+    fn dump(mut self) -> Result<Image, Error> {
+        let vm = self.vm;
+        self.class(&vm.object_class);
+        self.image.consts = sorted(&vm.consts).into_iter()
+            .map(|(name, obj)| (name, self.object(&obj)))
+            .collect();
+        self.image.globals = sorted(&vm.globals).into_iter()
+            .map(|(name, obj)| (name, self.object(&obj)))
+            .collect();
+        let used = vm.regs.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+        self.image.regs = vm.regs[..used].iter()
+            .map(|reg| reg.as_ref().map(|obj| self.object(obj)))
+            .collect();
+        self.image.symbol_procs = vm.sym_proc_cache.iter()
+            .map(|(sym, proc)| (sym.name.clone(), self.object(proc)))
+            .collect();
+
+        // contents refer to each other by ids, so they are filled in
+        // after the ids are assigned
+        while let Some(work) = self.pending.pop() {
+            match work {
+                Work::Class(class) => self.dump_class(&class)?,
+                Work::Env(env) => self.dump_env(&env)?,
+                Work::Object(obj) => self.dump_object(&obj)?,
+            }
+        }
+        Ok(self.image)
+    }
+
+    // The super class is given an id before the class, so it is
+    // made first on restore
+    fn class(&mut self, class: &Rc<RClass>) -> u32 {
+        if let Some(id) = self.classes.get(&Rc::as_ptr(class)) {
+            return *id;
+        }
+        let super_class = class.super_class.as_ref().map(|c| self.class(c));
+        let id = self.image.classes.len() as u32;
+        self.classes.insert(Rc::as_ptr(class), id);
+        self.image.classes.push(ClassImage {
+            path: self.paths.get(&Rc::as_ptr(class)).cloned(),
+            name: class.sym_id.name.clone(),
+            super_class,
+            is_module: class.is_module,
+            is_singleton: class.is_singleton,
+            procs: Vec::new(),
+            consts: Vec::new(),
+            cvars: Vec::new(),
+            ivar: Vec::new(),
+            included: Vec::new(),
+            prepended: Vec::new(),
+            singleton_class: None,
+        });
+        self.pending.push(Work::Class(class.clone()));
+        id
+    }
+
+    // Likewise the upper environment comes first
+    fn env(&mut self, env: &Rc<ENV>) -> u32 {
+        if let Some(id) = self.envs.get(&Rc::as_ptr(env)) {
+            return *id;
+        }
+        let upper = env.upper.as_ref().map(|e| self.env(e));
+        let id = self.image.envs.len() as u32;
+        self.envs.insert(Rc::as_ptr(env), id);
+        self.image.envs.push(EnvImage {
+            upper,
+            captured: None,
+            current_regs_offset: env.current_regs_offset as u64,
+            is_expired: env.expired(),
+            is_orphan: env.is_orphan.get(),
+        });
+        self.pending.push(Work::Env(env.clone()));
+        id
+    }
+
+    fn object(&mut self, obj: &Rc<RObject>) -> u32 {
+        if let Some(id) = self.objects.get(&Rc::as_ptr(obj)) {
+            return *id;
+        }
+        let id = self.image.objects.len() as u32;
+        self.objects.insert(Rc::as_ptr(obj), id);
+        self.image.objects.push(ObjectImage::Nil);
+        self.pending.push(Work::Object(obj.clone()));
+        id
+    }
+
+    fn proc(&mut self, proc: &RProc) -> Result<ProcImage, Error> {
+        let irep = match &proc.irep {
+            Some(irep) => Some(*self.ireps.get(&Rc::as_ptr(irep))
+                .ok_or_else(|| Error::InvalidSnapshot("proc made outside of the program".to_string()))?),
+            None => None,
+        };
+        let next = match &proc.next {
+            Some(next) => Some(Box::new(self.proc(next)?)),
+            None => None,
+        };
+        Ok(ProcImage {
+            is_rb_func: proc.is_rb_func,
+            is_lambda: proc.is_lambda,
+            sym_id: proc.sym_id.as_ref().map(|sym| sym.name.clone()),
+            next,
+            irep,
+            func: proc.func.map(|f| match self.vm.made_fns.get(&f) {
+                Some(made) => FuncImage::Made(made.clone()),
+                None => FuncImage::Index(f as u32),
+            }),
+            environ: proc.environ.as_ref().map(|e| self.env(e)),
+            block_self: proc.block_self.as_ref().map(|obj| self.object(obj)),
+        })
+    }
+
+    fn dump_class(&mut self, class: &Rc<RClass>) -> Result<(), Error> {
+        let mut procs: Vec<(String, RProc)> = class.procs.borrow().iter()
+            .map(|(sym, proc)| (sym.name.clone(), proc.clone()))
+            .collect();
+        procs.sort_by(|a, b| a.0.cmp(&b.0));
+        let procs = procs.iter()
+            .map(|(name, proc)| Ok((name.clone(), self.proc(proc)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let consts = sorted(&class.consts.borrow()).into_iter()
+            .map(|(name, obj)| (name, self.object(&obj)))
+            .collect();
+        let cvars = sorted(&class.cvars.borrow()).into_iter()
+            .map(|(name, obj)| (name, self.object(&obj)))
+            .collect();
+        let ivar = sorted_ivar(&class.ivar.borrow()).into_iter()
+            .map(|(name, obj)| (name, self.object(&obj)))
+            .collect();
+        let included = class.included.borrow().iter().map(|c| self.class(c)).collect();
+        let prepended = class.prepended.borrow().iter().map(|c| self.class(c)).collect();
+        let singleton_class = class.singleton_class.borrow().as_ref().map(|c| self.class(c));
+
+        let image = &mut self.image.classes[self.classes[&Rc::as_ptr(class)] as usize];
+        image.procs = procs;
+        image.consts = consts;
+        image.cvars = cvars;
+        image.ivar = ivar;
+        image.included = included;
+        image.prepended = prepended;
+        image.singleton_class = singleton_class;
+        Ok(())
+    }
+
+    // An environment of a frame not returned yet refers to the
+    // register stack, which is in the snapshot as well
+    fn dump_env(&mut self, env: &Rc<ENV>) -> Result<(), Error> {
+        // the registers of a suspended fiber are not in the snapshot
+        if env.stack.is_some() && !env.expired() {
+            return Err(Error::InvalidSnapshot("fibers cannot be snapshotted".to_string()));
+        }
+        let captured = env.captured.borrow().as_ref().map(|regs| {
+            regs.iter()
+                .map(|reg| reg.as_ref().map(|obj| self.object(obj)))
+                .collect()
+        });
+        self.image.envs[self.envs[&Rc::as_ptr(env)] as usize].captured = captured;
+        Ok(())
+    }
+
+    fn dump_object(&mut self, obj: &Rc<RObject>) -> Result<(), Error> {
+        let image = match &obj.value {
+            RValue::Nil => ObjectImage::Nil,
+            RValue::Bool(b) => ObjectImage::Bool(*b),
+            RValue::Integer(n) => ObjectImage::Integer(*n),
+            RValue::Float(f) => ObjectImage::Float(*f),
+            RValue::Bigint(b) => ObjectImage::Bigint(b.to_string_radix(16)),
+            RValue::Symbol(sym) => ObjectImage::Symbol(sym.name.clone()),
+            RValue::String(s) => ObjectImage::String(s.borrow().clone()),
+            RValue::Array(a) => {
+                let items = a.borrow().clone();
+                ObjectImage::Array(items.iter().map(|item| self.object(item)).collect())
+            }
+            RValue::Hash(h) => {
+                let pairs: Vec<_> = h.borrow().values().cloned().collect();
+                ObjectImage::Hash(pairs.iter()
+                    .map(|(k, v)| (self.object(k), self.object(v)))
+                    .collect())
+            }
+            RValue::Range(start, end, exclusive) => {
+                ObjectImage::Range(self.object(start), self.object(end), *exclusive)
+            }
+            RValue::Class(class) => ObjectImage::Class(self.class(class)),
+            RValue::Instance(ins) => ObjectImage::Instance {
+                class: self.class(&ins.class),
+                singleton_class: ins.singleton_class.borrow().as_ref().map(|c| self.class(c)),
+                ivar: sorted_ivar(&ins.ivar.borrow()).into_iter()
+                    .map(|(name, obj)| (name, self.object(&obj)))
+                    .collect(),
+                data: ins.data.clone(),
+                is_main: obj.object_id.get() == 0,
+            },
+            RValue::Exception(e) => ObjectImage::Exception {
+                class: self.class(&e.class),
+                message: e.message.borrow().clone(),
+                ivar: sorted_ivar(&e.ivar.borrow()).into_iter()
+                    .map(|(name, obj)| (name, self.object(&obj)))
+                    .collect(),
+                backtrace: e.backtrace.borrow().clone(),
+            },
+            RValue::Proc(proc) => ObjectImage::Proc(self.proc(proc)?),
+            RValue::SharedMemory(sm) => ObjectImage::SharedMemory(sm.borrow().memory.to_vec()),
+            RValue::Fiber(_) => {
+                return Err(Error::InvalidSnapshot("fibers cannot be snapshotted".to_string()));
+            }
+            RValue::Data => ObjectImage::Data,
+        };
+        self.image.objects[self.objects[&Rc::as_ptr(obj)] as usize] = image;
+        Ok(())
+    }
+}
+
+struct Loader<'a> {
+    vm: &'a mut VM,
+    ireps: Vec<Rc<IREP>>,
+    image: &'a Image,
+    classes: Vec<Rc<RClass>>,
+    envs: Vec<Rc<ENV>>,
+    objects: Vec<Option<Rc<RObject>>>,
+    made_fns: HashMap<MadeFn, usize>,
+    // objects being made, to refuse a proc or range containing itself
+    making: HashSet<u32>,
+}
+
+impl<'a> Loader<'a> {
+    fn new(vm: &'a mut VM, ireps: Vec<Rc<IREP>>, image: &'a Image) -> Self {
+        Loader {
+            vm,
+            ireps,
+            image,
+            classes: Vec::new(),
+            envs: Vec::new(),
+            objects: vec![None; image.objects.len()],
+            made_fns: HashMap::new(),
+            making: HashSet::new(),
+        }
+    }
+
+    fn load(mut self) -> Result<(), Error> {
+        self.make_classes()?;
+        self.make_envs()?;
+        for id in 0..self.image.objects.len() as u32 {
+            self.object(id)?;
+        }
+        self.fill_objects()?;
+        self.fill_envs()?;
+        self.fill_classes()?;
+        self.vm.expire_method_caches();
+
+        let image = self.image;
+        self.vm.consts = image.consts.iter()
+            .map(|(name, id)| Ok((name.clone(), self.object(*id)?)))
+            .collect::<Result<_, Error>>()?;
+        self.vm.globals = image.globals.iter()
+            .map(|(name, id)| Ok((name.clone(), self.object(*id)?)))
+            .collect::<Result<_, Error>>()?;
+        let regs = image.regs.iter()
+            .map(|reg| reg.map(|id| self.object(id)).transpose())
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.vm.sym_proc_cache = image.symbol_procs.iter()
+            .map(|(name, id)| Ok((RSym::from(name.as_str()), self.object(*id)?)))
+            .collect::<Result<_, Error>>()?;
+        let vm = self.vm;
+        vm.regs = regs;
+        vm.current_regs_offset = 0;
+        vm.upper = None;
+        vm.cur_env.clear();
+        vm.has_env_ref.clear();
+        vm.target_class = vm.object_class.clone();
+        Ok(())
+    }
+
+    fn class(&self, id: u32) -> Result<Rc<RClass>, Error> {
+        self.classes.get(id as usize).cloned()
+            .ok_or_else(|| Error::InvalidSnapshot(format!("class {} not found", id)))
+    }
+
+    fn env(&self, id: u32) -> Result<Rc<ENV>, Error> {
+        self.envs.get(id as usize).cloned()
+            .ok_or_else(|| Error::InvalidSnapshot(format!("environment {} not found", id)))
+    }
+
+    // Classes of the prelude are taken over, the others are made anew
+    fn make_classes(&mut self) -> Result<(), Error> {
+        let existing: HashMap<String, Rc<RClass>> = class_paths(self.vm).into_iter().collect();
+        for image in self.image.classes.iter() {
+            let super_class = image.super_class.map(|id| self.class(id)).transpose()?;
+            let class = match image.path.as_ref().and_then(|path| existing.get(path)) {
+                Some(class) if class.is_module == image.is_module && !image.is_singleton => class.clone(),
+                _ if image.is_singleton => {
+                    let super_class = super_class
+                        .ok_or_else(|| Error::InvalidSnapshot("singleton class without a super class".to_string()))?;
+                    Rc::new(RClass::new_singleton(&image.name, super_class))
+                }
+                _ if image.is_module => Rc::new(RClass::new_module(&image.name)),
+                _ => Rc::new(RClass::new(&image.name, super_class)),
+            };
+            self.classes.push(class);
+        }
+        Ok(())
+    }
+
+    fn make_envs(&mut self) -> Result<(), Error> {
+        for image in self.image.envs.iter() {
+            let upper = image.upper.map(|id| self.env(id)).transpose()?;
+            self.envs.push(Rc::new(ENV {
+                upper,
+                callinfo: None,
+                captured: RefCell::new(None),
+                current_regs_offset: image.current_regs_offset as usize,
+                is_expired: Cell::new(image.is_expired),
+                is_orphan: Cell::new(image.is_orphan),
+                stack: None,
+            }));
+        }
+        Ok(())
+    }
+
+    fn proc(&mut self, image: &ProcImage) -> Result<RProc, Error> {
+        let irep = match image.irep {
+            Some(id) => Some(self.ireps.get(id as usize).cloned()
+                .ok_or_else(|| Error::InvalidSnapshot(format!("irep {} not found", id)))?),
+            None => None,
+        };
+        let func = match &image.func {
+            Some(FuncImage::Index(func)) if (*func as usize) < self.vm.fn_table.len() => Some(*func as usize),
+            Some(FuncImage::Index(func)) => {
+                return Err(Error::InvalidSnapshot(format!("function {} is not defined", func)));
+            }
+            Some(FuncImage::Made(made)) => Some(self.made_fn(made)),
+            None => None,
+        };
+        let next = match &image.next {
+            Some(next) => Some(Rc::new(self.proc(next)?)),
+            None => None,
+        };
+        Ok(RProc {
+            is_rb_func: image.is_rb_func,
+            is_lambda: image.is_lambda,
+            sym_id: image.sym_id.as_deref().map(RSym::from),
+            next,
+            irep,
+            func,
+            environ: image.environ.map(|id| self.env(id)).transpose()?,
+            block_self: image.block_self.map(|id| self.object(id)).transpose()?,
+        })
+    }
+
+    fn made_fn(&mut self, made: &MadeFn) -> usize {
+        if let Some(index) = self.made_fns.get(made) {
+            return *index;
+        }
+        let index = self.vm.register_made_fn(made.clone());
+        self.made_fns.insert(made.clone(), index);
+        index
+    }
+
+    // Containers are made empty and filled by fill_objects, so that
+    // they can contain each other. Procs and ranges are made complete.
+    fn object(&mut self, id: u32) -> Result<Rc<RObject>, Error> {
+        if let Some(Some(obj)) = self.objects.get(id as usize) {
+            return Ok(obj.clone());
+        }
+        let image = self.image.objects.get(id as usize)
+            .ok_or_else(|| Error::InvalidSnapshot(format!("object {} not found", id)))?;
+        if !self.making.insert(id) {
+            return Err(Error::InvalidSnapshot(format!("object {} contains itself", id)));
+        }
+        let obj = match image {
+            ObjectImage::Nil => RObject::nil().into_rc(),
+            ObjectImage::Bool(b) => RObject::boolean(*b).into_rc(),
+            ObjectImage::Integer(n) => RObject::integer(*n).into_rc(),
+            ObjectImage::Float(f) => RObject::float(*f).into_rc(),
+            ObjectImage::Bigint(digits) => {
+                let b = RBigint::from_str_radix(digits, 16)
+                    .ok_or_else(|| Error::InvalidSnapshot(format!("invalid integer {}", digits)))?;
+                RObject::bigint(b).into_rc()
+            }
+            ObjectImage::Symbol(name) => RObject::symbol(RSym::from(name.as_str())).into_rc(),
+            ObjectImage::String(s) => RObject::string_from_vec(s.clone()).into_rc(),
+            ObjectImage::Array(_) => RObject::array(Vec::new()).into_rc(),
+            ObjectImage::Hash(_) => RObject::hash(HashMap::new()).into_rc(),
+            ObjectImage::Range(start, end, exclusive) => {
+                let start = self.object(*start)?;
+                let end = self.object(*end)?;
+                RObject::range(start, end, *exclusive).to_refcount_assigned()
+            }
+            ObjectImage::Class(class) => RObject::class(self.class(*class)?).to_refcount_assigned(),
+            ObjectImage::Instance { class, singleton_class, data, is_main, .. } => {
+                let mut obj = RObject::instance(self.class(*class)?);
+                if let RValue::Instance(ins) = &mut obj.value {
+                    ins.singleton_class.replace(singleton_class.map(|id| self.class(id)).transpose()?);
+                    ins.data = data.clone();
+                }
+                if *is_main {
+                    obj.object_id.set(0);
+                }
+                obj.to_refcount_assigned()
+            }
+            ObjectImage::Exception { class, message, backtrace, .. } => {
+                let e = RException::new(self.class(*class)?, message.clone());
+                e.backtrace.replace(backtrace.clone());
+                RObject::exception(Rc::new(e)).into_rc()
+            }
+            ObjectImage::Proc(proc) => {
+                let proc = self.proc(proc)?;
+                RObject {
+                    tt: RType::Proc,
+                    value: RValue::Proc(proc),
+                    object_id: u64::MAX.into(),
+                    meter: OnceCell::new(),
+                }.to_refcount_assigned()
+            }
+            ObjectImage::SharedMemory(bytes) => {
+                let mut sm = SharedMemory::new(bytes.len());
+                sm.memory.copy_from_slice(bytes);
+                RObject {
+                    tt: RType::SharedMemory,
+                    value: RValue::SharedMemory(Rc::new(RefCell::new(sm))),
+                    object_id: u64::MAX.into(),
+                    meter: OnceCell::new(),
+                }.to_refcount_assigned()
+            }
+            ObjectImage::Data => RObject {
+                tt: RType::Data,
+                value: RValue::Data,
+                object_id: u64::MAX.into(),
+                meter: OnceCell::new(),
+            }.to_refcount_assigned(),
+        };
+        self.making.remove(&id);
+        self.objects[id as usize] = Some(obj.clone());
+        Ok(obj)
+    }
+
+    fn ivar(&mut self, ivar: &[(String, u32)]) -> Result<HashMap<RSym, Rc<RObject>>, Error> {
+        ivar.iter()
+            .map(|(name, id)| Ok((RSym::from(name.as_str()), self.object(*id)?)))
+            .collect()
+    }
+
+    fn named(&mut self, entries: &[(String, u32)]) -> Result<HashMap<String, Rc<RObject>>, Error> {
+        entries.iter()
+            .map(|(name, id)| Ok((name.clone(), self.object(*id)?)))
+            .collect()
+    }
+
+    // Hashes are filled last, as their keys are hashed by contents
+    fn fill_objects(&mut self) -> Result<(), Error> {
+        let image = self.image;
+        let mut hashes = Vec::new();
+        for (id, object) in image.objects.iter().enumerate() {
+            let obj = self.object(id as u32)?;
+            match (object, &obj.value) {
+                (ObjectImage::Array(items), RValue::Array(a)) => {
+                    let items = items.iter()
+                        .map(|id| self.object(*id))
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    obj.with_resize(|| a.replace(items));
+                }
+                (ObjectImage::Instance { ivar, .. }, RValue::Instance(ins)) => {
+                    ins.ivar.replace(self.ivar(ivar)?);
+                }
+                (ObjectImage::Exception { ivar, .. }, RValue::Exception(e)) => {
+                    e.ivar.replace(self.ivar(ivar)?);
+                }
+                (ObjectImage::Hash(pairs), _) => hashes.push((obj.clone(), pairs)),
+                _ => {}
+            }
+        }
+        for (obj, pairs) in hashes {
+            let RValue::Hash(h) = &obj.value else {
+                continue;
+            };
+            let mut map = HashMap::new();
+            for (k, v) in pairs.iter() {
+                let key = self.object(*k)?;
+                let value = self.object(*v)?;
+                map.insert(key.as_hash_key()?, (key, value));
+            }
+            obj.with_resize(|| h.replace(map));
+        }
+        Ok(())
+    }
+
+    fn fill_envs(&mut self) -> Result<(), Error> {
+        let image = self.image;
+        for (id, env) in image.envs.iter().enumerate() {
+            if let Some(captured) = &env.captured {
+                let regs = captured.iter()
+                    .map(|reg| reg.map(|id| self.object(id)).transpose())
+                    .collect::<Result<Vec<_>, Error>>()?;
+                self.envs[id].capture_no_clone(regs);
+            }
+        }
+        Ok(())
+    }
+
+    // The tables of the prelude classes are replaced as a whole
+    fn fill_classes(&mut self) -> Result<(), Error> {
+        let image = self.image;
+        for (id, class_image) in image.classes.iter().enumerate() {
+            let class = self.classes[id].clone();
+            let procs = class_image.procs.iter()
+                .map(|(name, proc)| Ok((RSym::from(name.as_str()), self.proc(proc)?)))
+                .collect::<Result<HashMap<_, _>, Error>>()?;
+            class.procs.replace(procs);
+            class.consts.replace(self.named(&class_image.consts)?);
+            class.cvars.replace(self.named(&class_image.cvars)?);
+            class.ivar.replace(self.ivar(&class_image.ivar)?);
+            let included = class_image.included.iter()
+                .map(|id| self.class(*id))
+                .collect::<Result<Vec<_>, Error>>()?;
+            class.included.replace(included);
+            let prepended = class_image.prepended.iter()
+                .map(|id| self.class(*id))
+                .collect::<Result<Vec<_>, Error>>()?;
+            class.prepended.replace(prepended);
+            class.singleton_class.replace(class_image.singleton_class.map(|id| self.class(id)).transpose()?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+
+    fn str(&mut self, v: &str) {
+        self.bytes(v.as_bytes());
+    }
+
+    fn opt<T>(&mut self, v: &Option<T>, f: impl FnOnce(&mut Self, &T)) {
+        match v {
+            Some(v) => {
+                self.bool(true);
+                f(self, v);
+            }
+            None => self.bool(false),
+        }
+    }
+
+    fn list<T>(&mut self, v: &[T], mut f: impl FnMut(&mut Self, &T)) {
+        self.u32(v.len() as u32);
+        for item in v {
+            f(self, item);
+        }
+    }
+
+    fn named(&mut self, v: &[(String, u32)]) {
+        self.list(v, |w, (name, id)| {
+            w.str(name);
+            w.u32(*id);
+        });
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(n)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| Error::InvalidSnapshot("unexpected end of snapshot".to_string()))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn str(&mut self) -> Result<String, Error> {
+        String::from_utf8(self.bytes()?)
+            .map_err(|_| Error::InvalidSnapshot("invalid string".to_string()))
+    }
+
+    fn opt<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<Option<T>, Error> {
+        if self.bool()? {
+            Ok(Some(f(self)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn list<T>(&mut self, mut f: impl FnMut(&mut Self) -> Result<T, Error>) -> Result<Vec<T>, Error> {
+        let len = self.u32()? as usize;
+        // the length is not trusted for the allocation
+        let mut items = Vec::with_capacity(len.min(self.buf.len() - self.pos));
+        for _ in 0..len {
+            items.push(f(self)?);
+        }
+        Ok(items)
+    }
+
+    fn named(&mut self) -> Result<Vec<(String, u32)>, Error> {
+        self.list(|r| Ok((r.str()?, r.u32()?)))
+    }
+}
+
+impl Image {
+    fn write(&self, w: &mut Writer) {
+        w.list(&self.ireps, |w, len| w.u32(*len));
+        w.list(&self.classes, |w, class| class.write(w));
+        w.list(&self.envs, |w, env| env.write(w));
+        w.list(&self.objects, |w, obj| obj.write(w));
+        w.named(&self.consts);
+        w.named(&self.globals);
+        w.list(&self.regs, |w, reg| w.opt(reg, |w, id| w.u32(*id)));
+        w.named(&self.symbol_procs);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, Error> {
+        Ok(Image {
+            ireps: r.list(|r| r.u32())?,
+            classes: r.list(ClassImage::read)?,
+            envs: r.list(EnvImage::read)?,
+            objects: r.list(ObjectImage::read)?,
+            consts: r.named()?,
+            globals: r.named()?,
+            regs: r.list(|r| r.opt(|r| r.u32()))?,
+            symbol_procs: r.named()?,
+        })
+    }
+}
+
+impl ClassImage {
+    fn write(&self, w: &mut Writer) {
+        w.opt(&self.path, |w, path| w.str(path));
+        w.str(&self.name);
+        w.opt(&self.super_class, |w, id| w.u32(*id));
+        w.bool(self.is_module);
+        w.bool(self.is_singleton);
+        w.list(&self.procs, |w, (name, proc)| {
+            w.str(name);
+            proc.write(w);
+        });
+        w.named(&self.consts);
+        w.named(&self.cvars);
+        w.named(&self.ivar);
+        w.list(&self.included, |w, id| w.u32(*id));
+        w.list(&self.prepended, |w, id| w.u32(*id));
+        w.opt(&self.singleton_class, |w, id| w.u32(*id));
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, Error> {
+        Ok(ClassImage {
+            path: r.opt(|r| r.str())?,
+            name: r.str()?,
+            super_class: r.opt(|r| r.u32())?,
+            is_module: r.bool()?,
+            is_singleton: r.bool()?,
+            procs: r.list(|r| Ok((r.str()?, ProcImage::read(r)?)))?,
+            consts: r.named()?,
+            cvars: r.named()?,
+            ivar: r.named()?,
+            included: r.list(|r| r.u32())?,
+            prepended: r.list(|r| r.u32())?,
+            singleton_class: r.opt(|r| r.u32())?,
+        })
+    }
+}
+
+impl ProcImage {
+    fn write(&self, w: &mut Writer) {
+        w.bool(self.is_rb_func);
+        w.bool(self.is_lambda);
+        w.opt(&self.sym_id, |w, name| w.str(name));
+        w.opt(&self.next, |w, next| next.write(w));
+        w.opt(&self.irep, |w, id| w.u32(*id));
+        w.opt(&self.func, |w, func| func.write(w));
+        w.opt(&self.environ, |w, id| w.u32(*id));
+        w.opt(&self.block_self, |w, id| w.u32(*id));
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, Error> {
+        Ok(ProcImage {
+            is_rb_func: r.bool()?,
+            is_lambda: r.bool()?,
+            sym_id: r.opt(|r| r.str())?,
+            next: r.opt(|r| Ok(Box::new(ProcImage::read(r)?)))?,
+            irep: r.opt(|r| r.u32())?,
+            func: r.opt(FuncImage::read)?,
+            environ: r.opt(|r| r.u32())?,
+            block_self: r.opt(|r| r.u32())?,
+        })
+    }
+}
+
+impl FuncImage {
+    fn write(&self, w: &mut Writer) {
+        match self {
+            FuncImage::Index(index) => {
+                w.u8(0);
+                w.u32(*index);
+            }
+            FuncImage::Made(MadeFn::AttrReader(name)) => {
+                w.u8(1);
+                w.str(name);
+            }
+            FuncImage::Made(MadeFn::AttrWriter(name)) => {
+                w.u8(2);
+                w.str(name);
+            }
+            FuncImage::Made(MadeFn::SymbolProc(name)) => {
+                w.u8(3);
+                w.str(name);
+            }
+        }
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, Error> {
+        let func = match r.u8()? {
+            0 => FuncImage::Index(r.u32()?),
+            1 => FuncImage::Made(MadeFn::AttrReader(r.str()?)),
+            2 => FuncImage::Made(MadeFn::AttrWriter(r.str()?)),
+            3 => FuncImage::Made(MadeFn::SymbolProc(r.str()?)),
+            tag => return Err(Error::InvalidSnapshot(format!("unknown function tag {}", tag))),
+        };
+        Ok(func)
+    }
+}
+
+impl EnvImage {
+    fn write(&self, w: &mut Writer) {
+        w.opt(&self.upper, |w, id| w.u32(*id));
+        w.opt(&self.captured, |w, regs| {
+            w.list(regs, |w, reg| w.opt(reg, |w, id| w.u32(*id)));
+        });
+        w.u64(self.current_regs_offset);
+        w.bool(self.is_expired);
+        w.bool(self.is_orphan);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, Error> {
+        Ok(EnvImage {
+            upper: r.opt(|r| r.u32())?,
+            captured: r.opt(|r| r.list(|r| r.opt(|r| r.u32())))?,
+            current_regs_offset: r.u64()?,
+            is_expired: r.bool()?,
+            is_orphan: r.bool()?,
+        })
+    }
+}
+
+impl ObjectImage {
+    fn write(&self, w: &mut Writer) {
+        match self {
+            ObjectImage::Nil => w.u8(0),
+            ObjectImage::Bool(b) => {
+                w.u8(1);
+                w.bool(*b);
+            }
+            ObjectImage::Integer(n) => {
+                w.u8(2);
+                w.u64(*n as u64);
+            }
+            ObjectImage::Float(f) => {
+                w.u8(3);
+                w.u64(f.to_bits());
+            }
+            ObjectImage::Bigint(digits) => {
+                w.u8(4);
+                w.str(digits);
+            }
+            ObjectImage::Symbol(name) => {
+                w.u8(5);
+                w.str(name);
+            }
+            ObjectImage::String(s) => {
+                w.u8(6);
+                w.bytes(s);
+            }
+            ObjectImage::Array(items) => {
+                w.u8(7);
+                w.list(items, |w, id| w.u32(*id));
+            }
+            ObjectImage::Hash(pairs) => {
+                w.u8(8);
+                w.list(pairs, |w, (k, v)| {
+                    w.u32(*k);
+                    w.u32(*v);
+                });
+            }
+            ObjectImage::Range(start, end, exclusive) => {
+                w.u8(9);
+                w.u32(*start);
+                w.u32(*end);
+                w.bool(*exclusive);
+            }
+            ObjectImage::Class(id) => {
+                w.u8(10);
+                w.u32(*id);
+            }
+            ObjectImage::Instance { class, singleton_class, ivar, data, is_main } => {
+                w.u8(11);
+                w.u32(*class);
+                w.opt(singleton_class, |w, id| w.u32(*id));
+                w.named(ivar);
+                w.bytes(data);
+                w.bool(*is_main);
+            }
+            ObjectImage::Exception { class, message, ivar, backtrace } => {
+                w.u8(12);
+                w.u32(*class);
+                w.str(message);
+                w.named(ivar);
+                w.list(backtrace, |w, line| w.str(line));
+            }
+            ObjectImage::Proc(proc) => {
+                w.u8(13);
+                proc.write(w);
+            }
+            ObjectImage::SharedMemory(bytes) => {
+                w.u8(14);
+                w.bytes(bytes);
+            }
+            ObjectImage::Data => w.u8(15),
+        }
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, Error> {
+        let obj = match r.u8()? {
+            0 => ObjectImage::Nil,
+            1 => ObjectImage::Bool(r.bool()?),
+            2 => ObjectImage::Integer(r.u64()? as i64),
+            3 => ObjectImage::Float(f64::from_bits(r.u64()?)),
+            4 => ObjectImage::Bigint(r.str()?),
+            5 => ObjectImage::Symbol(r.str()?),
+            6 => ObjectImage::String(r.bytes()?),
+            7 => ObjectImage::Array(r.list(|r| r.u32())?),
+            8 => ObjectImage::Hash(r.list(|r| Ok((r.u32()?, r.u32()?)))?),
+            9 => ObjectImage::Range(r.u32()?, r.u32()?, r.bool()?),
+            10 => ObjectImage::Class(r.u32()?),
+            11 => ObjectImage::Instance {
+                class: r.u32()?,
+                singleton_class: r.opt(|r| r.u32())?,
+                ivar: r.named()?,
+                data: r.bytes()?,
+                is_main: r.bool()?,
+            },
+            12 => ObjectImage::Exception {
+                class: r.u32()?,
+                message: r.str()?,
+                ivar: r.named()?,
+                backtrace: r.list(|r| r.str())?,
+            },
+            13 => ObjectImage::Proc(ProcImage::read(r)?),
+            14 => ObjectImage::SharedMemory(r.bytes()?),
+            15 => ObjectImage::Data,
+            tag => return Err(Error::InvalidSnapshot(format!("unknown object tag {}", tag))),
+        };
+        Ok(obj)
+    }
+}
@@ -0,0 +1,64 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::value::RSym;
+
+// Interns symbol names into ids, starting from 1 in the order seen.
+// RSym is created without a VM at hand, so names are interned into
+// the table of the VM entered on the thread (see VM::enter).
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    names: RefCell<Vec<String>>,
+    ids: RefCell<HashMap<String, u32>>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Rc<SymbolTable>> = RefCell::new(Rc::default());
+}
+
+impl SymbolTable {
+    pub fn intern(&self, name: &str) -> RSym {
+        if let Some(id) = self.ids.borrow().get(name) {
+            return RSym { id: *id, name: name.to_string() };
+        }
+        let mut names = self.names.borrow_mut();
+        names.push(name.to_string());
+        let id = names.len() as u32;
+        self.ids.borrow_mut().insert(name.to_string(), id);
+        RSym { id, name: name.to_string() }
+    }
+
+    pub fn name(&self, id: u32) -> Option<String> {
+        let index = (id as usize).checked_sub(1)?;
+        self.names.borrow().get(index).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // every symbol interned so far, in the order of ids
+    pub fn all(&self) -> Vec<RSym> {
+        self.names.borrow().iter().enumerate()
+            .map(|(i, name)| RSym { id: i as u32 + 1, name: name.clone() })
+            .collect()
+    }
+
+    pub(crate) fn is_current(self: &Rc<Self>) -> bool {
+        CURRENT.with(|current| Rc::ptr_eq(&current.borrow(), self))
+    }
+}
+
+// Makes the table current, returning the previous one
+pub(crate) fn set_current(table: Rc<SymbolTable>) -> Rc<SymbolTable> {
+    CURRENT.with(|current| current.replace(table))
+}
+
+pub(crate) fn intern(name: &str) -> RSym {
+    CURRENT.with(|current| current.borrow().intern(name))
+}
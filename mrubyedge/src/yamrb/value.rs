@@ -1,13 +1,19 @@
-use std::cell::Cell;
+use std::cell::{Cell, OnceCell};
 use std::collections::HashSet;
-use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+use std::mem;
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::{Rc, Weak}};
 
 use crate::Error;
 
-use super::vm::{ENV, IREP, VM};
+use super::bigint::RBigint;
+use super::gc;
+use super::memory::{self, MemoryMeter};
+use super::symbol;
+use super::vm::{CALLINFO, ENV, IREP, VM};
+use super::fiber::RFiber;
 use super::shared_memory::SharedMemory;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RType {
     Bool,
     Symbol,
@@ -21,6 +27,7 @@ pub enum RType {
     String,
     Range,
     SharedMemory,
+    Fiber,
     Data,
     Exception,
     Nil,
@@ -31,6 +38,7 @@ pub enum RValue {
     Bool(bool),
     Symbol(RSym),
     Integer(i64),
+    Bigint(RBigint),
     Float(f64),
     Class(Rc<RClass>),
     Instance(RInstance),
@@ -40,6 +48,7 @@ pub enum RValue {
     String(RefCell<Vec<u8>>),
     Range(Rc<RObject>, Rc<RObject>, bool),
     SharedMemory(Rc<RefCell<SharedMemory>>),
+    Fiber(Rc<RFiber>),
     Data,
     Exception(Rc<RException>),
     Nil,
@@ -49,8 +58,9 @@ pub enum RValue {
 pub enum ValueHasher {
     Bool(bool),
     Integer(i64),
+    Bigint(RBigint),
     Float(Vec<u8>),
-    Symbol(String),
+    Symbol(u32),
     String(Vec<u8>),
     Class(String),
 }
@@ -59,8 +69,9 @@ pub enum ValueHasher {
 pub enum ValueEquality {
     Bool(bool),
     Integer(i64),
+    Bigint(RBigint),
     Float(f64),
-    Symbol(String),
+    Symbol(u32),
     String(Vec<u8>),
     Class(String),
     Range(Box<ValueEquality>, Box<ValueEquality>, bool),
@@ -87,19 +98,74 @@ impl PartialEq for ValueEqualityForKeyValue {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct RObject {
     pub tt: RType,
     pub value: RValue,
     pub object_id: Cell<u64>,
+    // the meter the heap of the object is charged to
+    pub(crate) meter: OnceCell<Rc<MemoryMeter>>,
+}
+
+impl Clone for RObject {
+    fn clone(&self) -> Self {
+        RObject {
+            tt: self.tt,
+            value: self.value.clone(),
+            object_id: self.object_id.clone(),
+            meter: OnceCell::new(),
+        }.charged()
+    }
+}
+
+impl Drop for RObject {
+    fn drop(&mut self) {
+        if let Some(meter) = self.meter.get() {
+            meter.release(self.heap_size());
+        }
+    }
+}
+
+// Integers shared by all the registers holding them, see RObject::into_rc
+const SHARED_INTEGER_MIN: i64 = -256;
+const SHARED_INTEGER_MAX: i64 = 1024;
+
+thread_local! {
+    static NIL: Rc<RObject> = Rc::new(RObject::nil());
+    static TRUE: Rc<RObject> = Rc::new(RObject::boolean(true));
+    static FALSE: Rc<RObject> = Rc::new(RObject::boolean(false));
+    static SHARED_INTEGERS: Vec<Rc<RObject>> = (SHARED_INTEGER_MIN..=SHARED_INTEGER_MAX)
+        .map(|n| Rc::new(RObject::integer(n)))
+        .collect();
 }
 
 impl RObject {
+    // Immutable values with a fixed object id need no allocation of
+    // their own: nil, true, false and small integers are preallocated
+    // and shared, others are put in a new Rc
+    pub fn into_rc(self) -> Rc<RObject> {
+        match &self.value {
+            RValue::Nil => NIL.with(Rc::clone),
+            RValue::Bool(true) => TRUE.with(Rc::clone),
+            RValue::Bool(false) => FALSE.with(Rc::clone),
+            RValue::Integer(n) if (SHARED_INTEGER_MIN..=SHARED_INTEGER_MAX).contains(n) => {
+                let index = (n - SHARED_INTEGER_MIN) as usize;
+                SHARED_INTEGERS.with(|integers| integers[index].clone())
+            }
+            _ => {
+                let rc = Rc::new(self);
+                gc::track(&rc);
+                rc
+            }
+        }
+    }
+
     pub fn nil() -> Self {
         RObject {
             tt: RType::Nil,
             value: RValue::Nil,
             object_id: 4.into(),
+            meter: OnceCell::new(),
         }
     }
 
@@ -108,14 +174,18 @@ impl RObject {
             tt: RType::Bool,
             value: RValue::Bool(b),
             object_id: (if b { 20 } else { 0 }).into(),
+            meter: OnceCell::new(),
         }
     }
 
     pub fn symbol(sym: RSym) -> Self {
+        // not to collide with integers (odd) nor heap addresses (aligned)
+        let object_id = ((sym.id as u64) << 8) | 0x0c;
         RObject {
             tt: RType::Symbol,
             value: RValue::Symbol(sym),
-            object_id: 2.into(), // TODO: calc the same id for the same symbol
+            object_id: object_id.into(),
+            meter: OnceCell::new(),
         }
     }
 
@@ -125,13 +195,27 @@ impl RObject {
         } else if n <= (i32::MIN as i64) {
             u64::MAX
         } else {
-            n as u64 * 2 + 1
+            (n as u64).wrapping_mul(2).wrapping_add(1)
         };
 
         RObject {
             tt: RType::Integer,
             value: RValue::Integer(n),
             object_id: object_id.into(),
+            meter: OnceCell::new(),
+        }
+    }
+
+    // An Integer out of i64 range; values in range become plain integers
+    pub fn bigint(b: RBigint) -> Self {
+        if let Some(n) = b.to_i64() {
+            return Self::integer(n);
+        }
+        RObject {
+            tt: RType::Integer,
+            value: RValue::Bigint(b),
+            object_id: (u64::MAX).into(),
+            meter: OnceCell::new(),
         }
     }
 
@@ -140,6 +224,7 @@ impl RObject {
             tt: RType::Float,
             value: RValue::Float(f),
             object_id: (f.to_bits() as u64).into(),
+            meter: OnceCell::new(),
         }
     }
 
@@ -148,7 +233,8 @@ impl RObject {
             tt: RType::String,
             value: RValue::String(RefCell::new(s.into_bytes())),
             object_id: (u64::MAX).into(),
-        }
+            meter: OnceCell::new(),
+        }.charged()
     }
 
     pub fn string_from_vec(v: Vec<u8>) -> Self {
@@ -156,7 +242,8 @@ impl RObject {
             tt: RType::String,
             value: RValue::String(RefCell::new(v)),
             object_id: (u64::MAX).into(),
-        }
+            meter: OnceCell::new(),
+        }.charged()
     }
 
     pub fn array(v: Vec<Rc<RObject>>) -> Self {
@@ -164,7 +251,8 @@ impl RObject {
             tt: RType::Array,
             value: RValue::Array(RefCell::new(v)),
             object_id: (u64::MAX).into(),
-        }
+            meter: OnceCell::new(),
+        }.charged()
     }
 
     pub fn hash(h: HashMap<ValueHasher, (Rc<RObject>, Rc<RObject>)>) -> Self {
@@ -172,7 +260,8 @@ impl RObject {
             tt: RType::Hash,
             value: RValue::Hash(RefCell::new(h)),
             object_id: (u64::MAX).into(),
-        }
+            meter: OnceCell::new(),
+        }.charged()
     }
 
     pub fn range(start: Rc<RObject>, end: Rc<RObject>, exclusive: bool) -> Self {
@@ -180,6 +269,7 @@ impl RObject {
             tt: RType::Range,
             value: RValue::Range(start, end, exclusive),
             object_id: (u64::MAX).into(),
+            meter: OnceCell::new(),
         }
     }
 
@@ -188,6 +278,7 @@ impl RObject {
             tt: RType::Class,
             value: RValue::Class(c),
             object_id: (u64::MAX).into(),
+            meter: OnceCell::new(),
         }
     }
 
@@ -196,12 +287,14 @@ impl RObject {
             tt: RType::Instance,
             value: RValue::Instance(RInstance {
                 class: c,
+                singleton_class: RefCell::new(None),
                 ivar: RefCell::new(HashMap::new()),
                 data: Vec::new(),
                 ref_count: 1,
             }),
             object_id: (u64::MAX).into(),
-        }
+            meter: OnceCell::new(),
+        }.charged()
     }
 
     pub fn exception(e: Rc<RException>) -> Self {
@@ -209,15 +302,55 @@ impl RObject {
             tt: RType::Exception,
             value: RValue::Exception(e),
             object_id: (u64::MAX).into(),
+            meter: OnceCell::new(),
         }
     }
 
+    // Bytes held by the object outside of itself, accounted to the VM.
+    // Objects growing in place account the difference by memory::resize.
+    pub fn heap_size(&self) -> usize {
+        match &self.value {
+            RValue::String(s) => s.borrow().len(),
+            RValue::Array(a) => a.borrow().len() * mem::size_of::<Rc<RObject>>(),
+            RValue::Hash(h) => h.borrow().len() * mem::size_of::<(ValueHasher, (Rc<RObject>, Rc<RObject>))>(),
+            RValue::Instance(_) => mem::size_of::<RInstance>(),
+            _ => 0,
+        }
+    }
+
+    // Runs f changing the contents in place, accounting the new heap size
+    pub(crate) fn with_resize<T>(&self, f: impl FnOnce() -> T) -> T {
+        let before = self.heap_size();
+        let ret = f();
+        if let Some(meter) = self.meter() {
+            meter.resize(before, self.heap_size());
+        }
+        ret
+    }
+
+    fn charged(self) -> Self {
+        let size = self.heap_size();
+        if size > 0 && let Some(meter) = self.meter() {
+            meter.charge(size);
+        }
+        self
+    }
+
+    // The meter charged so far, or the current one from the first charge
+    fn meter(&self) -> Option<&Rc<MemoryMeter>> {
+        if self.meter.get().is_none() {
+            let _ = self.meter.set(memory::current()?);
+        }
+        self.meter.get()
+    }
+
     pub fn to_refcount_assigned(self) -> Rc<Self> {
         let rc = Rc::new(self);
         let id = Rc::as_ptr(&rc) as u64;
         if rc.object_id.get() == u64::MAX {
             rc.object_id.set(id);
         }
+        gc::track(&rc);
         rc
     }
 
@@ -236,6 +369,16 @@ impl RObject {
         !self.is_falsy()
     }
 
+    // instance variables of the objects able to hold them
+    pub fn ivars(&self) -> Option<&RefCell<HashMap<RSym, Rc<RObject>>>> {
+        match &self.value {
+            RValue::Instance(ins) => Some(&ins.ivar),
+            RValue::Exception(e) => Some(&e.ivar),
+            RValue::Class(klass) => Some(&klass.ivar),
+            _ => None,
+        }
+    }
+
     pub fn is_nil(&self) -> bool {
         match self.tt {
             RType::Nil => true,
@@ -248,8 +391,9 @@ impl RObject {
         match &self.value {
             RValue::Bool(b) => Ok(ValueHasher::Bool(*b)),
             RValue::Integer(i) => Ok(ValueHasher::Integer(*i)),
+            RValue::Bigint(b) => Ok(ValueHasher::Bigint(b.clone())),
             RValue::Float(f) => Ok(ValueHasher::Float(f.to_be_bytes().to_vec())),
-            RValue::Symbol(s) => Ok(ValueHasher::Symbol(s.name.clone())),
+            RValue::Symbol(s) => Ok(ValueHasher::Symbol(s.id)),
             RValue::String(s) => Ok(ValueHasher::String(s.borrow().clone())),
             RValue::Class(c) => Ok(ValueHasher::Class(c.sym_id.name.clone())),
             _ => {
@@ -262,8 +406,9 @@ impl RObject {
         match &self.value {
             RValue::Bool(b) => ValueEquality::Bool(*b),
             RValue::Integer(i) => ValueEquality::Integer(*i),
+            RValue::Bigint(b) => ValueEquality::Bigint(b.clone()),
             RValue::Float(f) => ValueEquality::Float(*f),
-            RValue::Symbol(s) => ValueEquality::Symbol(s.name.clone()),
+            RValue::Symbol(s) => ValueEquality::Symbol(s.id),
             RValue::String(s) => ValueEquality::String(s.borrow().clone()),
             RValue::Class(c) => ValueEquality::Class(c.sym_id.name.clone()),
             RValue::Range(s, e, ex) => {
@@ -291,10 +436,30 @@ impl RObject {
         }
     }
 
+    // get_class returns the class to start method lookup from,
+    // that is the singleton class if any
     pub fn get_class(&self, vm: &VM) -> Rc<RClass> {
         match &self.value {
-            RValue::Class(_) => vm.get_class_by_name("Class"),
-            RValue::Instance(i) => i.class.clone(),
+            RValue::Class(c) => {
+                // a class without its own singleton class shares
+                // the nearest one of its superclasses
+                let mut klass = Some(c.clone());
+                while let Some(k) = klass {
+                    if let Some(sc) = k.singleton_class.borrow().as_ref() {
+                        return sc.clone();
+                    }
+                    klass = k.super_class.clone();
+                }
+                if c.is_module {
+                    vm.get_class_by_name("Module")
+                } else {
+                    vm.get_class_by_name("Class")
+                }
+            }
+            RValue::Instance(i) => match i.singleton_class.borrow().as_ref() {
+                Some(sc) => sc.clone(),
+                None => i.class.clone(),
+            },
             RValue::Bool(b) => {
                 if *b {
                     vm.get_class_by_name("TrueClass")
@@ -303,7 +468,7 @@ impl RObject {
                 }
             },
             RValue::Symbol(_) => vm.get_class_by_name("Symbol"),
-            RValue::Integer(_) => vm.get_class_by_name("Integer"),
+            RValue::Integer(_) | RValue::Bigint(_) => vm.get_class_by_name("Integer"),
             RValue::Float(_) => vm.get_class_by_name("Float"),
             RValue::Proc(_) => vm.get_class_by_name("Proc"),
             RValue::Array(_) => vm.get_class_by_name("Array"),
@@ -311,6 +476,7 @@ impl RObject {
             RValue::String(_) => vm.get_class_by_name("String"),
             RValue::Range(_, _, _) => vm.get_class_by_name("Range"),
             RValue::SharedMemory(_) => vm.get_class_by_name("SharedMemory"),
+            RValue::Fiber(f) => f.class.clone(),
             RValue::Data => todo!("return ...? class"),
             RValue::Exception(e) => e.class.clone(),
             RValue::Nil => vm.get_class_by_name("NilClass"),
@@ -362,6 +528,7 @@ impl TryFrom<&RObject> for i64 {
     fn try_from(value: &RObject) -> Result<Self, Self::Error> {
         match value.value {
             RValue::Integer(i) => Ok(i),
+            RValue::Bigint(_) => Err(Error::RangeError("bignum too big to convert into 'long'".to_string())),
             RValue::Bool(b) => {
                 if b {
                     Ok(1)
@@ -497,12 +664,22 @@ impl TryFrom<&RObject> for *mut u8 {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct RClass {
     pub sym_id: RSym,
     pub super_class: Option<Rc<RClass>>,
-    pub procs: RefCell<HashMap<String, RProc>>,
+    pub procs: RefCell<HashMap<RSym, RProc>>,
     pub consts: RefCell<HashMap<String, Rc<RObject>>>,
+    pub is_module: bool,
+    pub is_singleton: bool,
+    // class variables, shared with the subclasses
+    pub cvars: RefCell<HashMap<String, Rc<RObject>>>,
+    // instance variables of the class object itself, e.g. @count in a class body
+    pub ivar: RefCell<HashMap<RSym, Rc<RObject>>>,
+    // modules in the order of include/prepend calls
+    pub included: RefCell<Vec<Rc<RClass>>>,
+    pub prepended: RefCell<Vec<Rc<RClass>>>,
+    pub singleton_class: RefCell<Option<Rc<RClass>>>,
 }
 
 impl RClass {
@@ -513,6 +690,27 @@ impl RClass {
             super_class,
             procs: RefCell::new(HashMap::new()),
             consts: RefCell::new(HashMap::new()),
+            is_module: false,
+            is_singleton: false,
+            cvars: RefCell::new(HashMap::new()),
+            ivar: RefCell::new(HashMap::new()),
+            included: RefCell::new(Vec::new()),
+            prepended: RefCell::new(Vec::new()),
+            singleton_class: RefCell::new(None),
+        }
+    }
+
+    pub fn new_module(name: &str) -> Self {
+        RClass {
+            is_module: true,
+            ..RClass::new(name, None)
+        }
+    }
+
+    pub fn new_singleton(name: &str, super_class: Rc<RClass>) -> Self {
+        RClass {
+            is_singleton: true,
+            ..RClass::new(name, Some(super_class))
         }
     }
 
@@ -521,19 +719,114 @@ impl RClass {
         consts.get(name).map(|v| v.clone())
     }
 
-    // find_method will search method from self to superclass
-    pub fn find_method(&self, name: &str) -> Option<RProc> {
-        let procs = self.procs.borrow();
-        match procs.get(name) {
-            Some(p) => Some(p.clone()),
-            None => {
-                match &self.super_class {
-                    Some(sc) => sc.find_method(name),
-                    None => None,
+    // ancestors returns the linearized method resolution order:
+    // prepended modules, self, included modules, then the superclass's
+    pub fn ancestors(self: &Rc<Self>) -> Vec<Rc<RClass>> {
+        let mut result: Vec<Rc<RClass>> = Vec::new();
+        let mut push = |list: Vec<Rc<RClass>>| {
+            for k in list {
+                if !result.iter().any(|r| Rc::ptr_eq(r, &k)) {
+                    result.push(k);
                 }
             }
+        };
+        for m in self.prepended.borrow().iter().rev() {
+            push(m.ancestors());
+        }
+        push(vec![self.clone()]);
+        for m in self.included.borrow().iter().rev() {
+            push(m.ancestors());
+        }
+        if let Some(sc) = &self.super_class {
+            push(sc.ancestors());
+        }
+        result
+    }
+
+    // find_ancestor walks the same order as ancestors without collecting it,
+    // stopping at the first class f returns Some for.
+    // a module reached twice is visited again, which never changes the first hit
+    pub fn find_ancestor<T>(self: &Rc<Self>, f: &mut impl FnMut(&Rc<RClass>) -> Option<T>) -> Option<T> {
+        for m in self.prepended.borrow().iter().rev() {
+            if let Some(v) = m.find_ancestor(f) {
+                return Some(v);
+            }
+        }
+        if let Some(v) = f(self) {
+            return Some(v);
+        }
+        for m in self.included.borrow().iter().rev() {
+            if let Some(v) = m.find_ancestor(f) {
+                return Some(v);
+            }
+        }
+        self.super_class.as_ref().and_then(|sc| sc.find_ancestor(f))
+    }
+
+    pub fn is_ancestor_of(self: &Rc<Self>, klass: &Rc<RClass>) -> bool {
+        klass.find_ancestor(&mut |k| Rc::ptr_eq(k, self).then_some(())).is_some()
+    }
+
+    // find_method will search method along with ancestors
+    pub fn find_method(self: &Rc<Self>, name: &str) -> Option<RProc> {
+        self.find_method_with_owner(name).map(|(_, m)| m)
+    }
+
+    pub fn find_method_with_owner(self: &Rc<Self>, name: &str) -> Option<(Rc<RClass>, RProc)> {
+        self.find_method_by_sym(&RSym::from(name))
+    }
+
+    pub fn find_method_by_sym(self: &Rc<Self>, sym: &RSym) -> Option<(Rc<RClass>, RProc)> {
+        // an undefined entry stops the walk without a method
+        self.find_ancestor(&mut |klass| {
+            klass.procs.borrow().get(sym).map(|p| (klass.clone(), p.clone()))
+        }).filter(|(_, p)| !p.is_undefined())
+    }
+
+    pub fn cvar_get(self: &Rc<Self>, name: &str) -> Option<Rc<RObject>> {
+        self.find_ancestor(&mut |klass| klass.cvars.borrow().get(name).cloned())
+    }
+
+    // assigns to the ancestor already having the variable, or self
+    pub fn cvar_set(self: &Rc<Self>, name: &str, value: Rc<RObject>) {
+        let owner = self
+            .find_ancestor(&mut |klass| {
+                klass.cvars.borrow().contains_key(name).then(|| klass.clone())
+            })
+            .unwrap_or_else(|| self.clone());
+        owner.cvars.borrow_mut().insert(name.to_string(), value);
+    }
+
+    // The callers expire the method caches, see VM::expire_method_caches
+    pub fn include_module(self: &Rc<Self>, module: Rc<RClass>) {
+        if !module.is_ancestor_of(self) {
+            self.included.borrow_mut().push(module);
         }
     }
+
+    pub fn prepend_module(self: &Rc<Self>, module: Rc<RClass>) {
+        if !self.prepended.borrow().iter().any(|m| Rc::ptr_eq(m, &module)) {
+            self.prepended.borrow_mut().push(module);
+        }
+    }
+}
+
+// The singleton class is left out, as its superclasses may lead
+// back to this class
+impl Debug for RClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RClass")
+            .field("sym_id", &self.sym_id)
+            .field("super_class", &self.super_class)
+            .field("procs", &self.procs)
+            .field("consts", &self.consts)
+            .field("is_module", &self.is_module)
+            .field("is_singleton", &self.is_singleton)
+            .field("cvars", &self.cvars)
+            .field("included", &self.included)
+            .field("prepended", &self.prepended)
+            .finish_non_exhaustive()
+    }
 }
 
 impl From<Rc<RClass>> for RObject {
@@ -545,7 +838,8 @@ impl From<Rc<RClass>> for RObject {
 #[derive(Debug, Clone)]
 pub struct RInstance {
     pub class: Rc<RClass>,
-    pub ivar: RefCell<HashMap<String, Rc<RObject>>>,
+    pub singleton_class: RefCell<Option<Rc<RClass>>>,
+    pub ivar: RefCell<HashMap<RSym, Rc<RObject>>>,
     pub data: Vec<u8>,
     pub ref_count: usize,
 }
@@ -553,6 +847,9 @@ pub struct RInstance {
 #[derive(Debug, Clone)]
 pub struct RProc {
     pub is_rb_func: bool,
+    // lambdas and methods check the number of args and return from
+    // themselves on `break`, unlike blocks
+    pub is_lambda: bool,
     pub sym_id: Option<RSym>,
     pub next: Option<Rc<RProc>>,
     pub irep: Option<Rc<IREP>>,
@@ -561,26 +858,72 @@ pub struct RProc {
     pub block_self: Option<Rc<RObject>>,
 }
 
+impl RProc {
+    // a method entry made by `undef`, which stops the method lookup
+    pub fn undefined(name: &str) -> Self {
+        RProc {
+            is_rb_func: false,
+            is_lambda: true,
+            sym_id: Some(RSym::new(name.to_string())),
+            next: None,
+            irep: None,
+            func: None,
+            environ: None,
+            block_self: None,
+        }
+    }
+
+    pub fn is_undefined(&self) -> bool {
+        !self.is_rb_func && self.func.is_none()
+    }
+
+    // a method implemented by the function at the index of VM::fn_table
+    pub fn cfunc(name: &str, func: usize) -> Self {
+        RProc {
+            is_rb_func: false,
+            is_lambda: true,
+            sym_id: Some(RSym::new(name.to_string())),
+            next: None,
+            irep: None,
+            func: Some(func),
+            environ: None,
+            block_self: None,
+        }
+    }
+}
+
 pub type RFn = Box<dyn Fn(&mut VM, &[Rc<RObject>]) -> Result<Rc<RObject>, Error>>;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// Symbols compare and hash by the id interned in the symbol table
+#[derive(Debug, Clone)]
 pub struct RSym {
+    pub id: u32,
     pub name: String
 }
 
 impl RSym {
     pub fn new(name: String) -> Self {
-        Self {
-            name
-        }
+        symbol::intern(&name)
     }
 }
 
-impl From<&'static str> for RSym {
-    fn from(value: &'static str) -> Self {
-        Self {
-            name: value.to_string(),
-        }
+impl PartialEq for RSym {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for RSym {}
+
+impl std::hash::Hash for RSym {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl From<&str> for RSym {
+    fn from(value: &str) -> Self {
+        symbol::intern(value)
     }
 }
 
@@ -588,6 +931,10 @@ impl From<&'static str> for RSym {
 pub enum RPool {
     Str(String),
     Data(Vec<u8>),
+    Int(i64),
+    Float(f64),
+    // digits in the base, as compiled from a literal too large for Integer
+    BigInt { base: u32, negative: bool, digits: String },
 }
 
 impl RPool {
@@ -599,49 +946,120 @@ impl RPool {
     }
 }
 
+// Pending non-local exit raised by BREAK or RETURN_BLK.
+// Frames are unwound until `target` becomes the current frame.
+#[derive(Debug, Clone)]
+pub struct RBreak {
+    pub target: Option<Rc<CALLINFO>>,
+    // set for `return` in a block: the method frame to return from
+    pub home: Option<Rc<CALLINFO>>,
+    // set for JMPUW: the op index to jump to in the target frame
+    pub jump: Option<usize>,
+    pub value: Rc<RObject>,
+}
+
 #[derive(Debug)]
 pub struct RException {
     pub class: Rc<RClass>,
-    pub error_type: RefCell<Error>,
-    pub message: String,
-    pub backtrace: Vec<String>, // TODO
+    // the Rust error this exception is made from; None if made in Ruby
+    pub error_type: RefCell<Option<Error>>,
+    pub message: RefCell<String>,
+    pub ivar: RefCell<HashMap<RSym, Rc<RObject>>>,
+    pub backtrace: RefCell<Vec<String>>,
+    // set while an ensure clause runs for a break, return or JMPUW
+    pub break_object: Option<Rc<RBreak>>,
+    // the object raised by Kernel#raise, bound again by `rescue => e`
+    pub object: RefCell<Weak<RObject>>,
 }
 
+// exceptions are compared by identity
+impl PartialEq for RException {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for RException {}
+
 impl RClass {
     pub fn from_error(vm: &mut VM, e: &Error) -> Rc<Self> {
         match e {
-            Error::General => {
-                return vm.get_class_by_name("Exception");
-            }
-            Error::Internal(_) => {
-                return vm.get_class_by_name("InternalError");
-            }
-            Error::InvalidOpCode => {
-                return vm.get_class_by_name("LoadError");
-            }
-            Error::RuntimeError(_) => {
-                return vm.get_class_by_name("RuntimeError");
-            }
-            Error::TypeMismatch => {
-                return vm.get_class_by_name("LoadError");
-            }
-            Error::NoMethodError(_) => {
-                return vm.get_class_by_name("NoMethodError");
-            }
-            Error::NameError(_) => {
-                return vm.get_class_by_name("NameError");
-            }
+            Error::General => vm.get_class_by_name("Exception"),
+            Error::Internal(_) | Error::OutOfFuel => vm.get_class_by_name("InternalError"),
+            Error::InvalidOpCode | Error::InvalidSnapshot(_) => vm.get_class_by_name("LoadError"),
+            Error::RuntimeError(_) => vm.get_class_by_name("RuntimeError"),
+            Error::TypeMismatch => vm.get_class_by_name("LoadError"),
+            Error::TypeError(_) => vm.get_class_by_name("TypeError"),
+            Error::NoMethodError(_) => vm.get_class_by_name("NoMethodError"),
+            Error::NameError(_) => vm.get_class_by_name("NameError"),
+            Error::ArgumentError(_) => vm.get_class_by_name("ArgumentError"),
+            Error::RangeError(_) => vm.get_class_by_name("RangeError"),
+            Error::NoMemoryError(_) => vm.get_class_by_name("NoMemoryError"),
+            Error::SystemStackError(_) => vm.get_class_by_name("SystemStackError"),
+            Error::ZeroDivisionError(_) => vm.get_class_by_name("ZeroDivisionError"),
+            Error::LocalJumpError(_) | Error::Break => vm.get_class_by_name("LocalJumpError"),
+            Error::FiberError(_) => vm.get_class_by_name("FiberError"),
+            Error::Exception(e) => e.class.clone(),
         }
     }
 }
 
 impl RException {
+    pub fn new(class: Rc<RClass>, message: String) -> Self {
+        RException {
+            class,
+            error_type: RefCell::new(None),
+            message: RefCell::new(message),
+            ivar: RefCell::new(HashMap::new()),
+            backtrace: RefCell::new(Vec::new()),
+            break_object: None,
+            object: RefCell::new(Weak::new()),
+        }
+    }
+
     pub fn from_error(vm: &mut VM, e: &Error) -> Self {
         RException {
-            class: RClass::from_error(vm, e),
-            error_type: RefCell::new(e.clone()),
-            message: e.message(),
-            backtrace: Vec::new(),
+            error_type: RefCell::new(Some(e.clone())),
+            ..RException::new(RClass::from_error(vm, e), e.message())
+        }
+    }
+
+    // The exception object for a raised error: a raised Ruby exception is
+    // the object itself
+    pub fn wrap(vm: &mut VM, e: &Error) -> Rc<Self> {
+        match e {
+            Error::Exception(exc) => exc.clone(),
+            e => Rc::new(RException::from_error(vm, e)),
+        }
+    }
+
+    // The error to raise this exception again, keeping its backtrace
+    pub fn to_error(self: &Rc<Self>) -> Error {
+        if self.break_object.is_some() {
+            return Error::Break;
+        }
+        Error::Exception(self.clone())
+    }
+
+    pub fn is_a(&self, klass: &Rc<RClass>) -> bool {
+        klass.is_ancestor_of(&self.class)
+    }
+
+    // The object holding this exception: the raised one while it is alive,
+    // or a new one made once and kept for the later rescues
+    pub fn to_object(self: &Rc<Self>) -> Rc<RObject> {
+        if let Some(obj) = self.object.borrow().upgrade() {
+            return obj;
+        }
+        let obj = RObject::exception(self.clone()).to_refcount_assigned();
+        self.object.replace(Rc::downgrade(&obj));
+        obj
+    }
+
+    pub fn from_break(vm: &mut VM, brk: Rc<RBreak>) -> Self {
+        RException {
+            break_object: Some(brk),
+            ..RException::from_error(vm, &Error::Break)
         }
     }
 }
\ No newline at end of file
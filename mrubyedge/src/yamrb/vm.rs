@@ -1,35 +1,110 @@
 use std::cell::{Cell, RefCell};
 use std::env;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::collections::HashMap;
 
-use crate::rite::{insn, Irep, Rite};
+use crate::rite::{insn, DebugInfo, Irep, PoolValue, Rite};
 use crate::Error;
 
+use super::fiber::RFiber;
+use super::gc::{self, CycleCollector};
+use super::memory::{self, MemoryMeter};
+use super::method_cache::MethodCache;
+use super::snapshot::{self, MadeFn};
+use super::symbol::{self, SymbolTable};
 use super::{op, optable::*};
-use super::prelude::prelude;
+use super::prelude::{mrblib, prelude};
 use super::value::*;
 use super::op::Op;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 pub const ENGINE: &'static str = "mruby/edge";
 
-const MAX_REGS_SIZE: usize = 256;
+// Nesting of method and block calls allowed by default; see VM::set_max_call_depth
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10000;
+// Nesting of blocks called from Rust methods allowed by default, each of
+// which runs on the native stack; see VM::set_max_native_depth
+pub const DEFAULT_MAX_NATIVE_DEPTH: usize = 64;
+// Instructions a step may run past its budget by default, in blocks
+// called from Rust methods; see VM::set_max_step_overrun
+pub const DEFAULT_MAX_STEP_OVERRUN: u64 = 100_000;
+
+// Where VM::step stopped
+#[derive(Debug, Clone)]
+pub enum StepResult {
+    // ran out of the steps; call VM::step again to resume
+    Yielded,
+    Finished(Rc<RObject>),
+    Raised(Error),
+}
+
+// Enters the previous VM again when dropped, see VM::enter
+pub(crate) struct Entered {
+    // None when the VM was entered already
+    symbols: Option<Rc<SymbolTable>>,
+    memory: Option<Rc<MemoryMeter>>,
+    gc: Option<Rc<CycleCollector>>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        if let Some(symbols) = self.symbols.take() {
+            symbol::set_current(symbols);
+            memory::set_current(self.memory.take());
+            gc::set_current(self.gc.take());
+        }
+    }
+}
 
 pub struct VM {
     pub irep: Rc<IREP>,
-    
+    // the methods of the prelude written in Ruby
+    pub(crate) mrblib: Rc<IREP>,
+
     pub id: usize,
     pub bytecode: Vec<u8>,
     pub current_irep: Rc<IREP>,
     pub pc: Cell<usize>,
-    pub regs: [Option<Rc<RObject>>; MAX_REGS_SIZE],
+    // register stack, grown on demand as frames are pushed
+    pub regs: Vec<Option<Rc<RObject>>>,
     pub current_regs_offset: usize,
     pub current_callinfo: Option<Rc<CALLINFO>>,
     pub target_class: Rc<RClass>,
     pub exception: Option<Rc<RException>>,
+    // the exception which ended the last run from the host, with its
+    // backtrace; see VM::host_error
+    pub last_exception: Option<Rc<RException>>,
+    pub break_object: Option<Rc<RBreak>>,
 
     pub flag_preemption: Cell<bool>,
+    // instructions left to execute; None for no limit
+    pub fuel: Option<u64>,
+    // instructions left before VM::step yields; None when not stepping
+    pub(crate) steps_left: Option<u64>,
+    // instructions run past the steps in nested runs, which cannot yield
+    pub(crate) step_overrun: u64,
+    // the result VM::step finished or raised with, returned again by later steps
+    pub(crate) step_result: Option<StepResult>,
+    // nesting of VM::run, 1 in the outermost one
+    pub(crate) run_depth: usize,
+    // fibers being resumed, the running one last
+    pub(crate) fibers: Vec<Rc<RFiber>>,
+    // value passed to Fiber.yield, until the fiber leaves its run
+    pub(crate) fiber_yield: Option<Rc<RObject>>,
+    // whether the args of the Rust method being called end with a
+    // keyword hash, before the block slot
+    pub(crate) kargs_given: bool,
+    pub memory: Rc<MemoryMeter>,
+    pub gc: Rc<CycleCollector>,
+    pub symbols: Rc<SymbolTable>,
+    // procs made by Symbol#to_proc
+    pub sym_proc_cache: HashMap<RSym, Rc<RObject>>,
+    // bumped whenever a method table or an ancestor chain changes,
+    // which expires every inline method cache of the VM at once
+    pub method_state: u64,
+    pub max_call_depth: usize,
+    pub max_native_depth: usize,
+    pub max_step_overrun: u64,
 
     // common class
     pub object_class: Rc<RClass>,
@@ -44,6 +119,8 @@ pub struct VM {
     pub has_env_ref: HashMap<usize, bool>,
 
     pub fn_table: Vec<Rc<RFn>>,
+    // functions made by the program, by their index in fn_table
+    pub(crate) made_fns: HashMap<usize, MadeFn>,
 }
 
 impl VM {
@@ -65,37 +142,65 @@ impl VM {
             syms: Vec::new(),
             pool: Vec::new(),
             reps: Vec::new(),
-            catch_target_pos: Vec::new(),
+            catch_handlers: Vec::new(),
+            debug_info: None,
+            lv: Vec::new(),
+            method_cache: MethodCache::default(),
         };
         Self::new_by_raw_irep(irep)
     }
 
-    pub fn new_by_raw_irep(irep: IREP) -> VM {
+    pub fn new_by_raw_irep(mut irep: IREP) -> VM {
+        // the new VM is entered while it is built, see VM::enter
+        let symbols = Rc::new(SymbolTable::default());
+        let memory = Rc::new(MemoryMeter::default());
+        let gc = Rc::new(CycleCollector::default());
+        let entered = Entered {
+            symbols: Some(symbol::set_current(symbols.clone())),
+            memory: memory::set_current(Some(memory.clone())),
+            gc: gc::set_current(Some(gc.clone())),
+        };
+        reintern_irep(&mut irep);
         let irep = Rc::new(irep);
+        let mut mrblib = rite_to_irep(&mut mrblib::load());
+        reintern_irep(&mut mrblib);
+        shift_irep_ids(&mut mrblib, max_irep_id(&irep) + 1);
+        let mrblib = Rc::new(mrblib);
         let globals = HashMap::new();
         let consts = HashMap::new();
         let builtin_class_table = HashMap::new();
 
         let object_class = Rc::new(
-            RClass {
-                sym_id: "Object".into(),
-                super_class: None,
-                procs: RefCell::new(HashMap::new()),
-                consts: RefCell::new(HashMap::new()),
-            }
+            RClass::new("Object", None)
         );
 
         let id = 1; // TODO generator
         let bytecode = Vec::new();
         let current_irep = irep.clone();
         let pc = Cell::new(0);
-        let regs: [Option<Rc<RObject>>; MAX_REGS_SIZE] = [const { None }; MAX_REGS_SIZE];
+        let regs = Vec::new();
         let current_regs_offset = 0;
         let current_callinfo = None;
         let target_class = object_class.clone();
         let exception = None;
+        let last_exception = None;
+        let break_object = None;
         let flag_preemption = Cell::new(false);
+        let fuel = None;
+        let steps_left = None;
+        let step_overrun = 0;
+        let step_result = None;
+        let run_depth = 0;
+        let fibers = Vec::new();
+        let fiber_yield = None;
+        let kargs_given = false;
+        let sym_proc_cache = HashMap::new();
+        let method_state = 0;
+        let max_call_depth = DEFAULT_MAX_CALL_DEPTH;
+        let max_native_depth = DEFAULT_MAX_NATIVE_DEPTH;
+        let max_step_overrun = DEFAULT_MAX_STEP_OVERRUN;
         let fn_table = Vec::new();
+        let made_fns = HashMap::new();
         let upper = None;
         let cur_env = HashMap::new();
         let has_env_ref = HashMap::new();
@@ -104,6 +209,7 @@ impl VM {
             id,
             bytecode,
             irep,
+            mrblib,
             current_irep,
             pc,
             regs,
@@ -111,7 +217,25 @@ impl VM {
             current_callinfo,
             target_class,
             exception,
+            last_exception,
+            break_object,
             flag_preemption,
+            fuel,
+            steps_left,
+            step_overrun,
+            step_result,
+            run_depth,
+            fibers,
+            fiber_yield,
+            kargs_given,
+            memory,
+            gc,
+            symbols,
+            sym_proc_cache,
+            method_state,
+            max_call_depth,
+            max_native_depth,
+            max_step_overrun,
             object_class,
             builtin_class_table,
             globals,
@@ -119,42 +243,90 @@ impl VM {
             upper,
             cur_env,
             has_env_ref,
-            fn_table
+            fn_table,
+            made_fns,
         };
 
         prelude(&mut vm);
-        
+        drop(entered);
+
         vm
     }
 
     pub fn run(&mut self) -> Result<Rc<RObject>, Box<dyn std::error::Error>> {
+        let _entered = self.enter();
+        self.run_depth += 1;
+        let res = self.run_loop();
+        self.run_depth -= 1;
+        let res = match res {
+            Err(e) if self.run_depth == 0 => match e.downcast::<Error>() {
+                Ok(e) => Err(self.host_error(*e).into()),
+                Err(e) => Err(e),
+            },
+            res => res,
+        };
+        // only the outermost run of VM::step yields
+        res.map(|ret| ret.unwrap_or_else(|| RObject::nil().into_rc()))
+    }
+
+    // Runs the program for n instructions, which can be resumed by
+    // calling step again. Blocks called from Rust methods, such as the
+    // one of Hash#each or the initialize called by new, cannot be
+    // suspended and keep running past n, by at most max_step_overrun
+    // instructions before the step raises Error::OutOfFuel.
+    pub fn step(&mut self, n: u64) -> StepResult {
+        if let Some(res) = &self.step_result {
+            return res.clone();
+        }
+        let _entered = self.enter();
+        self.steps_left = Some(n);
+        self.step_overrun = 0;
+        self.run_depth += 1;
+        let res = self.run_loop();
+        self.run_depth -= 1;
+        self.steps_left = None;
+        let res = match res {
+            Ok(Some(ret)) => StepResult::Finished(ret),
+            Ok(None) => return StepResult::Yielded,
+            Err(e) => match e.downcast::<Error>() {
+                Ok(e) => StepResult::Raised(self.host_error(*e)),
+                Err(e) => StepResult::Raised(Error::RuntimeError(e.to_string())),
+            },
+        };
+        self.step_result = Some(res.clone());
+        res
+    }
+
+    // Returns None when VM::step or Fiber.yield yields
+    pub(crate) fn run_loop(&mut self) -> Result<Option<Rc<RObject>>, Box<dyn std::error::Error>> {
         let class = self.object_class.clone();
         // Insert top_self
-        let top_self = RObject{
-            tt: RType::Instance,
-            value: RValue::Instance(RInstance {
-                class,
-                ivar: RefCell::new(HashMap::new()),
-                data: Vec::new(),
-                ref_count: 1,
-            }),
-            object_id: 0.into(),
-        }.to_refcount_assigned();
+        let top_self = RObject::instance(class);
+        top_self.object_id.set(0);
+        let top_self = top_self.to_refcount_assigned();
         if self.current_regs()[0].is_none() {
             self.current_regs()[0].replace(top_self.clone());
         }
         let mut rescued = false;
 
         loop {
+            // yield between instructions, not right after rescuing
+            if self.steps_left == Some(0) && self.run_depth == 1 && !rescued {
+                return Ok(None);
+            }
+            if self.fiber_yield.is_some() && self.exception.is_none() {
+                return Ok(None);
+            }
             if ! rescued {
                 if let Some(_e) = self.exception.clone() {
                     let operand = insn::Fetched::B(0);
-                    if let Some(pos) = self.find_next_handler_pos() {
-                        self.pc.set(pos);
+                    if let Some(handler) = self.find_catch_handler() {
+                        self.pc.set(handler.target);
                         rescued = true;
                         continue;
                     }
 
+                    // no handler in this frame: pop it and look up the caller
                     match op_return(self, &operand) {
                         Ok(_) => {},
                         Err(_) => {
@@ -180,16 +352,59 @@ impl VM {
                 || Error::internal("end of opcode reached")
             )?;
             let operand = op.operand;
+            if let Some(fuel) = self.fuel {
+                if fuel == 0 {
+                    unwind_run(self, &Error::OutOfFuel);
+                    return Err(Error::OutOfFuel.into());
+                }
+                self.fuel = Some(fuel - 1);
+            }
+            if let Some(steps) = self.steps_left {
+                if steps == 0 && self.run_depth > 1 {
+                    // a nested run cannot yield
+                    if self.step_overrun >= self.max_step_overrun {
+                        unwind_run(self, &Error::OutOfFuel);
+                        return Err(Error::OutOfFuel.into());
+                    }
+                    self.step_overrun += 1;
+                }
+                self.steps_left = Some(steps.saturating_sub(1));
+            }
             self.pc.set(pc + 1);
 
             if env::var("MRUBYEDGE_DEBUG").is_ok() {
                 eprintln!("{:?}: {:?} (pos={} len={})", &op.code, &op.operand, op.pos, op.len);
             }
             match consume_expr(self, op.code, &operand, op.pos, op.len) {
-                Ok(_) => {},
+                Ok(_) => {
+                    if self.memory.take_exceeded() {
+                        self.raise(&Error::NoMemoryError("out of memory".to_string()));
+                        continue;
+                    }
+                },
+                Err(Error::OutOfFuel) => {
+                    // ran out in a nested run; leave this one as well
+                    unwind_run(self, &Error::OutOfFuel);
+                    return Err(Error::OutOfFuel.into());
+                }
+                Err(Error::Break) => {
+                    match unwind_break(self) {
+                        Ok(_) => {
+                            // entering an ensure clause, not raising
+                            rescued = self.exception.is_some();
+                        },
+                        Err(Error::Break) => {
+                            // propagate to the Rust caller of this run
+                            return Err(Error::Break.into());
+                        }
+                        Err(e) => {
+                            self.raise(&e);
+                            continue;
+                        }
+                    }
+                }
                 Err(e) => {
-                    let exception = RException::from_error(self, &e);
-                    self.exception = Some(Rc::new(exception));
+                    self.raise(&e);
                     continue;
                 }
             }
@@ -202,38 +417,220 @@ impl VM {
         self.flag_preemption.set(false);
 
         if let Some(e) = self.exception.clone() {
-            return Err(e.error_type.borrow().clone().into());
+            return Err(e.to_error().into());
         }
 
         let retval = match self.current_regs()[0].take() {
-            Some(v) => Ok(v),
-            None => Ok(Rc::new(RObject::nil()))
+            Some(v) => Ok(Some(v)),
+            None => Ok(Some(RObject::nil().into_rc()))
         };
         self.current_regs()[0].replace(top_self.clone());
 
         retval
     }
 
-    pub(crate) fn find_next_handler_pos(&mut self) -> Option<usize> {
-        let ci = self.pc.get();
-        for p in self.current_irep.catch_target_pos.iter() {
-            if ci < *p {
-                return Some(*p);
+    // Makes the symbol table, memory meter and cycle collector of the VM
+    // current on the thread until the returned guard is dropped. Symbols
+    // and objects are made without a VM at hand and use the current ones,
+    // so every entry point from the host enters its VM first, which lets
+    // several VMs run on a thread.
+    pub(crate) fn enter(&self) -> Entered {
+        if self.symbols.is_current() {
+            return Entered { symbols: None, memory: None, gc: None };
+        }
+        Entered {
+            symbols: Some(symbol::set_current(self.symbols.clone())),
+            memory: memory::set_current(Some(self.memory.clone())),
+            gc: gc::set_current(Some(self.gc.clone())),
+        }
+    }
+
+    // Interns the name into the symbols of this VM. Symbols the host
+    // passes to the VM, e.g. by RObject::symbol, should be made by it.
+    pub fn intern(&self, name: &str) -> RSym {
+        self.symbols.intern(name)
+    }
+
+    // Limits the number of instructions executed from now on, through
+    // all the nested runs. Running out fails the call with Error::OutOfFuel.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    // Limits the bytes held by strings, arrays, hashes, instances and
+    // shared memories. Allocating over it raises NoMemoryError.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory.set_limit(limit);
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.memory.used()
+    }
+
+    // Frees the objects only held by reference cycles, returning how
+    // many were freed. Objects held by the host are kept alive.
+    pub fn gc_start(&mut self) -> usize {
+        let _entered = self.enter();
+        self.gc.collect()
+    }
+
+    pub fn gc_count(&self) -> usize {
+        self.gc.count()
+    }
+
+    // Serializes the state of the VM after a run: classes and their
+    // methods, consts, globals, ivars and every reachable object.
+    // The code is not included; see VM::restore.
+    pub fn snapshot(&self) -> Result<Vec<u8>, Error> {
+        snapshot::snapshot(self)
+    }
+
+    // Takes over the state in a snapshot, instead of running the top
+    // level again. The VM must be opened from the same Rite, with the
+    // same Rust methods defined in the same order as the snapshotted one.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), Error> {
+        let _entered = self.enter();
+        snapshot::restore(self, snapshot)
+    }
+
+    // Fails before allocating the bytes would go over the memory limit
+    pub(crate) fn check_memory(&self, bytes: usize) -> Result<(), Error> {
+        if self.memory.can_allocate(bytes) {
+            Ok(())
+        } else {
+            Err(Error::NoMemoryError("out of memory".to_string()))
+        }
+    }
+
+    // Expires the inline method caches, which must be done after
+    // changing a method table or an ancestor chain
+    pub fn expire_method_caches(&mut self) {
+        self.method_state += 1;
+    }
+
+    // Limits how deep method and block calls can nest. Calling deeper
+    // raises SystemStackError.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    // Limits how deep blocks called from Rust methods, such as the one
+    // of Hash#each, can nest. Calling deeper raises SystemStackError.
+    pub fn set_max_native_depth(&mut self, depth: usize) {
+        self.max_native_depth = depth;
+    }
+
+    // Limits how many instructions VM::step may run past its budget in
+    // blocks called from Rust methods, which cannot be suspended
+    pub fn set_max_step_overrun(&mut self, n: u64) {
+        self.max_step_overrun = n;
+    }
+
+    // Fails with SystemStackError before running the VM nested once more
+    pub(crate) fn check_native_depth(&self) -> Result<(), Error> {
+        if self.run_depth >= self.max_native_depth {
+            return Err(Error::SystemStackError("stack level too deep".to_string()));
+        }
+        Ok(())
+    }
+
+    // An error leaving the outermost run goes back to the host. An error
+    // with a recorded backtrace stays Error::Exception to carry it, and
+    // Error::kind gives the variant the VM raised it as; the others go
+    // back as their own variants. The exception is kept in
+    // VM::last_exception, and not raised again by the next run.
+    fn host_error(&mut self, e: Error) -> Error {
+        self.last_exception = self.exception.take();
+        match e {
+            Error::Exception(exc) if exc.backtrace.borrow().is_empty() => {
+                let error_type = exc.error_type.borrow().clone();
+                error_type.unwrap_or(Error::Exception(exc))
             }
+            e => e,
         }
-        None
+    }
+
+    // Sets the exception for the error, recording where it is raised
+    pub(crate) fn raise(&mut self, e: &Error) {
+        let exception = RException::wrap(self, e);
+        if exception.backtrace.borrow().is_empty() {
+            let backtrace = self.backtrace();
+            exception.backtrace.replace(backtrace);
+        }
+        self.exception = Some(exception);
+    }
+
+    // "file:line:in 'method'" of each frame from the innermost one.
+    // Frames without debug info are skipped.
+    pub fn backtrace(&self) -> Vec<String> {
+        fn method_name(ci: &Option<Rc<CALLINFO>>) -> String {
+            match ci {
+                Some(ci) => ci.method_id.name.clone(),
+                None => "<main>".to_string(),
+            }
+        }
+        fn location(irep: &IREP, pc: usize) -> Option<String> {
+            let op = irep.code.get(pc.checked_sub(1)?)?;
+            let (file, line) = irep.debug_info.as_ref()?.lookup(op.pos)?;
+            Some(format!("{}:{}", file, line))
+        }
+
+        let mut backtrace = Vec::new();
+        if let Some(loc) = location(&self.current_irep, self.pc.get()) {
+            backtrace.push(format!("{}:in '{}'", loc, method_name(&self.current_callinfo)));
+        }
+        let mut ci = self.current_callinfo.clone();
+        while let Some(c) = ci {
+            if c.host_entry {
+                break;
+            }
+            if let Some(loc) = location(&c.pc_irep, c.pc) {
+                backtrace.push(format!("{}:in '{}'", loc, method_name(&c.prev)));
+            }
+            ci = c.prev.clone();
+        }
+        backtrace
+    }
+
+    // The innermost rescue or ensure handler covering the op being executed
+    pub(crate) fn find_catch_handler(&self) -> Option<CatchHandler> {
+        let pc = self.pc.get().checked_sub(1)?;
+        self.current_irep.catch_handlers.iter().rev()
+            .find(|h| h.covers(pc))
+            .cloned()
     }
 
     pub(crate) fn current_regs(&mut self) -> &mut [Option<Rc<RObject>>] {
+        // at least self, even for an empty irep
+        self.ensure_regs(self.current_irep.nregs.max(1));
         &mut self.regs[self.current_regs_offset..]
     }
 
+    // Makes room for n registers from the current offset
+    pub(crate) fn ensure_regs(&mut self, n: usize) {
+        let size = self.current_regs_offset + n;
+        if self.regs.len() < size {
+            self.regs.resize(size, None);
+        }
+    }
+
     pub(crate) fn get_current_regs_cloned(&mut self, i: usize) -> Result<Rc<RObject>, Error> {
-        self.current_regs()[i].clone().ok_or_else(|| Error::internal(format!("register {} is not assigned", i)))   
+        self.current_regs()[i].clone().ok_or_else(|| self.unassigned_register(i))
     }
 
     pub(crate) fn take_current_regs(&mut self, i: usize) -> Result<Rc<RObject>, Error> {
-        self.current_regs()[i].take().ok_or_else(|| Error::internal(format!("register {} is not assigned", i)))   
+        self.current_regs()[i].take().ok_or_else(|| self.unassigned_register(i))
+    }
+
+    fn unassigned_register(&self, i: usize) -> Error {
+        match self.current_irep.local_variable_name(i) {
+            Some(name) => Error::internal(format!("local variable {} (register {}) is not assigned", name, i)),
+            None => Error::internal(format!("register {} is not assigned", i)),
+        }
     }
 
     pub fn getself(&mut self) -> Result<Rc<RObject>, Error> {
@@ -249,6 +646,14 @@ impl VM {
         return self.fn_table.len() - 1;
     }
     
+    // Registers a function made by the program, which a snapshot
+    // records how to make again
+    pub(crate) fn register_made_fn(&mut self, made: MadeFn) -> usize {
+        let index = self.register_fn(made.make());
+        self.made_fns.insert(index, made);
+        index
+    }
+
     pub(crate) fn get_fn(&self, i: usize) -> Option<Rc<RFn>> {
         self.fn_table.get(i).cloned()
     }
@@ -270,6 +675,31 @@ impl VM {
         class
     }
 
+    pub(crate) fn define_module(&mut self, name: &str) -> Rc<RClass> {
+        let module = Rc::new(RClass::new_module(name));
+        let object = RObject::class(module.clone()).to_refcount_assigned();
+        self.consts.insert(name.to_string(), object);
+        module
+    }
+
+    // classes and modules opened in another class body live in its consts
+    pub(crate) fn define_nested_class(&mut self, outer: &Rc<RClass>, name: &str, superclass: Option<Rc<RClass>>) -> Rc<RClass> {
+        let superclass = superclass.unwrap_or_else(|| self.object_class.clone());
+        let path = format!("{}::{}", outer.sym_id.name, name);
+        let class = Rc::new(RClass::new(&path, Some(superclass)));
+        let object = RObject::class(class.clone()).to_refcount_assigned();
+        outer.consts.borrow_mut().insert(name.to_string(), object);
+        class
+    }
+
+    pub(crate) fn define_nested_module(&mut self, outer: &Rc<RClass>, name: &str) -> Rc<RClass> {
+        let path = format!("{}::{}", outer.sym_id.name, name);
+        let module = Rc::new(RClass::new_module(&path));
+        let object = RObject::class(module.clone()).to_refcount_assigned();
+        outer.consts.borrow_mut().insert(name.to_string(), object);
+        module
+    }
+
     pub(crate) fn define_standard_class(&mut self, name: &'static str) -> Rc<RClass> {
         let class = self.define_class(name, None);
         self.builtin_class_table.insert(name, class.clone());
@@ -307,26 +737,52 @@ fn load_irep_1(reps: &mut [Irep], pos: usize) -> (IREP, usize) {
         syms: Vec::new(),
         pool: Vec::new(),
         reps: Vec::new(),
-        catch_target_pos: Vec::new(),
+        catch_handlers: Vec::new(),
+        debug_info: irep.debug_info.clone(),
+        lv: Vec::new(),
+        method_cache: MethodCache::default(),
     };
+    for name in irep.lv.iter() {
+        irep1.lv.push(name.as_ref().map(|n| RSym::new(n.clone())));
+    }
     for sym in irep.syms.iter() {
         irep1.syms.push(RSym::new(sym.to_string_lossy().to_string()));
     }
-    for str in irep.strvals.iter() {
-        irep1.pool.push(RPool::Str(str.to_string_lossy().to_string()));
+    for val in irep.pool.iter() {
+        let val = match val {
+            PoolValue::Str(s) | PoolValue::StaticStr(s) => RPool::Str(s.to_string_lossy().to_string()),
+            PoolValue::Int32(n) => RPool::Int(*n as i64),
+            PoolValue::Int64(n) => RPool::Int(*n),
+            PoolValue::Float(f) => RPool::Float(*f),
+            PoolValue::BigInt { base, digits } => RPool::BigInt {
+                base: base.unsigned_abs() as u32,
+                negative: *base < 0,
+                digits: String::from_utf8_lossy(digits).to_string(),
+            },
+        };
+        irep1.pool.push(val);
     }
     let code = interpret_insn(&mut irep.insn);
     for ch in irep.catch_handlers.iter() {
         let pos = ch.target;
         let (i, _) = code.iter().enumerate().find(|(_, op)| op.pos == pos).expect("catch handler mismatch");
-        irep1.catch_target_pos.push(i);
+        irep1.catch_handlers.push(CatchHandler {
+            type_: if ch.type_ == 1 { CatchType::Ensure } else { CatchType::Rescue },
+            start: op_index(&code, ch.start),
+            end: op_index(&code, ch.end),
+            target: i,
+        });
     }
-    irep1.catch_target_pos.sort();
 
     irep1.code = code;
     (irep1, pos + 1)
 }
 
+// index of the op at the byte position, or the end of the code
+fn op_index(code: &[Op], pos: usize) -> usize {
+    code.iter().position(|op| op.pos >= pos).unwrap_or(code.len())
+}
+
 fn load_irep_0(reps: &mut [Irep], pos: usize) -> (IREP, usize) {
     let (mut irep0, newpos) = load_irep_1(reps, pos);
     let mut pos = newpos;
@@ -338,6 +794,33 @@ fn load_irep_0(reps: &mut [Irep], pos: usize) -> (IREP, usize) {
     (irep0, pos)
 }
 
+// The IREP may be built before the VM, so its symbols are interned
+// again into the symbol table of the VM
+fn reintern_irep(irep: &mut IREP) {
+    for sym in irep.syms.iter_mut() {
+        *sym = RSym::from(sym.name.as_str());
+    }
+    for sym in irep.lv.iter_mut().flatten() {
+        *sym = RSym::from(sym.name.as_str());
+    }
+    for rep in irep.reps.iter_mut() {
+        reintern_irep(Rc::make_mut(rep));
+    }
+}
+
+fn max_irep_id(irep: &IREP) -> usize {
+    irep.reps.iter().map(|rep| max_irep_id(rep)).fold(irep.__id, usize::max)
+}
+
+// The environments of frames are keyed by the ids of IREPs, so the ones
+// of the mrblib are moved after the ones of the program
+fn shift_irep_ids(irep: &mut IREP, base: usize) {
+    irep.__id += base;
+    for rep in irep.reps.iter_mut() {
+        shift_irep_ids(Rc::make_mut(rep), base);
+    }
+}
+
 // This will consume the Rite object and return the IREP
 fn rite_to_irep(rite: &mut Rite) -> IREP {
     let (irep0, _) = load_irep_0(&mut rite.irep, 0);
@@ -355,7 +838,44 @@ pub struct IREP {
     pub syms: Vec<RSym>,
     pub pool: Vec<RPool>,
     pub reps: Vec<Rc<IREP>>,
-    pub catch_target_pos: Vec<usize>,
+    pub catch_handlers: Vec<CatchHandler>,
+    pub debug_info: Option<DebugInfo>,
+    // names of the local variables from R1; None for unnamed registers
+    pub lv: Vec<Option<RSym>>,
+    pub method_cache: MethodCache,
+}
+
+impl IREP {
+    // name of the local variable held in the register, if any
+    pub fn local_variable_name(&self, reg: usize) -> Option<&str> {
+        let sym = self.lv.get(reg.checked_sub(1)?)?.as_ref()?;
+        Some(&sym.name)
+    }
+
+    pub fn local_variables(&self) -> Vec<&str> {
+        self.lv.iter().flatten().map(|sym| sym.name.as_str()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchType {
+    Rescue,
+    Ensure,
+}
+
+// start, end and target are op indices; the handler covers start..end
+#[derive(Debug, Clone)]
+pub struct CatchHandler {
+    pub type_: CatchType,
+    pub start: usize,
+    pub end: usize,
+    pub target: usize,
+}
+
+impl CatchHandler {
+    pub fn covers(&self, pc: usize) -> bool {
+        self.start <= pc && pc < self.end
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -367,14 +887,38 @@ pub struct CALLINFO {
     pub current_regs_offset: usize,
     pub target_class: Rc<RClass>,
     pub n_args: usize,
+    // number of frames up to this one, checked against VM::max_call_depth
+    pub depth: usize,
+    // register index of the keyword arguments hash, if any
+    pub kdict_index: Cell<Option<usize>>,
+    // true when the frame is entered from Rust (mrb_funcall, block call)
+    // and returning from it must leave the nested VM::run loop
+    pub called_from_rust: bool,
+    // true when the host enters the VM while no Ruby code runs, so the
+    // caller position saved in the frame is not a Ruby caller
+    pub host_entry: bool,
+    // the frame runs a lambda or method, see RProc::is_lambda
+    pub is_lambda: bool,
+    // block environment of the caller, restored on return
+    pub upper: Option<Rc<ENV>>,
+    // environment of the block literal passed to this method, which is
+    // orphaned when the method returns
+    pub block_env: Option<Rc<ENV>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ENV {
     pub upper: Option<Rc<ENV>>,
+    // the frame which created the block, None for the top level
+    pub callinfo: Option<Rc<CALLINFO>>,
     pub captured: RefCell<Option<Vec<Option<Rc<RObject>>>>>,
     pub current_regs_offset: usize,
     pub is_expired: Cell<bool>,
+    // set when the call the block was given to has returned,
+    // after which `break` has nowhere to go
+    pub is_orphan: Cell<bool>,
+    // the fiber whose register stack holds the frame, None for the root one
+    pub stack: Option<Weak<RFiber>>,
 }
 
 impl ENV {
@@ -0,0 +1,142 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn alias_test() {
+    let code = "
+    class Calc
+      def add(a, b)
+        a + b
+      end
+      alias plus add
+      alias_method :sum, :add
+
+      def add(a, b)
+        0
+      end
+    end
+
+    def test_main
+      c = Calc.new
+      c.plus(1, 2) + c.sum(10, 20) + c.add(100, 200)
+    end
+    ";
+    let binary = mrbc_compile("alias", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 33);
+}
+
+#[test]
+fn undef_method_test() {
+    let code = "
+    class Base
+      def name
+        1
+      end
+    end
+
+    class Child < Base
+      def name
+        2
+      end
+      undef name
+    end
+
+    def test_main
+      begin
+        Child.new.name
+      rescue NoMethodError
+        0
+      end
+    end
+    ";
+    let binary = mrbc_compile("undef_method", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 0);
+}
+
+#[test]
+fn remove_method_test() {
+    let code = "
+    class Base
+      def name
+        1
+      end
+    end
+
+    class Child < Base
+      def name
+        2
+      end
+      remove_method :name
+    end
+
+    def test_main
+      Child.new.name
+    end
+    ";
+    let binary = mrbc_compile("remove_method", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn remove_undefined_method_test() {
+    let code = "
+    class Foo
+      def m
+        1
+      end
+    end
+
+    class Bar < Foo
+      undef m
+    end
+
+    def test_main
+      begin
+        Bar.remove_method(:m)
+      rescue NameError
+      end
+      begin
+        Bar.new.m
+      rescue NoMethodError
+        0
+      end
+    end
+    ";
+    let binary = mrbc_compile("remove_undefined_method", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 0);
+}
@@ -0,0 +1,60 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn backtrace_test() {
+    let code = "
+    def inner
+      raise \"boom\"
+    end
+
+    def test_main
+      begin
+        inner
+      rescue => e
+        e.backtrace.size.to_s + \",\" + e.backtrace[0] + \",\" + e.backtrace[1]
+      end
+    end
+    ";
+    let binary = mrbc_compile_debug("backtrace", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    let frames: Vec<&str> = result.split(",").collect();
+    // the frame of the host call ends the backtrace
+    assert_eq!(frames[0], "2", "{}", result);
+    assert!(frames[1].ends_with(".mrb:3:in 'inner'"), "{}", result);
+    assert!(frames[2].ends_with(".mrb:8:in 'test_main'"), "{}", result);
+}
+
+#[test]
+fn backtrace_rust_error_test() {
+    let code = "
+    def test_main
+      [1, 2].each do |x|
+        raise \"in block\" if x == 2
+      end
+    end
+    ";
+    let binary = mrbc_compile_debug("backtrace_rust_error", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let err = mrb_funcall(&mut vm, None, "test_main", &args).err().unwrap();
+    assert_eq!(err.kind(), mrubyedge::Error::RuntimeError("in block".to_string()));
+    let backtrace = err.backtrace();
+    assert!(backtrace[0].ends_with(":4:in '<block>'"), "{:?}", backtrace);
+    assert!(backtrace[1].ends_with(":3:in 'test_main'"), "{:?}", backtrace);
+    assert_eq!(backtrace.len(), 2, "{:?}", backtrace);
+}
@@ -0,0 +1,143 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn bigint_factorial_test() {
+    let code = "
+    def fact(n)
+      r = 1
+      i = 1
+      while i <= n
+        r = r * i
+        i += 1
+      end
+      r
+    end
+
+    def test_main
+      fact(30).to_s
+    end
+    ";
+    let binary = mrbc_compile("bigint_factorial", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "265252859812191058636308480000000");
+}
+
+#[test]
+fn bigint_demote_test() {
+    let code = "
+    def test_main
+      big = 9223372036854775807 + 1
+      (big * big) / big - 1
+    end
+    ";
+    let binary = mrbc_compile("bigint_demote", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, i64::MAX);
+}
+
+#[test]
+fn bigint_compare_and_hash_key_test() {
+    let code = "
+    def test_main
+      a = 123456789012345678901234567890
+      b = a + 1
+      h = {}
+      h[a * 2] = \"found\"
+      if b > a && a < b && 0 - b < 0 - a && a == b - 1
+        h[a + a]
+      else
+        \"ng\"
+      end
+    end
+    ";
+    let binary = mrbc_compile("bigint_compare", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "found");
+}
+
+#[test]
+fn integer_floored_division_test() {
+    let code = "
+    def test_main
+      [-7 / 2, -7 % 2, 7 % -2, 100000000000000000000 % 1000000007]
+    end
+    ";
+    let binary = mrbc_compile("floored_division", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: Vec<std::rc::Rc<mrubyedge::yamrb::value::RObject>> = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    let result: Vec<i64> = result.iter().map(|v| v.as_ref().try_into().unwrap()).collect();
+    assert_eq!(result, vec![-4, 1, -1, 4900]);
+}
+
+#[test]
+fn zero_division_test() {
+    let code = "
+    def test_main
+      begin
+        1 / 0
+      rescue ZeroDivisionError => e
+        e.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("zero_division", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "divided by 0");
+}
+
+#[test]
+fn integer_pow_test() {
+    let code = "
+    def test_main
+      (2 ** 10).to_s + \" \" + (2 ** 100).to_s + \" \" + (7 ** 0).to_s
+    end
+    ";
+    let binary = mrbc_compile("integer_pow", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "1024 1267650600228229401496703205376 1");
+}
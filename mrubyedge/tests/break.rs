@@ -0,0 +1,174 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use std::rc::Rc;
+
+use helpers::*;
+use mrubyedge::yamrb::value::RObject;
+
+#[test]
+fn break_rust_iterator_test() {
+    let code = "
+    def test_main
+      a = [1, 2, 3, 4].each { |x| break x * 100 if x == 2 }
+      b = 10.times { |i| break i if i == 7 }
+      c = (1..10).each { |i| break i + 1000 if i == 3 }
+      d = {k: 4}.each { |k, v| break v * 10000 }
+      a + b + c + d
+    end
+    ";
+    let binary = mrbc_compile("break_rust_iterator", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 200 + 7 + 1003 + 40000);
+}
+
+#[test]
+fn break_ruby_iterator_test() {
+    let code = "
+    def my_each(xs)
+      i = 0
+      while i < xs.size
+        yield xs[i]
+        i += 1
+      end
+      -1
+    end
+
+    def test_main
+      my_each([1, 2, 3]) { |x| break x + 50 if x == 2 }
+    end
+    ";
+    let binary = mrbc_compile("break_ruby_iterator", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 52);
+}
+
+#[test]
+fn next_test() {
+    let code = "
+    def test_main
+      $sum = 0
+      [1, 2, 3, 4].each do |x|
+        next if x == 2
+        $sum += x
+      end
+      $sum
+    end
+    ";
+    let binary = mrbc_compile("next", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 8);
+}
+
+#[test]
+fn return_from_block_test() {
+    let code = "
+    def find_first(xs)
+      xs.each { |x| return x if x > 2 }
+      nil
+    end
+
+    def test_main
+      find_first([1, 2, 3, 4]) + 10
+    end
+    ";
+    let binary = mrbc_compile("return_from_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 13);
+
+    // Assert 2: the method is called from Rust directly
+    let xs = vec![Rc::new(RObject::integer(5)), Rc::new(RObject::integer(6))];
+    let args = vec![Rc::new(RObject::array(xs))];
+    let result: i32 = mrb_funcall(&mut vm, None, "find_first", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 5);
+}
+
+#[test]
+fn return_from_nested_block_test() {
+    let code = "
+    def find_pair
+      [1, 2, 3].each do |a|
+        [4, 5, 6].each do |b|
+          return a * 10 + b if a + b == 8
+        end
+      end
+      0
+    end
+
+    def test_main
+      find_pair
+    end
+    ";
+    let binary = mrbc_compile("return_from_nested_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 26);
+}
+
+#[test]
+fn break_from_orphan_block_test() {
+    let code = "
+    def keep(&b)
+      b
+    end
+
+    def give(&b)
+      b.call
+    end
+
+    def test_main
+      through = give { break 4 }
+      begin
+        keep { break 3 }.call
+      rescue LocalJumpError => e
+        e.message + \"/\" + through.to_s
+      end
+    end
+    ";
+    let binary = mrbc_compile("break_from_orphan_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "break from proc-closure/4");
+}
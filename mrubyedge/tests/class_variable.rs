@@ -0,0 +1,100 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn class_variable_test() {
+    let code = "
+    class Registry
+      @@count = 0
+
+      def self.register
+        @@count += 1
+      end
+
+      def count
+        @@count
+      end
+    end
+
+    class SubRegistry < Registry
+      def bump
+        @@count += 10
+      end
+    end
+
+    def test_main
+      Registry.register
+      SubRegistry.register
+      SubRegistry.new.bump
+      Registry.new.count
+    end
+    ";
+    let binary = mrbc_compile("class_variable", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 12);
+}
+
+#[test]
+fn class_variable_get_set_test() {
+    let code = "
+    class Config
+    end
+
+    class AppConfig < Config
+    end
+
+    def test_main
+      Config.class_variable_set(:@@size, 40)
+      AppConfig.class_variable_get(:@@size) + 2
+    end
+    ";
+    let binary = mrbc_compile("class_variable_get_set", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn uninitialized_class_variable_test() {
+    let code = "
+    class Empty
+      def value
+        @@value
+      end
+    end
+
+    def test_main
+      begin
+        Empty.new.value
+      rescue NameError => e
+        e.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("uninitialized_class_variable", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert!(result.contains("uninitialized class variable @@value in Empty"));
+}
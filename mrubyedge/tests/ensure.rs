@@ -0,0 +1,125 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn ensure_normal_exit_test() {
+    let code = "
+    def test_main
+      log = []
+      begin
+        log.push(1)
+      ensure
+        log.push(2)
+      end
+      log.size
+    end
+    ";
+    let binary = mrbc_compile("ensure_normal_exit", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 2);
+}
+
+#[test]
+fn ensure_on_return_test() {
+    let code = "
+    $log = []
+
+    def early
+      begin
+        begin
+          return 10
+        ensure
+          $log.push(1)
+        end
+      ensure
+        $log.push(2)
+      end
+      20
+    end
+
+    def test_main
+      early + $log.size
+    end
+    ";
+    let binary = mrbc_compile("ensure_on_return", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 12);
+}
+
+#[test]
+fn ensure_on_exception_test() {
+    let code = "
+    $log = []
+
+    def fail
+      begin
+        raise \"boom\"
+      ensure
+        $log.push(:ensured)
+      end
+    end
+
+    def test_main
+      begin
+        fail
+      rescue => e
+        $log.push(e.message)
+      end
+      $log.size
+    end
+    ";
+    let binary = mrbc_compile("ensure_on_exception", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 2);
+}
+
+#[test]
+fn ensure_on_break_test() {
+    let code = "
+    def test_main
+      log = []
+      found = [1, 2, 3].each do |x|
+        begin
+          break x * 100 if x == 2
+        ensure
+          log.push(x)
+        end
+      end
+      found + log.size
+    end
+    ";
+    let binary = mrbc_compile("ensure_on_break", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 202);
+}
@@ -0,0 +1,179 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn custom_exception_test() {
+    let code = "
+    class AppError < StandardError
+      attr_reader :code
+
+      def initialize(msg, code)
+        super(msg)
+        @code = code
+      end
+    end
+
+    class OtherError < StandardError
+    end
+
+    def test_main
+      begin
+        raise AppError.new(\"failed\", 40)
+      rescue OtherError
+        0
+      rescue AppError => e
+        e.code + e.message.size
+      end
+    end
+    ";
+    let binary = mrbc_compile("custom_exception", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 46);
+}
+
+#[test]
+fn raise_class_with_message_test() {
+    let code = "
+    class AppError < StandardError
+    end
+
+    def test_main
+      begin
+        raise AppError, \"with message\"
+      rescue StandardError => e
+        if e.class == AppError
+          e.message
+        else
+          \"NG\"
+        end
+      end
+    end
+    ";
+    let binary = mrbc_compile("raise_class_with_message", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "with message");
+}
+
+#[test]
+fn exception_new_test() {
+    let code = "
+    def test_main
+      e = ArgumentError.new(\"prepared\")
+      begin
+        raise e
+      rescue ArgumentError => caught
+        caught.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("exception_new", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "prepared");
+}
+
+#[test]
+fn rescue_binds_raised_object_test() {
+    let code = "
+    class E2 < StandardError
+    end
+
+    def test_main
+      ex = E2.new(\"obj\")
+      begin
+        raise ex
+      rescue E2 => e
+        e.object_id == ex.object_id ? 1 : 0
+      end
+    end
+    ";
+    let binary = mrbc_compile("rescue_binds_raised_object", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn uncaught_custom_exception_test() {
+    let code = "
+    class AppError < StandardError
+    end
+
+    def test_main
+      raise AppError, \"not rescued\"
+    end
+    ";
+    let binary = mrbc_compile("uncaught_custom_exception", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let err = mrb_funcall(&mut vm, None, "test_main", &args).err().unwrap();
+    assert_eq!(&err.message(), "not rescued");
+    let klass = vm.get_class_by_name("StandardError");
+    assert!(err.is_a(&mut vm, klass));
+}
+
+#[test]
+fn uncaught_vm_error_test() {
+    let code = "
+    def missing
+      no_such_method
+    end
+
+    def in_block
+      [1].each { |x| x + nil }
+    end
+
+    def fine
+      1
+    end
+    ";
+    let binary = mrbc_compile("uncaught_vm_error", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let err = mrb_funcall(&mut vm, None, "missing", &args).err().unwrap();
+    assert!(matches!(err, mrubyedge::Error::NoMethodError(_)), "{:?}", err);
+    assert!(vm.last_exception.is_some());
+    let err = mrb_funcall(&mut vm, None, "in_block", &args).err().unwrap();
+    assert!(!matches!(err, mrubyedge::Error::Exception(_)), "{:?}", err);
+    // the error is not raised again by the next call
+    let result: i32 = mrb_funcall(&mut vm, None, "fine", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+}
@@ -0,0 +1,286 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn fiber_generator_test() {
+    let code = "
+    def test_main
+      fib = Fiber.new do
+        a = 0
+        b = 1
+        while true
+          Fiber.yield a
+          a, b = b, a + b
+        end
+      end
+      result = []
+      10.times do
+        result.push(fib.resume)
+      end
+      result
+    end
+    ";
+    let binary = mrbc_compile("fiber_generator", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args).unwrap();
+    let result: Vec<i64> = match &result.value {
+        mrubyedge::yamrb::value::RValue::Array(a) => a.borrow().iter()
+            .map(|v| v.as_ref().try_into().unwrap()).collect(),
+        _ => panic!("not an array"),
+    };
+    assert_eq!(result, vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+}
+
+#[test]
+fn fiber_transfer_values_test() {
+    let code = "
+    def wait_for(label)
+      $log = $log + label + \";\"
+      Fiber.yield label
+    end
+
+    def test_main
+      $log = \"\"
+      bot = Fiber.new do |name|
+        got = wait_for(name + \"-ready\")
+        got = wait_for(got + \"-moved\")
+        \"done:\" + got
+      end
+      r1 = bot.resume(\"bot\")
+      r2 = bot.resume(\"left\")
+      r3 = bot.resume(\"right\")
+      state = bot.alive? ? \"alive\" : \"dead\"
+      r1 + \"/\" + r2 + \"/\" + r3 + \"/\" + state + \"/\" + $log
+    end
+    ";
+    let binary = mrbc_compile("fiber_transfer_values", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "bot-ready/left-moved/done:right/dead/bot-ready;left-moved;");
+}
+
+#[test]
+fn fiber_closure_test() {
+    let code = "
+    def capture(&block)
+      block
+    end
+
+    def test_main
+      count = 0
+      getter = nil
+      fib = Fiber.new do
+        local = 10
+        getter = capture { local }
+        while true
+          count += 1
+          local += 1
+          Fiber.yield count
+        end
+      end
+      fib.resume
+      fib.resume
+      count * 100 + getter.call
+    end
+    ";
+    let binary = mrbc_compile("fiber_closure", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    // the getter reads the registers of the suspended fiber
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 212);
+}
+
+#[test]
+fn fiber_nested_test() {
+    let code = "
+    def test_main
+      inner = Fiber.new do
+        Fiber.yield 1
+        2
+      end
+      outer = Fiber.new do
+        Fiber.yield inner.resume * 10
+        Fiber.yield inner.resume * 10
+        0
+      end
+      result = [outer.resume, outer.resume, outer.resume]
+      result.push(inner.alive? ? 1 : 0)
+      result.push(outer.alive? ? 1 : 0)
+      result
+    end
+    ";
+    let binary = mrbc_compile("fiber_nested", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args).unwrap();
+    let result: Vec<i64> = match &result.value {
+        mrubyedge::yamrb::value::RValue::Array(a) => a.borrow().iter()
+            .map(|v| v.as_ref().try_into().unwrap()).collect(),
+        _ => panic!("not an array"),
+    };
+    assert_eq!(result, vec![10, 20, 0, 0, 0]);
+}
+
+#[test]
+fn fiber_errors_test() {
+    let code = "
+    def test_main
+      log = []
+      fib = Fiber.new do
+        Fiber.yield 1
+        raise \"inside\"
+      end
+      fib.resume
+      begin
+        fib.resume
+      rescue => e
+        log.push(e.message)
+      end
+      begin
+        fib.resume
+      rescue FiberError => e
+        log.push(e.message)
+      end
+      begin
+        Fiber.yield
+      rescue FiberError => e
+        log.push(e.message)
+      end
+      log[0] + \"/\" + log[1] + \"/\" + log[2]
+    end
+    ";
+    let binary = mrbc_compile("fiber_errors", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "inside/attempt to resume a terminated fiber/can't yield from root fiber");
+}
+
+#[test]
+fn fiber_subclass_test() {
+    let code = "
+    class Counter < Fiber
+      def next_twice
+        resume * 2
+      end
+    end
+
+    def test_main
+      fib = Counter.new do
+        n = 1
+        while true
+          Fiber.yield n
+          n += 1
+        end
+      end
+      fib.resume
+      (fib.class == Counter ? 100 : 0) + fib.next_twice
+    end
+    ";
+    let binary = mrbc_compile("fiber_subclass", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 104);
+}
+
+#[test]
+fn fiber_yield_in_iterator_test() {
+    let code = "
+    def pairs
+      yield 1, 2
+      yield 3, 4
+    end
+
+    def test_main
+      fib = Fiber.new do
+        [1, 2].each { |i| Fiber.yield i }
+        2.times { |i| Fiber.yield i * 10 }
+        (5..6).each { |i| Fiber.yield i * 100 }
+        pairs { |a, b| Fiber.yield a + b }
+        ->(i) { Fiber.yield i }.call(9)
+        0
+      end
+      result = []
+      10.times do
+        result.push(fib.resume)
+      end
+      result
+    end
+    ";
+    let binary = mrbc_compile("fiber_yield_in_iterator", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args).unwrap();
+    let result: Vec<i64> = match &result.value {
+        mrubyedge::yamrb::value::RValue::Array(a) => a.borrow().iter()
+            .map(|v| v.as_ref().try_into().unwrap()).collect(),
+        _ => panic!("not an array"),
+    };
+    assert_eq!(result, vec![1, 2, 0, 10, 500, 600, 3, 7, 9, 0]);
+}
+
+#[test]
+fn fiber_yield_in_rust_method_test() {
+    // blocks called by Rust methods cannot be suspended, see fiber::fiber_yield
+    let code = "
+    def test_main
+      fib = Fiber.new do
+        {a: 1}.each { |k, v| Fiber.yield v }
+      end
+      begin
+        fib.resume
+      rescue FiberError => e
+        e.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("fiber_yield_in_rust_method", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "can't yield from a block called by a Rust method");
+}
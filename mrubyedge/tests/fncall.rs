@@ -105,3 +105,24 @@ complex_calc(2, 3)
     let result: i64 = result.as_ref().try_into().unwrap();
     assert_eq!(result, 15);
 }
+
+#[test]
+fn fncall_rust_method_args_test() {
+    let code = "
+def make
+  [1]
+end
+    ";
+    let binary = mrbc_compile("fncall_rust_method_args", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // mrb_funcall passes the block slot to Rust methods as SEND does
+    let ary = mrb_funcall(&mut vm, None, "make", &[]).unwrap();
+    let args = vec![Rc::new(RObject::integer(2)), Rc::new(RObject::integer(3))];
+    mrb_funcall(&mut vm, Some(ary.clone()), "push", &args).unwrap();
+    let result = mrb_funcall(&mut vm, Some(ary), "size", &[]).unwrap();
+    let result: i64 = result.as_ref().try_into().unwrap();
+    assert_eq!(result, 3);
+}
@@ -0,0 +1,128 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+use mrubyedge::Error;
+
+#[test]
+fn fuel_exhausted_test() {
+    let code = "
+    def test_main
+      while true
+      end
+    end
+    ";
+    let binary = mrbc_compile("fuel_exhausted", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_fuel(Some(1000));
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args);
+    assert!(matches!(result, Err(Error::OutOfFuel)));
+    assert_eq!(vm.remaining_fuel(), Some(0));
+}
+
+#[test]
+fn fuel_remaining_test() {
+    let code = "
+    def test_main
+      a = 1
+      a + 2
+    end
+    ";
+    let binary = mrbc_compile("fuel_remaining", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_fuel(Some(1000));
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 3);
+    let remaining = vm.remaining_fuel().unwrap();
+    assert!(remaining < 1000 && remaining > 990, "{}", remaining);
+}
+
+#[test]
+fn fuel_not_rescued_test() {
+    let code = "
+    $rescued = false
+    $ensured = false
+
+    def spin
+      while true
+      end
+    end
+
+    def test_main
+      begin
+        spin
+      rescue Exception
+        $rescued = true
+      ensure
+        $ensured = true
+      end
+    end
+
+    def check
+      [$rescued, $ensured]
+    end
+    ";
+    let binary = mrbc_compile("fuel_not_rescued", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_fuel(Some(1000));
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args);
+    assert!(matches!(result, Err(Error::OutOfFuel)));
+
+    // the VM is usable again after refueling
+    vm.set_fuel(None);
+    let result: Vec<std::rc::Rc<mrubyedge::yamrb::value::RObject>> = mrb_funcall(&mut vm, None, "check", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert!(result.iter().all(|v| v.is_falsy()));
+}
+
+#[test]
+fn fuel_in_block_test() {
+    let code = "
+    def test_main
+      [1, 2, 3].each do |x|
+        while true
+        end
+      end
+    end
+
+    def after
+      $sum = 0
+      [1, 2, 3].each do |x|
+        $sum = $sum + x
+      end
+      $sum
+    end
+    ";
+    let binary = mrbc_compile("fuel_in_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_fuel(Some(1000));
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args);
+    assert!(matches!(result, Err(Error::OutOfFuel)));
+
+    vm.set_fuel(Some(1000));
+    let result: i64 = mrb_funcall(&mut vm, None, "after", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 6);
+}
@@ -0,0 +1,195 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn gc_parent_cycle_test() {
+    let code = "
+    class Node
+      def initialize(parent)
+        @parent = parent
+        @children = []
+      end
+
+      def add
+        child = Node.new(self)
+        @children.push(child)
+        child
+      end
+    end
+
+    def tick
+      root = Node.new(nil)
+      root.add
+      root.add
+      nil
+    end
+
+    def objects
+      ObjectSpace.count_objects[:T_OBJECT]
+    end
+    ";
+    let binary = mrbc_compile("gc_parent_cycle", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    let args = vec![];
+    let before: i64 = mrb_funcall(&mut vm, None, "objects", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    for _ in 0..100 {
+        mrb_funcall(&mut vm, None, "tick", &args).unwrap();
+    }
+    let leaked: i64 = mrb_funcall(&mut vm, None, "objects", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    let freed = vm.gc_start();
+    let after: i64 = mrb_funcall(&mut vm, None, "objects", &args)
+        .unwrap().as_ref().try_into().unwrap();
+
+    // Assert
+    assert_eq!(leaked - before, 300);
+    // 300 nodes and 300 children arrays
+    assert_eq!(freed, 600);
+    assert_eq!(after, before);
+    assert_eq!(vm.gc_count(), 1);
+}
+
+#[test]
+fn gc_self_containing_hash_test() {
+    let code = "
+    def tick
+      h = {}
+      h[:self] = h
+      a = [h]
+      h[:array] = a
+      nil
+    end
+
+    def test_main
+      10.times do
+        tick
+      end
+      GC.start
+      GC.count
+    end
+    ";
+    let binary = mrbc_compile("gc_self_containing_hash", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let count: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(count, 1);
+    let hashes = vm.gc.count_objects()
+        .get(&mrubyedge::yamrb::value::RType::Hash).copied().unwrap_or(0);
+    assert_eq!(hashes, 0);
+}
+
+#[test]
+fn gc_closure_cycle_test() {
+    let code = "
+    def capture(&block)
+      block
+    end
+
+    def make
+      pr = nil
+      pr = capture { pr }
+      nil
+    end
+    ";
+    let binary = mrbc_compile("gc_closure_cycle", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    let args = vec![];
+    for _ in 0..10 {
+        mrb_funcall(&mut vm, None, "make", &args).unwrap();
+    }
+    let procs = |vm: &mrubyedge::yamrb::vm::VM| vm.gc.count_objects()
+        .get(&mrubyedge::yamrb::value::RType::Proc).copied().unwrap_or(0);
+    let leaked = procs(&vm);
+    vm.gc_start();
+
+    // Assert
+    // the environment of the last block stays referenced by the VM
+    assert_eq!(leaked, 10);
+    assert_eq!(procs(&vm), 1);
+}
+
+#[test]
+fn gc_keeps_reachable_test() {
+    let code = "
+    def test_main
+      $keep = []
+      $keep.push($keep)
+      h = {}
+      h[:self] = h
+      GC.start
+      [$keep.size, h[:self].size]
+    end
+
+    def kept
+      $keep[0].size
+    end
+    ";
+    let binary = mrbc_compile("gc_keeps_reachable", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args).unwrap();
+    vm.gc_start();
+    let kept: i64 = mrb_funcall(&mut vm, None, "kept", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(kept, 1);
+    let result: Vec<i64> = match &result.value {
+        mrubyedge::yamrb::value::RValue::Array(a) => a.borrow().iter()
+            .map(|v| v.as_ref().try_into().unwrap()).collect(),
+        _ => panic!("not an array"),
+    };
+    assert_eq!(result, vec![1, 1]);
+}
+
+#[test]
+fn gc_two_vms_test() {
+    let code = "
+    def capture(&block)
+      block
+    end
+
+    def make
+      pr = nil
+      pr = capture { pr }
+      nil
+    end
+    ";
+    let binary = mrbc_compile("gc_two_vms", code);
+    let mut rite_a = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm_a = mrubyedge::yamrb::vm::VM::open(&mut rite_a);
+    vm_a.run().unwrap();
+    let mut rite_b = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm_b = mrubyedge::yamrb::vm::VM::open(&mut rite_b);
+    vm_b.run().unwrap();
+
+    let args = vec![];
+    for _ in 0..10 {
+        mrb_funcall(&mut vm_a, None, "make", &args).unwrap();
+    }
+    let procs = |vm: &mrubyedge::yamrb::vm::VM| vm.gc.count_objects()
+        .get(&mrubyedge::yamrb::value::RType::Proc).copied().unwrap_or(0);
+
+    // Assert
+    assert_eq!(procs(&vm_a), 10);
+    assert_eq!(procs(&vm_b), 0);
+    vm_a.gc_start();
+    assert_eq!(procs(&vm_a), 1);
+}
@@ -0,0 +1,42 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use std::rc::Rc;
+
+use helpers::*;
+use mrubyedge::yamrb::value::RObject;
+
+#[test]
+fn shared_immediates_test() {
+    assert!(Rc::ptr_eq(&RObject::nil().into_rc(), &RObject::nil().into_rc()));
+    assert!(Rc::ptr_eq(&RObject::boolean(true).into_rc(), &RObject::boolean(true).into_rc()));
+    assert!(!Rc::ptr_eq(&RObject::boolean(true).into_rc(), &RObject::boolean(false).into_rc()));
+    assert!(Rc::ptr_eq(&RObject::integer(-3).into_rc(), &RObject::integer(-3).into_rc()));
+    assert!(!Rc::ptr_eq(&RObject::integer(1 << 40).into_rc(), &RObject::integer(1 << 40).into_rc()));
+}
+
+#[test]
+fn shared_integers_object_id_test() {
+    let code = "
+    def test_main
+      a = 7
+      b = 3 + 4
+      if a.object_id == b.object_id
+        \"same\"
+      else
+        \"differ\"
+      end
+    end
+    ";
+    let binary = mrbc_compile("shared_integers_object_id", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "same");
+}
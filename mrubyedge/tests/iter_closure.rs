@@ -125,3 +125,29 @@ fn expired_closure_test() {
         .unwrap().as_ref().try_into().unwrap();
     assert_eq!(result, 3);
 }
+
+#[test]
+fn closure_across_iterator_test() {
+    let code = "
+    def a
+    end
+
+    def test_main
+      x = 1
+      getter = ->(i) { x + i }
+      [0].each(&getter)
+      x = 7
+      getter.call(0)
+    end
+    ";
+    let binary = mrbc_compile("closure_across_iterator", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 7);
+}
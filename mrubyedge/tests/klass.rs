@@ -80,4 +80,177 @@ fn attr_accessor_test() {
     let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
         .unwrap().as_ref().try_into().unwrap();
     assert_eq!(&result, "Hola, attr");
-}
\ No newline at end of file
+}
+
+#[test]
+fn initialize_with_args_test() {
+    let code = "
+    class Hello
+      def initialize(a, b)
+        @sum = a + b
+      end
+
+      def sum
+        @sum
+      end
+    end
+
+    def test_main
+      Hello.new(1, 2).sum
+    end
+    ";
+    let binary = mrbc_compile("initialize_with_args", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 3);
+}
+
+#[test]
+fn initialize_with_kwargs_test() {
+    let code = "
+    class Named
+      def initialize(name:, age: 20)
+        @name = name
+        @age = age
+      end
+
+      def describe
+        @name + \" \" + @age.to_s
+      end
+    end
+
+    class Bag
+      def initialize(opts)
+        @opts = opts
+      end
+
+      def size
+        @opts.size
+      end
+    end
+
+    def test_main
+      Named.new(name: \"a\").describe + \",\" + Named.new(name: \"b\", age: 3).describe + \",\" +
+        Bag.new({x: 1, y: 2}).size.to_s
+    end
+    ";
+    let binary = mrbc_compile("initialize_with_kwargs", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "a 20,b 3,2");
+}
+
+#[test]
+fn initialize_with_block_test() {
+    let code = "
+    class Counter
+      def initialize(start, &block)
+        @value = block.call(start)
+      end
+
+      def value
+        @value
+      end
+    end
+
+    class Yielder
+      def initialize
+        @value = yield
+      end
+
+      def value
+        @value
+      end
+    end
+
+    def test_main
+      Counter.new(1) { |n| n + 10 }.value + Yielder.new { 100 }.value
+    end
+    ";
+    let binary = mrbc_compile("initialize_with_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 111);
+}
+
+#[test]
+fn reopen_class_test() {
+    let code = "
+    class Integer
+      def double
+        self * 2
+      end
+    end
+
+    class Greeter
+      def a; 1; end
+    end
+
+    class Greeter
+      def b; 20; end
+    end
+
+    def test_main
+      g = Greeter.new
+      g.a + g.b + 3.double + (4 + 5)
+    end
+    ";
+    let binary = mrbc_compile("reopen_class", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 36);
+}
+
+#[test]
+fn superclass_mismatch_test() {
+    let code = "
+    class Base; end
+    class Other; end
+    class Child < Base; end
+
+    RESULT = begin
+      class Child < Other; end
+      \"reopened\"
+    rescue TypeError => e
+      e.message
+    end
+
+    def test_main
+      RESULT
+    end
+    ";
+    let binary = mrbc_compile("superclass_mismatch", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "superclass mismatch for class Child");
+}
@@ -0,0 +1,191 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn kwargs_test() {
+    let code = "
+    def calc(a, opt: 1, req:)
+      a * 100 + opt * 10 + req
+    end
+
+    def test_main
+      calc(1, req: 3) + calc(2, opt: 5, req: 4)
+    end
+    ";
+    let binary = mrbc_compile("kwargs", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 113 + 254);
+}
+
+#[test]
+fn kwargs_rest_test() {
+    let code = "
+    def config(name, **opts)
+      opts[:size]
+    end
+
+    def test_main
+      h = {size: 42}
+      config(\"bot\", **h)
+    end
+    ";
+    let binary = mrbc_compile("kwargs_rest", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn kwargs_as_hash_test() {
+    let code = "
+    def config(opts)
+      opts[:size]
+    end
+
+    def test_main
+      config(size: 7)
+    end
+    ";
+    let binary = mrbc_compile("kwargs_as_hash", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 7);
+}
+
+#[test]
+fn kwargs_unknown_keyword_test() {
+    let code = "
+    def calc(opt: 1)
+      opt
+    end
+
+    def test_main
+      calc(other: 2)
+    end
+    ";
+    let binary = mrbc_compile("kwargs_unknown", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args)
+        .err();
+    assert_eq!(&result.unwrap().message(), "unknown keyword: :other");
+}
+
+#[test]
+fn kwargs_missing_keyword_test() {
+    let code = "
+    def calc(req:)
+      req
+    end
+
+    def test_main
+      begin
+        calc
+      rescue ArgumentError => e
+        e.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("kwargs_missing", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "missing keyword: :req");
+}
+
+#[test]
+fn optional_args_test() {
+    let code = "
+    def calc(a, b = 10, c = 20)
+      a + b + c
+    end
+
+    def test_main
+      calc(1) + calc(1, 2) + calc(1, 2, 3)
+    end
+    ";
+    let binary = mrbc_compile("optional_args", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 31 + 23 + 6);
+}
+
+#[test]
+fn lambda_kwargs_test() {
+    let code = "
+    def test_main
+      mul = ->(x, y: 3) { x * y }
+      mul.call(2, y: 5) + mul[2]
+    end
+    ";
+    let binary = mrbc_compile("lambda_kwargs", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 10 + 6);
+}
+
+#[test]
+fn yield_kwargs_test() {
+    let code = "
+    def f
+      yield(1, k: 2)
+    end
+
+    def test_main
+      f { |a, k: 0| a + k }
+    end
+    ";
+    let binary = mrbc_compile("yield_kwargs", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 3);
+}
@@ -0,0 +1,63 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn int_literal_test() {
+    let code = "
+    def test_main
+      [100000, -100000, 2147483647, -2147483648, 1099511627776, -4000000000]
+    end
+    ";
+    let binary = mrbc_compile("int_literal", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: Vec<std::rc::Rc<mrubyedge::yamrb::value::RObject>> = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    let result: Vec<i64> = result.iter().map(|v| v.as_ref().try_into().unwrap()).collect();
+    assert_eq!(result, vec![100000, -100000, 2147483647, -2147483648, 1099511627776, -4000000000]);
+}
+
+#[test]
+fn float_literal_test() {
+    let code = "
+    def test_main
+      3.14
+    end
+    ";
+    let binary = mrbc_compile("float_literal", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: f32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 3.14);
+}
+
+#[test]
+fn bigint_literal_test() {
+    let code = "
+    def test_main
+      123456789012345678901234567890.to_s
+    end
+    ";
+    let binary = mrbc_compile("bigint_literal", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "123456789012345678901234567890");
+}
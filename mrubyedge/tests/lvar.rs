@@ -0,0 +1,46 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn lvar_names_test() {
+    let code = "
+    a = 1
+    b = 2
+    def add(x, y)
+      z = x + y
+      z
+    end
+    ";
+    let binary = mrbc_compile("lvar", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+
+    // Assert
+    let top = &vm.irep;
+    assert_eq!(top.local_variables(), vec!["a", "b"]);
+    assert_eq!(top.local_variable_name(1), Some("a"));
+    assert_eq!(top.local_variable_name(0), None);
+    let add = &top.reps[0];
+    assert_eq!(add.local_variables(), vec!["x", "y", "z"]);
+}
+
+#[test]
+fn lvar_block_test() {
+    let code = "
+    def test_main
+      [1].each do |item|
+        doubled = item * 2
+      end
+    end
+    ";
+    let binary = mrbc_compile("lvar_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+
+    // Assert
+    let block = &vm.irep.reps[0].reps[0];
+    assert_eq!(block.local_variables(), vec!["item", "doubled"]);
+}
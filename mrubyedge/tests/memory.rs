@@ -0,0 +1,166 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn memory_limit_array_test() {
+    let code = "
+    def grow
+      a = []
+      while true
+        a.push(1)
+      end
+    end
+
+    def test_main
+      begin
+        grow
+      rescue NoMemoryError
+        \"nomem\"
+      end
+    end
+    ";
+    let binary = mrbc_compile("memory_limit_array", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_memory_limit(Some(vm.memory_usage() + 100_000));
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "nomem");
+}
+
+#[test]
+fn memory_limit_string_test() {
+    let code = "
+    def test_main
+      s = \"x\"
+      while true
+        s = \"#{s}#{s}\"
+      end
+    end
+    ";
+    let binary = mrbc_compile("memory_limit_string", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_memory_limit(Some(vm.memory_usage() + 1_000_000));
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args);
+    assert!(result.unwrap_err().is_instance_of(vm.get_class_by_name("NoMemoryError")));
+}
+
+#[test]
+fn memory_limit_string_repeat_test() {
+    let code = "
+    def small
+      \"ab\" * 3
+    end
+
+    def test_main
+      \"x\" * 10**9
+    end
+    ";
+    let binary = mrbc_compile("memory_limit_string_repeat", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_memory_limit(Some(vm.memory_usage() + 1_000_000));
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "small", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "ababab");
+    let result = mrb_funcall(&mut vm, None, "test_main", &args);
+    assert!(result.unwrap_err().is_instance_of(vm.get_class_by_name("NoMemoryError")));
+}
+
+#[test]
+fn memory_limit_shared_memory_test() {
+    let code = "
+    def test_main
+      SharedMemory.new(100_000_000)
+    end
+    ";
+    let binary = mrbc_compile("memory_limit_shared_memory", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_memory_limit(Some(1_000_000));
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args);
+    assert!(result.unwrap_err().is_instance_of(vm.get_class_by_name("NoMemoryError")));
+}
+
+#[test]
+fn memory_usage_test() {
+    let code = "
+    def test_main
+      a = []
+      i = 0
+      while i < 1000
+        a.push(\"item\")
+        i += 1
+      end
+      a
+    end
+    ";
+    let binary = mrbc_compile("memory_usage", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let before = vm.memory_usage();
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args).unwrap();
+    assert!(vm.memory_usage() >= before + 1000 * 4);
+    drop(result);
+    assert!(vm.memory_usage() < before + 1000, "{} {}", before, vm.memory_usage());
+}
+
+#[test]
+fn memory_usage_two_vms_test() {
+    let code = "
+    def test_main
+      a = []
+      i = 0
+      while i < 1000
+        a.push(\"item\")
+        i += 1
+      end
+      a
+    end
+    ";
+    let binary = mrbc_compile("memory_usage_two_vms", code);
+    let mut rite_a = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm_a = mrubyedge::yamrb::vm::VM::open(&mut rite_a);
+    vm_a.run().unwrap();
+    let mut rite_b = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm_b = mrubyedge::yamrb::vm::VM::open(&mut rite_b);
+    vm_b.run().unwrap();
+
+    // Assert
+    let before_a = vm_a.memory_usage();
+    let before_b = vm_b.memory_usage();
+    let args = vec![];
+    let result_a = mrb_funcall(&mut vm_a, None, "test_main", &args).unwrap();
+    assert!(vm_a.memory_usage() >= before_a + 1000 * 4);
+    assert_eq!(vm_b.memory_usage(), before_b);
+    let result_b = mrb_funcall(&mut vm_b, None, "test_main", &args).unwrap();
+    drop(result_a);
+    assert!(vm_a.memory_usage() < before_a + 1000, "{} {}", before_a, vm_a.memory_usage());
+    assert!(vm_b.memory_usage() >= before_b + 1000 * 4);
+    drop(result_b);
+    assert!(vm_b.memory_usage() < before_b + 1000, "{} {}", before_b, vm_b.memory_usage());
+}
@@ -0,0 +1,141 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn method_cache_invalidation_test() {
+    let code = "
+    class A
+      def value
+        1
+      end
+    end
+    class B < A
+    end
+
+    def call_it(o)
+      o.value
+    end
+
+    $r1 = call_it(B.new)
+    class A
+      def value
+        2
+      end
+    end
+    $r2 = call_it(B.new)
+
+    module M
+      def value
+        3
+      end
+    end
+    class B
+      include M
+    end
+    $r3 = call_it(B.new)
+
+    module P
+      def value
+        4
+      end
+    end
+    class B
+      prepend P
+    end
+    $r4 = call_it(B.new)
+
+    b = B.new
+    def b.value
+      5
+    end
+    $r5 = call_it(b)
+    $r6 = call_it(B.new)
+
+    def test_main
+      $r1 * 100000 + $r2 * 10000 + $r3 * 1000 + $r4 * 100 + $r5 * 10 + $r6
+    end
+    ";
+    let binary = mrbc_compile("method_cache_invalidation", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 123454);
+}
+
+#[test]
+fn method_cache_polymorphic_test() {
+    let code = "
+    class Dog
+      def speak
+        \"woof\"
+      end
+    end
+    class Cat
+      def speak
+        \"meow\"
+      end
+    end
+
+    def speak(o)
+      o.speak
+    end
+
+    def test_main
+      s = \"\"
+      [Dog.new, Cat.new, Dog.new, Cat.new].each do |o|
+        s = s + speak(o)
+      end
+      s
+    end
+    ";
+    let binary = mrbc_compile("method_cache_polymorphic", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "woofmeowwoofmeow");
+}
+
+#[test]
+fn method_cache_per_vm_test() {
+    let code = "
+    class A
+      def value
+        1
+      end
+    end
+
+    def test_main
+      A.new.value
+    end
+    ";
+    let binary = mrbc_compile("method_cache_per_vm", code);
+    let mut rite_a = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm_a = mrubyedge::yamrb::vm::VM::open(&mut rite_a);
+    vm_a.run().unwrap();
+    let state = vm_a.method_state;
+
+    // defining methods in another VM keeps the caches of this one
+    let mut rite_b = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm_b = mrubyedge::yamrb::vm::VM::open(&mut rite_b);
+    vm_b.run().unwrap();
+
+    // Assert
+    assert_eq!(vm_a.method_state, state);
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm_a, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+}
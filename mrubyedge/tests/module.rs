@@ -0,0 +1,207 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn include_test() {
+    let code = "
+    module Greet
+      def greet
+        \"Hello, \" + name
+      end
+    end
+
+    class Bot
+      include Greet
+
+      def name
+        \"bot\"
+      end
+    end
+
+    def test_main
+      Bot.new.greet
+    end
+    ";
+    let binary = mrbc_compile("include", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "Hello, bot");
+}
+
+#[test]
+fn method_resolution_order_test() {
+    let code = "
+    module A
+      def who
+        \"a\"
+      end
+    end
+
+    module B
+      def who
+        \"b+\" + super
+      end
+    end
+
+    module P
+      def who
+        \"p+\" + super
+      end
+    end
+
+    class Base
+      def who
+        \"base\"
+      end
+    end
+
+    class Bot < Base
+      include A
+      include B
+      prepend P
+
+      def who
+        \"bot+\" + super
+      end
+    end
+
+    def test_main
+      Bot.new.who
+    end
+
+    def test_ancestors
+      Bot.ancestors.size
+    end
+    ";
+    let binary = mrbc_compile("method_resolution_order", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "p+bot+b+a");
+
+    // [P, Bot, B, A, Base, Object]
+    let result: i32 = mrb_funcall(&mut vm, None, "test_ancestors", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 6);
+}
+
+#[test]
+fn extend_test() {
+    let code = "
+    module Factory
+      def build
+        new
+      end
+    end
+
+    class Bot
+      extend Factory
+
+      def name
+        \"bot\"
+      end
+    end
+
+    class SubBot < Bot
+      def name
+        \"sub\"
+      end
+    end
+
+    def test_main
+      Bot.build.name + SubBot.build.name
+    end
+    ";
+    let binary = mrbc_compile("extend", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "botsub");
+}
+
+#[test]
+fn extend_instance_test() {
+    let code = "
+    module Size
+      def size
+        42
+      end
+    end
+
+    def test_main
+      o = Object.new
+      o.extend(Size)
+      o.size
+    end
+    ";
+    let binary = mrbc_compile("extend_instance", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn nested_class_test() {
+    let code = "
+    module A
+      class B
+        def hi
+          \"a\"
+        end
+      end
+    end
+
+    class B
+      def hi
+        \"top\"
+      end
+    end
+
+    module A
+      class B
+        def bye
+          \"bye\"
+        end
+      end
+    end
+
+    def test_main
+      A::B.new.hi + B.new.hi + A::B.new.bye
+    end
+    ";
+    let binary = mrbc_compile("nested_class", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "atopbye");
+}
@@ -0,0 +1,17 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn mrblib_is_compiled_test() {
+    // the binary loaded by the prelude is the one of the source
+    let code = include_str!("../src/yamrb/prelude/mrblib/iterator.rb");
+    let binary = mrbc_compile("mrblib_iterator", code);
+    if std::env::var("MRBLIB_WRITE").is_ok() {
+        std::fs::write("src/yamrb/prelude/mrblib/iterator.mrb", &binary).unwrap();
+    }
+    let expected = include_bytes!("../src/yamrb/prelude/mrblib/iterator.mrb");
+    assert!(binary == expected, "compile iterator.rb to iterator.mrb");
+}
@@ -191,4 +191,86 @@ fn rescue_nest_nest_test() {
     let result: String = mrb_funcall(&mut vm, None, "test_raise_parent", &args)
         .unwrap().as_ref().try_into().unwrap();
     assert_eq!(&result, "rescue: Intentional Error 4b");
-}
\ No newline at end of file
+}
+
+#[test]
+fn rescue_in_rescue_test() {
+    let code = "
+    def test_main
+      log = []
+      begin
+        begin
+          raise \"inner\"
+        rescue => e
+          log.push(e.message)
+          raise \"again\"
+        end
+      rescue => e
+        log.push(e.message)
+      end
+      log[0] + log[1]
+    end
+    ";
+    let binary = mrbc_compile("rescue_in_rescue", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "inneragain");
+}
+
+#[test]
+fn raise_after_begin_block_test() {
+    let code = "
+    def test_main
+      begin
+        1
+      rescue
+        \"NG\"
+      end
+      raise \"outside\"
+    rescue => e
+      e.message
+    end
+    ";
+    let binary = mrbc_compile("raise_after_begin_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "outside");
+}
+
+#[test]
+fn retry_test() {
+    let code = "
+    def test_main
+      n = 0
+      begin
+        n += 1
+        raise \"not yet\" if n < 3
+        n
+      rescue
+        retry
+      end
+    end
+    ";
+    let binary = mrbc_compile("retry", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 3);
+}
@@ -0,0 +1,210 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn class_method_test() {
+    let code = "
+    class Point
+      def self.origin
+        new.set(0, 0)
+      end
+
+      def set(x, y)
+        @x = x
+        @y = y
+        self
+      end
+
+      def sum
+        @x + @y + 1
+      end
+    end
+
+    def test_main
+      Point.origin.sum
+    end
+    ";
+    let binary = mrbc_compile("class_method", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn class_self_block_test() {
+    let code = "
+    class Counter
+      class << self
+        def double(n)
+          n * 2
+        end
+      end
+    end
+
+    class SubCounter < Counter
+    end
+
+    def test_main
+      Counter.double(3) + SubCounter.double(5)
+    end
+    ";
+    let binary = mrbc_compile("class_self_block", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 16);
+}
+
+#[test]
+fn define_singleton_method_test() {
+    let code = "
+    def test_main
+      base = 100
+      obj = Object.new
+      obj.define_singleton_method(:add) do |n|
+        base + n
+      end
+      obj.add(23)
+    end
+    ";
+    let binary = mrbc_compile("define_singleton_method", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 123);
+}
+
+#[test]
+fn singleton_method_is_per_object_test() {
+    let code = "
+    class Item
+    end
+
+    def test_main
+      a = Item.new
+      b = Item.new
+      def a.special
+        1
+      end
+      begin
+        b.special
+      rescue NoMethodError
+        a.special + 1
+      end
+    end
+    ";
+    let binary = mrbc_compile("singleton_per_object", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 2);
+}
+
+#[test]
+fn class_instance_variable_test() {
+    let code = "
+    class Counter
+      @count = 5
+
+      def self.bump
+        @count += 1
+      end
+
+      def self.unset
+        @unset
+      end
+    end
+
+    def test_main
+      Counter.bump
+      Counter.unset.nil? ? Counter.bump : 0
+    end
+    ";
+    let binary = mrbc_compile("class_ivar", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 7);
+}
+
+#[test]
+fn singleton_attr_accessor_test() {
+    let code = "
+    class Foo
+      class << self
+        attr_accessor :conf
+      end
+    end
+
+    def test_main
+      Foo.conf = 9
+      Foo.conf
+    end
+    ";
+    let binary = mrbc_compile("singleton_attr_accessor", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 9);
+}
+
+#[test]
+fn singleton_on_string_test() {
+    let code = "
+    def test_main
+      str = \"bot\"
+      begin
+        def str.x
+          1
+        end
+        0
+      rescue TypeError => e
+        e.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("singleton_on_string", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(&result, "can't define singleton");
+}
@@ -0,0 +1,149 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+use std::rc::Rc;
+
+use mrubyedge::yamrb::helpers::mrb_define_cmethod;
+use mrubyedge::yamrb::value::RObject;
+use mrubyedge::yamrb::vm::VM;
+use mrubyedge::Error;
+
+fn host_value(_vm: &mut VM, _args: &[Rc<RObject>]) -> Result<Rc<RObject>, Error> {
+    Ok(Rc::new(RObject::integer(7)))
+}
+
+fn open_with_host_fn(binary: &[u8]) -> VM {
+    let mut rite = mrubyedge::rite::load(binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+    let kernel = vm.object_class.clone();
+    mrb_define_cmethod(&mut vm, kernel, "host_value", Box::new(host_value));
+    vm
+}
+
+#[test]
+fn snapshot_restore_test() {
+    let code = "
+    module Greeter
+      def greet
+        \"hi \" + name
+      end
+    end
+
+    class Person
+      include Greeter
+      attr_reader :name
+      @kind = \"human\"
+
+      def self.kind
+        @kind
+      end
+
+      def initialize(name)
+        @name = name
+        @friends = []
+      end
+
+      def befriend(other)
+        @friends.push(other)
+        other.add_friend(self)
+      end
+
+      def add_friend(other)
+        @friends.push(other)
+      end
+
+      def friend_count
+        @friends.size
+      end
+    end
+
+    LIMIT = 42
+    $alice = Person.new(\"alice\")
+    $bob = Person.new(\"bob\")
+    $alice.befriend($bob)
+    $table = {a: [1, 2.5, \"three\", :four], b: 100000000000000000000}
+    $calls = 0
+
+    def test_main
+      $calls += 1
+      $alice.greet + \"/\" + $bob.friend_count.to_s + \"/\" + LIMIT.to_s + \"/\" + $table[:a][2] + \"/\" + $table[:b].to_s + \"/\" + host_value.to_s + \"/\" + $calls.to_s + \"/\" + Person.kind
+    end
+    ";
+    let binary = mrbc_compile("snapshot_restore", code);
+    let mut vm = open_with_host_fn(&binary);
+    vm.run().unwrap();
+    let args = vec![];
+    mrb_funcall(&mut vm, None, "test_main", &args).unwrap();
+    let snapshot = vm.snapshot().unwrap();
+
+    // the top level is not run again
+    let mut restored = open_with_host_fn(&binary);
+    restored.restore(&snapshot).unwrap();
+
+    // Assert
+    let result: String = mrb_funcall(&mut restored, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "hi alice/1/42/three/100000000000000000000/7/2/human");
+}
+
+#[test]
+fn snapshot_closure_test() {
+    let code = "
+    def capture(&block)
+      block
+    end
+
+    def make_counter
+      count = 0
+      capture { count += 1 }
+    end
+
+    $counter = make_counter
+    $counter.call
+    $to_s = :to_s.to_proc
+
+    def test_main
+      $to_s.call($counter.call)
+    end
+    ";
+    let binary = mrbc_compile("snapshot_closure", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+    vm.run().unwrap();
+    let snapshot = vm.snapshot().unwrap();
+
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut restored = VM::open(&mut rite);
+    restored.restore(&snapshot).unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut restored, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "2");
+    let result: String = mrb_funcall(&mut restored, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "3");
+}
+
+#[test]
+fn snapshot_other_program_test() {
+    let binary = mrbc_compile("snapshot_program_a", "$a = 1");
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+    vm.run().unwrap();
+    let snapshot = vm.snapshot().unwrap();
+
+    let other = mrbc_compile("snapshot_program_b", "def foo\n  1\nend\n$b = foo");
+    let mut rite = mrubyedge::rite::load(&other).unwrap();
+    let mut restored = VM::open(&mut rite);
+
+    // Assert
+    let err = restored.restore(&snapshot).unwrap_err();
+    assert!(matches!(err, Error::InvalidSnapshot(_)));
+    let err = restored.restore(&snapshot[..10]).unwrap_err();
+    assert!(matches!(err, Error::InvalidSnapshot(_)));
+}
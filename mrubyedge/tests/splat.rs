@@ -0,0 +1,180 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn masgn_test() {
+    let code = "
+    def test_main
+      pair = [1, 2]
+      a, b = pair
+      c, *d, e = 3, 4, 5, 6
+      f, (g, h) = 7, [8, 9]
+      a + b + c + d[0] + d[1] + e + f + g + h
+    end
+    ";
+    let binary = mrbc_compile("masgn", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 45);
+}
+
+#[test]
+fn splat_call_test() {
+    let code = "
+    def sum3(a, b, c)
+      a * 100 + b * 10 + c
+    end
+
+    def test_main
+      args = [1, 2]
+      sum3(*args, 3)
+    end
+    ";
+    let binary = mrbc_compile("splat_call", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 123);
+}
+
+#[test]
+fn splat_array_literal_test() {
+    let code = "
+    def test_main
+      xs = [1, 2]
+      ys = [*xs, 3, *xs]
+      ys.size
+    end
+    ";
+    let binary = mrbc_compile("splat_array_literal", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 5);
+}
+
+#[test]
+fn rest_and_post_args_test() {
+    let code = "
+    def f(a, *rest, z)
+      a * 1000 + rest.size * 100 + z
+    end
+
+    def test_main
+      f(1, 2) + f(1, 2, 3, 4)
+    end
+    ";
+    let binary = mrbc_compile("rest_and_post_args", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1002 + 1204);
+}
+
+#[test]
+fn rest_args_arity_error_test() {
+    let code = "
+    def f(a, *rest, z)
+      a
+    end
+
+    def test_main
+      f(1)
+    end
+    ";
+    let binary = mrbc_compile("rest_args_arity_error", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args)
+        .err();
+    assert_eq!(&result.unwrap().message(), "wrong number of arguments (given 1, expected 2+)");
+}
+
+#[test]
+fn zsuper_with_rest_test() {
+    let code = "
+    class Base
+      def calc(*args)
+        args.size
+      end
+    end
+
+    class Child < Base
+      def calc(a, *rest)
+        super
+      end
+    end
+
+    def test_main
+      Child.new.calc(1, 2, 3)
+    end
+    ";
+    let binary = mrbc_compile("zsuper_with_rest", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 3);
+}
+
+#[test]
+fn lambda_arity_test() {
+    let code = "
+    def give_one
+      yield 1
+    end
+
+    def test_main
+      lenient = give_one { |a, b| b.nil? ? 1 : 0 } + [[1, 2]].each { |a, b| a + b }.size
+      strict = begin
+        ->(a, b) { a }.call(1)
+        0
+      rescue ArgumentError
+        10
+      end
+      lenient + strict
+    end
+    ";
+    let binary = mrbc_compile("lambda_arity", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i32 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 12);
+}
@@ -0,0 +1,153 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn deep_recursion_test() {
+    let code = "
+    def sum(n)
+      if n == 0
+        0
+      else
+        n + sum(n - 1)
+      end
+    end
+
+    def test_main
+      sum(5000)
+    end
+    ";
+    let binary = mrbc_compile("deep_recursion", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 12502500);
+}
+
+#[test]
+fn recursive_fib_test() {
+    let code = "
+    def fib(n)
+      if n < 2
+        n
+      else
+        fib(n - 1) + fib(n - 2)
+      end
+    end
+
+    def test_main
+      fib(20)
+    end
+    ";
+    let binary = mrbc_compile("recursive_fib", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 6765);
+}
+
+#[test]
+fn stack_level_too_deep_rescue_test() {
+    let code = "
+    def forever(n)
+      forever(n + 1)
+    end
+
+    def test_main
+      begin
+        forever(0)
+      rescue SystemStackError => e
+        e.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("stack_level_too_deep_rescue", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "stack level too deep");
+}
+
+#[test]
+fn max_call_depth_test() {
+    let code = "
+    def depth(n)
+      if n == 0
+        0
+      else
+        1 + depth(n - 1)
+      end
+    end
+
+    def test_main(n)
+      depth(n)
+    end
+    ";
+    let binary = mrbc_compile("max_call_depth", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    vm.set_max_call_depth(100);
+    let args = vec![int(50)];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 50);
+
+    let args = vec![int(200)];
+    let result = mrb_funcall(&mut vm, None, "test_main", &args);
+    assert!(result.unwrap_err().is_instance_of(vm.get_class_by_name("SystemStackError")));
+}
+
+#[test]
+fn native_nesting_test() {
+    let code = "
+    def d(n)
+      return 0 if n == 0
+      r = 0
+      {a: 1}.each { r = d(n - 1) }
+      r
+    end
+
+    def test_main(n)
+      begin
+        d(n)
+      rescue SystemStackError => e
+        e.message
+      end
+    end
+    ";
+    let binary = mrbc_compile("native_nesting", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![int(50)];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 0);
+
+    let args = vec![int(1000)];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "stack level too deep");
+}
@@ -0,0 +1,268 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+use mrubyedge::Error;
+use mrubyedge::yamrb::vm::{StepResult, VM};
+
+fn step_to_end(vm: &mut VM, n: u64) -> (StepResult, usize) {
+    let mut yields = 0;
+    loop {
+        match vm.step(n) {
+            StepResult::Yielded => yields += 1,
+            res => return (res, yields),
+        }
+    }
+}
+
+#[test]
+fn step_resume_test() {
+    let code = "
+    $count = 0
+    while $count < 100
+      $count += 1
+    end
+    $count * 2
+    ";
+    let binary = mrbc_compile("step_resume", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+
+    // Assert
+    assert!(matches!(vm.step(50), StepResult::Yielded));
+    let count: i64 = vm.globals["$count"].as_ref().try_into().unwrap();
+    assert!(count > 0 && count < 100, "{}", count);
+
+    let (res, yields) = step_to_end(&mut vm, 50);
+    assert!(yields > 1);
+    let StepResult::Finished(value) = res else {
+        panic!("not finished: {:?}", res);
+    };
+    let value: i64 = value.as_ref().try_into().unwrap();
+    assert_eq!(value, 200);
+}
+
+#[test]
+fn step_interleave_test() {
+    let code_a = "
+    $log = \"\"
+    i = 0
+    while i < 20
+      $log = $log + \"a\"
+      i += 1
+    end
+    $log
+    ";
+    let code_b = "
+    i = 0
+    sum = 0
+    while i < 30
+      sum += i
+      i += 1
+    end
+    sum
+    ";
+    let binary_a = mrbc_compile("step_interleave_a", code_a);
+    let binary_b = mrbc_compile("step_interleave_b", code_b);
+    let mut rite_a = mrubyedge::rite::load(&binary_a).unwrap();
+    let mut rite_b = mrubyedge::rite::load(&binary_b).unwrap();
+    let mut vm_a = VM::open(&mut rite_a);
+    let mut vm_b = VM::open(&mut rite_b);
+
+    let mut result_a = None;
+    let mut result_b = None;
+    while result_a.is_none() || result_b.is_none() {
+        if result_a.is_none() {
+            if let StepResult::Finished(v) = vm_a.step(7) {
+                result_a = Some(v);
+            }
+        }
+        if result_b.is_none() {
+            if let StepResult::Finished(v) = vm_b.step(7) {
+                result_b = Some(v);
+            }
+        }
+    }
+
+    // Assert
+    let log: String = result_a.unwrap().as_ref().try_into().unwrap();
+    assert_eq!(log, "a".repeat(20));
+    let sum: i64 = result_b.unwrap().as_ref().try_into().unwrap();
+    assert_eq!(sum, 435);
+}
+
+#[test]
+fn step_rescue_test() {
+    let code = "
+    $log = \"\"
+    def check(i)
+      if i == 2
+        raise \"two\"
+      end
+      i
+    end
+
+    i = 0
+    while i < 4
+      begin
+        check(i)
+        $log = $log + i.to_s
+      rescue => e
+        $log = $log + \"!\"
+      ensure
+        $log = $log + \";\"
+      end
+      i += 1
+    end
+    3.times do |j|
+      $log = $log + j.to_s
+    end
+    $log
+    ";
+    let binary = mrbc_compile("step_rescue", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+
+    // Assert
+    let (res, yields) = step_to_end(&mut vm, 1);
+    assert!(yields > 10);
+    let StepResult::Finished(value) = res else {
+        panic!("not finished: {:?}", res);
+    };
+    let log: String = value.as_ref().try_into().unwrap();
+    assert_eq!(log, "0;1;!;3;012");
+}
+
+#[test]
+fn step_raised_test() {
+    let code = "
+    i = 0
+    while i < 10
+      i += 1
+    end
+    raise \"boom\"
+    ";
+    let binary = mrbc_compile("step_raised", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+
+    // Assert
+    let (res, _) = step_to_end(&mut vm, 5);
+    let StepResult::Raised(err) = res else {
+        panic!("not raised: {:?}", res);
+    };
+    assert_eq!(err.message(), "boom");
+}
+
+#[test]
+fn step_after_finished_test() {
+    let code = "
+    $count = 0
+    3.times { $count += 1 }
+    $count * 10
+    ";
+    let binary = mrbc_compile("step_after_finished", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+
+    // Assert
+    let (res, _) = step_to_end(&mut vm, 4);
+    let StepResult::Finished(value) = res else {
+        panic!("not finished: {:?}", res);
+    };
+    let value: i32 = value.as_ref().try_into().unwrap();
+    assert_eq!(value, 30);
+
+    let StepResult::Finished(again) = vm.step(4) else {
+        panic!("not finished again");
+    };
+    let again: i32 = again.as_ref().try_into().unwrap();
+    assert_eq!(again, 30);
+}
+
+#[test]
+fn step_after_raised_test() {
+    let code = "
+    $n = 1
+    boom
+    $n = 100
+    ";
+    let binary = mrbc_compile("step_after_raised", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+
+    // Assert
+    let (res, _) = step_to_end(&mut vm, 2);
+    let StepResult::Raised(err) = res else {
+        panic!("not raised: {:?}", res);
+    };
+    assert_eq!(err, Error::NoMethodError("boom".to_string()));
+
+    let StepResult::Raised(again) = vm.step(2) else {
+        panic!("not raised again");
+    };
+    assert_eq!(again, err);
+    let n: i32 = vm.globals["$n"].as_ref().try_into().unwrap();
+    assert_eq!(n, 1);
+}
+
+#[test]
+fn step_in_iterator_test() {
+    let code = "
+    $count = 0
+    100.times { $count += 1 }
+    [1, 2, 3].each { |i| $count += i }
+    $count
+    ";
+    let binary = mrbc_compile("step_in_iterator", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+
+    // Assert
+    assert!(matches!(vm.step(50), StepResult::Yielded));
+    let count: i64 = vm.globals["$count"].as_ref().try_into().unwrap();
+    assert!(count > 0 && count < 100, "{}", count);
+
+    let (res, _) = step_to_end(&mut vm, 50);
+    let StepResult::Finished(value) = res else {
+        panic!("not finished: {:?}", res);
+    };
+    let value: i32 = value.as_ref().try_into().unwrap();
+    assert_eq!(value, 106);
+}
+
+#[test]
+fn step_overrun_test() {
+    let code = "
+    class Counter
+      def initialize(n)
+        @n = 0
+        while @n < n
+          @n += 1
+        end
+      end
+    end
+
+    $c = Counter.new(10)
+    {k: 1}.each do |k, v|
+      while true
+      end
+    end
+    ";
+    let binary = mrbc_compile("step_overrun", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = VM::open(&mut rite);
+    vm.set_max_step_overrun(1000);
+
+    // Assert
+    // initialize called by new runs past the steps within the limit
+    let (res, yields) = step_to_end(&mut vm, 5);
+    assert!(yields > 1);
+    assert!(vm.globals.contains_key("$c"));
+    let StepResult::Raised(err) = res else {
+        panic!("not raised: {:?}", res);
+    };
+    assert_eq!(err, Error::OutOfFuel);
+}
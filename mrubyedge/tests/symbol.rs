@@ -0,0 +1,162 @@
+extern crate mec_mrbc_sys;
+extern crate mrubyedge;
+
+mod helpers;
+use helpers::*;
+
+#[test]
+fn symbol_object_id_test() {
+    let code = "
+    def test_main
+      a = :foo
+      b = :foo
+      c = :bar
+      if a.object_id == c.object_id
+        \"collided\"
+      elsif a.object_id == b.object_id
+        \"stable\"
+      else
+        \"unstable\"
+      end
+    end
+    ";
+    let binary = mrbc_compile("symbol_object_id", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "stable");
+}
+
+#[test]
+fn symbol_to_proc_test() {
+    let code = "
+    def apply(x)
+      yield x
+    end
+
+    def test_main
+      apply(12, &:to_s) + :to_s.to_proc.call(3)
+    end
+    ";
+    let binary = mrbc_compile("symbol_to_proc", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "123");
+}
+
+#[test]
+fn symbol_cmp_test() {
+    let code = "
+    def test_main
+      (:b <=> :a) * 100 + (:a <=> :b) * 10 + (:a <=> :a)
+    end
+    ";
+    let binary = mrbc_compile("symbol_cmp", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 90);
+}
+
+#[test]
+fn symbol_all_symbols_test() {
+    let code = "
+    def test_main
+      found = 0
+      Symbol.all_symbols.each do |sym|
+        found += 1 if sym == :a_symbol_only_in_this_test
+      end
+      found
+    end
+    ";
+    let binary = mrbc_compile("symbol_all_symbols", code);
+    let mut rite = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm = mrubyedge::yamrb::vm::VM::open(&mut rite);
+    vm.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: i64 = mrb_funcall(&mut vm, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+
+    let sym = vm.symbols.intern("a_symbol_only_in_this_test");
+    assert_eq!(vm.symbols.name(sym.id).unwrap(), "a_symbol_only_in_this_test");
+    assert_eq!(vm.symbols.intern("a_symbol_only_in_this_test").id, sym.id);
+}
+
+#[test]
+fn symbol_two_vms_test() {
+    let code_a = "
+    def test_main
+      :from_a.to_s
+    end
+    ";
+    let code_b = "
+    def other_main
+      :from_b.to_s
+    end
+    ";
+    let binary_a = mrbc_compile("symbol_two_vms_a", code_a);
+    let mut rite_a = mrubyedge::rite::load(&binary_a).unwrap();
+    let mut vm_a = mrubyedge::yamrb::vm::VM::open(&mut rite_a);
+    vm_a.run().unwrap();
+    let binary_b = mrbc_compile("symbol_two_vms_b", code_b);
+    let mut rite_b = mrubyedge::rite::load(&binary_b).unwrap();
+    let mut vm_b = mrubyedge::yamrb::vm::VM::open(&mut rite_b);
+    vm_b.run().unwrap();
+
+    // Assert
+    let args = vec![];
+    let result: String = mrb_funcall(&mut vm_a, None, "test_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "from_a");
+    let result: String = mrb_funcall(&mut vm_b, None, "other_main", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, "from_b");
+}
+
+#[test]
+fn symbol_arg_across_vms_test() {
+    use mrubyedge::yamrb::value::{RObject, RSym};
+
+    let code = "
+    def t(x)
+      x == :foo ? 1 : 0
+    end
+    ";
+    let binary = mrbc_compile("symbol_arg_across_vms", code);
+    let mut rite1 = mrubyedge::rite::load(&binary).unwrap();
+    let mut vm1 = mrubyedge::yamrb::vm::VM::open(&mut rite1);
+    vm1.run().unwrap();
+    let other = mrbc_compile("symbol_arg_other_vm", "def a; [:b, :c, :d, :e, :foo]; end");
+    let mut rite2 = mrubyedge::rite::load(&other).unwrap();
+    let mut vm2 = mrubyedge::yamrb::vm::VM::open(&mut rite2);
+    vm2.run().unwrap();
+
+    // Assert
+    let args = vec![RObject::symbol(RSym::from("foo")).into_rc()];
+    let result: i32 = mrb_funcall(&mut vm1, None, "t", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+    let args = vec![RObject::symbol(vm1.intern("foo")).into_rc()];
+    let result: i32 = mrb_funcall(&mut vm1, None, "t", &args)
+        .unwrap().as_ref().try_into().unwrap();
+    assert_eq!(result, 1);
+}